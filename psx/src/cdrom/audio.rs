@@ -0,0 +1,151 @@
+use super::{from_bcd, CdRom, DriveStat, Error, Msf, Result};
+use crate::hw::cdrom;
+use crate::hw::cdrom::{Command, Idx};
+use crate::hw::Register;
+
+/// The mix of the CD audio outputs into the SPU's CD audio inputs.
+///
+/// Each volume ranges from 0 to 255 where [`Volume::NORMAL`] (0x80) passes the
+/// output through unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Volume {
+    /// The left output to left input volume.
+    pub left_to_left: u8,
+    /// The left output to right input volume.
+    pub left_to_right: u8,
+    /// The right output to right input volume.
+    pub right_to_right: u8,
+    /// The right output to left input volume.
+    pub right_to_left: u8,
+}
+
+impl Volume {
+    /// The volume which passes each channel through unchanged.
+    pub const NORMAL: u8 = 0x80;
+
+    /// Creates a stereo mix with the given left and right volumes.
+    pub const fn stereo(left: u8, right: u8) -> Self {
+        Volume {
+            left_to_left: left,
+            left_to_right: 0,
+            right_to_right: right,
+            right_to_left: 0,
+        }
+    }
+
+    /// Creates a mix sending both outputs to both inputs at half the given
+    /// volume.
+    pub const fn mono(volume: u8) -> Self {
+        let half = volume / 2;
+        Volume {
+            left_to_left: half,
+            left_to_right: half,
+            right_to_right: half,
+            right_to_left: half,
+        }
+    }
+
+    /// Creates a mix with all volumes set to zero.
+    pub const fn silent() -> Self {
+        Volume::stereo(0, 0)
+    }
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Volume::stereo(Volume::NORMAL, Volume::NORMAL)
+    }
+}
+
+impl CdRom {
+    /// Starts playing CD-DA from `loc`.
+    pub fn play(&mut self, loc: Msf) -> Result<DriveStat> {
+        self.set_loc(loc)?;
+        Ok(self.command(Command::Play, &[])?.stat())
+    }
+
+    /// Starts playing a CD-DA track, checking that it exists on the disc.
+    ///
+    /// Playback continues into the following tracks unless auto pause is
+    /// enabled in the drive [`Mode`][super::Mode].
+    pub fn play_track(&mut self, track: u8) -> Result<DriveStat> {
+        let (first, last) = self.get_tn()?;
+        if track < first || track > last {
+            return Err(Error::NoSuchTrack)
+        }
+        let start = self.get_td(track)?;
+        self.play(start)
+    }
+
+    /// Sets the file and channel of the XA-ADPCM sectors which are played when
+    /// the XA filter is enabled in the drive [`Mode`][super::Mode].
+    pub fn set_filter(&mut self, file: u8, channel: u8) -> Result<DriveStat> {
+        Ok(self.command(Command::Setfilter, &[file, channel])?.stat())
+    }
+
+    /// Starts playing an interleaved XA-ADPCM channel from the sector at
+    /// `lba`.
+    ///
+    /// This enables XA-ADPCM output, the XA filter and double speed in the
+    /// drive mode. Data sectors in the stream are still signalled by
+    /// [`IntCause::DataReady`][cdrom::IntCause::DataReady] and should be
+    /// polled for.
+    pub fn play_xa(&mut self, lba: u32, file: u8, channel: u8) -> Result<DriveStat> {
+        self.set_filter(file, channel)?;
+        let mode = self
+            .mode()
+            .xa_adpcm(true)
+            .xa_filter(true)
+            .double_speed(true);
+        self.set_mode(mode)?;
+        self.set_loc(Msf::from_lba(lba))?;
+        self.read_s()
+    }
+
+    /// Mutes CD-DA and XA-ADPCM audio without stopping playback.
+    pub fn mute(&mut self) -> Result<DriveStat> {
+        Ok(self.command(Command::Mute, &[])?.stat())
+    }
+
+    /// Unmutes CD-DA and XA-ADPCM audio.
+    pub fn demute(&mut self) -> Result<DriveStat> {
+        Ok(self.command(Command::Demute, &[])?.stat())
+    }
+
+    /// Stops CD-DA playback and stops the motor.
+    pub fn stop(&mut self) -> Result<DriveStat> {
+        self.blocking_command(Command::Stop, &[])
+    }
+
+    /// Gets the track number which is currently being played.
+    pub fn current_track(&mut self) -> Result<u8> {
+        let response = self.command(Command::GetlocP, &[])?;
+        match response.bytes() {
+            [track, ..] => Ok(from_bcd(*track)),
+            _ => Err(Error::ShortResponse),
+        }
+    }
+
+    /// Sets how the CD audio outputs are mixed into the SPU's CD audio inputs.
+    /// XA-ADPCM audio is muted if `mute_adpcm` is true.
+    pub fn set_volume(&mut self, volume: Volume, mute_adpcm: bool) {
+        self.select(Idx::Idx2);
+        cdrom::LeftToLeft::skip_load()
+            .assign(volume.left_to_left)
+            .store();
+        cdrom::LeftToRight::skip_load()
+            .assign(volume.left_to_right)
+            .store();
+        self.select(Idx::Idx3);
+        cdrom::RightToRight::skip_load()
+            .assign(volume.right_to_right)
+            .store();
+        cdrom::RightToLeft::skip_load()
+            .assign(volume.right_to_left)
+            .store();
+        cdrom::VolumeApply::skip_load()
+            .apply_volume(mute_adpcm)
+            .store();
+        self.select(Idx::Idx0);
+    }
+}
@@ -0,0 +1,610 @@
+//! CD-ROM controller driver
+//!
+//! This module drives the CD-ROM controller directly through the registers in
+//! [`hw::cdrom`][crate::hw::cdrom] rather than through the BIOS. Commands are
+//! sent with their parameters and responses are read from the response FIFO as
+//! the controller signals them with its INT1-INT5 interrupts. Interrupts are
+//! polled from the controller's interrupt flag register so
+//! [`IRQ::CDROM`][crate::hw::irq::IRQ::CDROM] doesn't have to be enabled.
+
+use crate::dma;
+use crate::format::iso9660;
+use crate::format::iso9660::SectorRead;
+use crate::hw::cdrom;
+use crate::hw::cdrom::{Command, Idx, IntCause};
+use crate::hw::Register;
+
+mod audio;
+mod stream;
+
+pub use audio::Volume;
+pub use stream::Stream;
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// The maximum number of bytes in a controller response.
+pub const RESPONSE_LEN: usize = 16;
+
+// The number of sectors in the two second pregap before LBA 0.
+const PREGAP: u32 = 150;
+const SECTORS_PER_SECOND: u32 = 75;
+
+const CDDA: u8 = 0;
+const AUTO_PAUSE: u8 = 1;
+const REPORT: u8 = 2;
+const XA_FILTER: u8 = 3;
+const IGNORE_BIT: u8 = 4;
+const SECTOR_SIZE: u8 = 5;
+const XA_ADPCM: u8 = 6;
+const DOUBLE_SPEED: u8 = 7;
+
+/// A CD-ROM driver error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The controller responded with INT5.
+    Drive {
+        /// The drive's status when the error occurred.
+        stat: DriveStat,
+        /// The second response byte which is usually an error code.
+        code: u8,
+    },
+    /// The controller responded with an unexpected interrupt.
+    UnexpectedInterrupt(IntCause),
+    /// The response was shorter than expected for the command.
+    ShortResponse,
+    /// The buffer size isn't a multiple of the sector size.
+    BadBufferSize,
+    /// The track isn't on the disc.
+    NoSuchTrack,
+    /// The sector couldn't be transferred by DMA.
+    DMA(dma::Error),
+}
+
+/// Converts a binary number to binary-coded decimal.
+pub const fn to_bcd(x: u8) -> u8 {
+    ((x / 10) << 4) | (x % 10)
+}
+
+/// Converts a binary-coded decimal number to binary.
+pub const fn from_bcd(x: u8) -> u8 {
+    (x >> 4) * 10 + (x & 0xF)
+}
+
+/// A position on the disc in minutes, seconds and frames (i.e. sectors).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Msf {
+    /// Minutes from the start of the disc.
+    pub minute: u8,
+    /// Seconds from the start of the minute.
+    pub second: u8,
+    /// Frames from the start of the second ranging from 0 to 74.
+    pub frame: u8,
+}
+
+impl Msf {
+    /// Creates a new position on the disc.
+    pub const fn new(minute: u8, second: u8, frame: u8) -> Self {
+        Msf {
+            minute,
+            second,
+            frame,
+        }
+    }
+
+    /// Converts a logical block address to a position on the disc.
+    pub const fn from_lba(lba: u32) -> Self {
+        let sector = lba + PREGAP;
+        Msf {
+            minute: (sector / (60 * SECTORS_PER_SECOND)) as u8,
+            second: ((sector / SECTORS_PER_SECOND) % 60) as u8,
+            frame: (sector % SECTORS_PER_SECOND) as u8,
+        }
+    }
+
+    /// Converts a position on the disc to a logical block address. Positions
+    /// in the pregap saturate to LBA 0.
+    pub const fn to_lba(self) -> u32 {
+        let sector =
+            (self.minute as u32 * 60 + self.second as u32) * SECTORS_PER_SECOND + self.frame as u32;
+        sector.saturating_sub(PREGAP)
+    }
+
+    /// Converts the position to the BCD parameters expected by
+    /// [`Command::Setloc`].
+    pub const fn to_bcd(self) -> [u8; 3] {
+        [to_bcd(self.minute), to_bcd(self.second), to_bcd(self.frame)]
+    }
+
+    /// Creates a position from BCD minutes, seconds and frames.
+    pub const fn from_bcd([minute, second, frame]: [u8; 3]) -> Self {
+        Msf::new(from_bcd(minute), from_bcd(second), from_bcd(frame))
+    }
+}
+
+/// The drive status byte which is the first byte of most responses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DriveStat(pub u8);
+
+impl DriveStat {
+    /// Checks if the last command failed.
+    pub fn error(&self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    /// Checks if the spindle motor is on.
+    pub fn motor_on(&self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+
+    /// Checks if the last seek failed.
+    pub fn seek_error(&self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    /// Checks if reading the disc's license string failed.
+    pub fn id_error(&self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    /// Checks if the shell is open or was opened since the last `GetStat`.
+    pub fn shell_open(&self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+
+    /// Checks if the drive is reading data sectors.
+    pub fn reading(&self) -> bool {
+        self.0 & (1 << 5) != 0
+    }
+
+    /// Checks if the drive is seeking.
+    pub fn seeking(&self) -> bool {
+        self.0 & (1 << 6) != 0
+    }
+
+    /// Checks if the drive is playing CD-DA.
+    pub fn playing(&self) -> bool {
+        self.0 & (1 << 7) != 0
+    }
+}
+
+/// The number of bytes in each sector delivered to the data FIFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectorSize {
+    /// Only the 2048-byte user data area.
+    Bytes2048 = 0,
+    /// The whole sector except for the 12-byte sync pattern.
+    Bytes2340,
+}
+
+impl SectorSize {
+    /// The sector size in bytes.
+    pub const fn bytes(self) -> usize {
+        match self {
+            SectorSize::Bytes2048 => 2048,
+            SectorSize::Bytes2340 => 2340,
+        }
+    }
+
+    /// The sector size in 4-byte words.
+    pub const fn words(self) -> usize {
+        self.bytes() / 4
+    }
+}
+
+/// The drive mode set by [`CdRom::set_mode`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Mode(u8);
+
+impl Mode {
+    /// Creates a mode with all settings disabled and 2048-byte sectors.
+    pub const fn new() -> Self {
+        Mode(0)
+    }
+
+    const fn with(self, bit: u8, enabled: bool) -> Self {
+        Mode((self.0 & !(1 << bit)) | ((enabled as u8) << bit))
+    }
+
+    /// Allows reading CD-DA sectors.
+    pub const fn cdda(self, enabled: bool) -> Self {
+        self.with(CDDA, enabled)
+    }
+
+    /// Pauses CD-DA playback at the end of the track.
+    pub const fn auto_pause(self, enabled: bool) -> Self {
+        self.with(AUTO_PAUSE, enabled)
+    }
+
+    /// Enables INT1 position reports during CD-DA playback.
+    pub const fn report(self, enabled: bool) -> Self {
+        self.with(REPORT, enabled)
+    }
+
+    /// Only plays XA-ADPCM sectors matching the file and channel set by
+    /// [`Command::Setfilter`].
+    pub const fn xa_filter(self, enabled: bool) -> Self {
+        self.with(XA_FILTER, enabled)
+    }
+
+    /// Ignores the sector size and the position set by [`Command::Setloc`].
+    pub const fn ignore_bit(self, enabled: bool) -> Self {
+        self.with(IGNORE_BIT, enabled)
+    }
+
+    /// Sets the number of bytes per sector delivered to the data FIFO.
+    pub const fn sector_size(self, size: SectorSize) -> Self {
+        self.with(SECTOR_SIZE, size as u8 != 0)
+    }
+
+    /// Sends XA-ADPCM sectors to the SPU instead of the data FIFO.
+    pub const fn xa_adpcm(self, enabled: bool) -> Self {
+        self.with(XA_ADPCM, enabled)
+    }
+
+    /// Reads at double speed (150 sectors per second).
+    pub const fn double_speed(self, enabled: bool) -> Self {
+        self.with(DOUBLE_SPEED, enabled)
+    }
+
+    /// Gets the number of bytes per sector delivered to the data FIFO.
+    pub const fn get_sector_size(self) -> SectorSize {
+        if self.0 & (1 << SECTOR_SIZE) != 0 {
+            SectorSize::Bytes2340
+        } else {
+            SectorSize::Bytes2048
+        }
+    }
+
+    /// Gets the mode as a raw parameter byte.
+    pub const fn to_bits(self) -> u8 {
+        self.0
+    }
+}
+
+/// A response read from the controller's response FIFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Response {
+    /// The interrupt which signalled the response.
+    pub cause: IntCause,
+    len: u8,
+    data: [u8; RESPONSE_LEN],
+}
+
+impl Response {
+    /// The response bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+
+    /// The drive status byte at the start of the response.
+    pub fn stat(&self) -> DriveStat {
+        DriveStat(self.data[0])
+    }
+
+    pub(crate) fn error(&self) -> Error {
+        Error::Drive {
+            stat: self.stat(),
+            code: self.data[1],
+        }
+    }
+}
+
+/// The region of a licensed disc.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    America,
+    Europe,
+    Japan,
+}
+
+/// The disc information returned by [`CdRom::get_id`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscInfo {
+    /// The drive status.
+    pub stat: DriveStat,
+    /// Whether the disc has a valid license string.
+    pub licensed: bool,
+    /// Whether the disc is an audio CD.
+    pub audio: bool,
+    /// Whether the disc is a mode 2 disc.
+    pub mode2: bool,
+    /// The region of a licensed disc.
+    pub region: Option<Region>,
+}
+
+impl DiscInfo {
+    fn from_response(response: &Response) -> Result<Self> {
+        let data = response.bytes();
+        if data.len() < 8 {
+            return Err(Error::ShortResponse)
+        }
+        let region = match &data[4..8] {
+            b"SCEA" => Some(Region::America),
+            b"SCEE" => Some(Region::Europe),
+            b"SCEI" => Some(Region::Japan),
+            _ => None,
+        };
+        Ok(DiscInfo {
+            stat: response.stat(),
+            licensed: data[1] & (1 << 7) == 0,
+            audio: data[1] & (1 << 4) != 0,
+            mode2: data[2] == 0x20,
+            region,
+        })
+    }
+}
+
+/// A handle to the CD-ROM controller.
+pub struct CdRom {
+    status: cdrom::Status,
+    mode: Mode,
+    // The sector the drive reads next if it's still reading the run of
+    // sectors started by `read_sector`
+    next_lba: Option<u32>,
+}
+
+impl CdRom {
+    /// Creates a handle to the CD-ROM controller, enabling all of its
+    /// interrupts and acknowledging any that are pending.
+    pub fn new() -> Self {
+        let mut cd = CdRom {
+            status: cdrom::Status::new(),
+            mode: Mode::new(),
+            next_lba: None,
+        };
+        cd.select(Idx::Idx1);
+        cdrom::InterruptEnable::skip_load().enable_all().store();
+        cdrom::Interrupt::skip_load().ack_and_clear_params().store();
+        cd.select(Idx::Idx0);
+        cd
+    }
+
+    fn select(&mut self, idx: Idx) {
+        self.status.set_idx(idx).store();
+    }
+
+    /// Gets the mode last set by [`CdRom::set_mode`].
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Checks for an interrupt, reading the response FIFO and acknowledging
+    /// the interrupt if there is one.
+    pub fn poll(&mut self) -> Option<Response> {
+        self.select(Idx::Idx1);
+        let mut int = cdrom::Interrupt::new();
+        let res = int.cause().map(|cause| {
+            let mut response = Response {
+                cause,
+                len: 0,
+                data: [0; RESPONSE_LEN],
+            };
+            let mut fifo = cdrom::Response::skip_load();
+            self.status.load();
+            while !self.status.response_fifo_empty() && (response.len as usize) < RESPONSE_LEN {
+                response.data[response.len as usize] = fifo.load().to_bits();
+                response.len += 1;
+                self.status.load();
+            }
+            int.ack_all().store();
+            response
+        });
+        self.select(Idx::Idx0);
+        res
+    }
+
+    /// Spins until the controller raises an interrupt and returns its
+    /// response.
+    pub fn wait(&mut self) -> Response {
+        loop {
+            if let Some(response) = self.poll() {
+                return response
+            }
+        }
+    }
+
+    /// Spins until the controller responds with `cause`. Sectors signalled by
+    /// [`IntCause::DataReady`] are dropped unless that's what's expected.
+    fn expect(&mut self, cause: IntCause) -> Result<Response> {
+        loop {
+            let response = self.wait();
+            match response.cause {
+                c if c == cause => return Ok(response),
+                IntCause::DataReady => continue,
+                IntCause::DiskError => return Err(response.error()),
+                other => return Err(Error::UnexpectedInterrupt(other)),
+            }
+        }
+    }
+
+    /// Sends a command with parameters without waiting for a response.
+    pub fn send(&mut self, cmd: Command, params: &[u8]) {
+        self.next_lba = None;
+        self.status.load();
+        while self.status.busy() {
+            self.status.load();
+        }
+        self.select(Idx::Idx0);
+        let mut param = cdrom::Parameter::skip_load();
+        for &p in params {
+            param.set_param(p).store();
+        }
+        cdrom::Controller::skip_load().send_cmd(cmd).store();
+    }
+
+    /// Sends a command with parameters and waits for the controller to
+    /// acknowledge it.
+    pub fn command(&mut self, cmd: Command, params: &[u8]) -> Result<Response> {
+        self.send(cmd, params);
+        self.expect(IntCause::Acknowledge)
+    }
+
+    /// Sends a command which takes a while to complete and waits for its second
+    /// response.
+    fn blocking_command(&mut self, cmd: Command, params: &[u8]) -> Result<DriveStat> {
+        self.command(cmd, params)?;
+        Ok(self.expect(IntCause::Complete)?.stat())
+    }
+
+    /// Gets the drive status.
+    pub fn get_stat(&mut self) -> Result<DriveStat> {
+        Ok(self.command(Command::GetStat, &[])?.stat())
+    }
+
+    /// Resets the drive mode, aborts any reads and starts the motor.
+    pub fn init(&mut self) -> Result<DriveStat> {
+        let stat = self.blocking_command(Command::Init, &[])?;
+        self.mode = Mode::new();
+        Ok(stat)
+    }
+
+    /// Sets the drive mode.
+    pub fn set_mode(&mut self, mode: Mode) -> Result<DriveStat> {
+        let stat = self.command(Command::Setmode, &[mode.to_bits()])?.stat();
+        self.mode = mode;
+        Ok(stat)
+    }
+
+    /// Sets the position used by the next seek, read or play command.
+    pub fn set_loc(&mut self, loc: Msf) -> Result<DriveStat> {
+        Ok(self.command(Command::Setloc, &loc.to_bcd())?.stat())
+    }
+
+    /// Seeks to the data sector set by [`CdRom::set_loc`] and waits for the
+    /// seek to complete.
+    pub fn seek_l(&mut self) -> Result<DriveStat> {
+        self.blocking_command(Command::SeekL, &[])
+    }
+
+    /// Starts reading sectors from the position set by [`CdRom::set_loc`]
+    /// with retries on errors. Each sector is signalled by
+    /// [`IntCause::DataReady`].
+    pub fn read_n(&mut self) -> Result<DriveStat> {
+        Ok(self.command(Command::ReadN, &[])?.stat())
+    }
+
+    /// Starts reading sectors from the position set by [`CdRom::set_loc`]
+    /// without retries. Each sector is signalled by [`IntCause::DataReady`].
+    pub fn read_s(&mut self) -> Result<DriveStat> {
+        Ok(self.command(Command::ReadS, &[])?.stat())
+    }
+
+    /// Stops reading or playing and waits for the drive to pause.
+    pub fn pause(&mut self) -> Result<DriveStat> {
+        self.blocking_command(Command::Pause, &[])
+    }
+
+    /// Gets information about the disc in the drive.
+    pub fn get_id(&mut self) -> Result<DiscInfo> {
+        self.command(Command::GetID, &[])?;
+        loop {
+            let response = self.wait();
+            match response.cause {
+                IntCause::Complete => return DiscInfo::from_response(&response),
+                // Unlicensed discs report an error, but still return their info
+                IntCause::DiskError if response.bytes().len() == 8 => {
+                    return DiscInfo::from_response(&response)
+                },
+                IntCause::DiskError => return Err(response.error()),
+                IntCause::DataReady => continue,
+                other => return Err(Error::UnexpectedInterrupt(other)),
+            }
+        }
+    }
+
+    /// Gets the first and last track numbers on the disc.
+    pub fn get_tn(&mut self) -> Result<(u8, u8)> {
+        let response = self.command(Command::GetTN, &[])?;
+        match response.bytes() {
+            [_, first, last, ..] => Ok((from_bcd(*first), from_bcd(*last))),
+            _ => Err(Error::ShortResponse),
+        }
+    }
+
+    /// Gets the start of a track. Track 0 gives the end of the last track.
+    pub fn get_td(&mut self, track: u8) -> Result<Msf> {
+        let response = self.command(Command::GetTD, &[to_bcd(track)])?;
+        match response.bytes() {
+            [_, minute, second, ..] => Ok(Msf::from_bcd([*minute, *second, 0])),
+            _ => Err(Error::ShortResponse),
+        }
+    }
+
+    /// Copies the sector signalled by [`IntCause::DataReady`] from the data
+    /// FIFO into `buf` using the CPU.
+    pub fn read_data(&mut self, buf: &mut [u8]) {
+        let mut request = cdrom::Request::skip_load();
+        request.request_data(true).store();
+        self.status.load();
+        while self.status.data_fifo_empty() {
+            self.status.load();
+        }
+        let mut fifo = cdrom::DataByte::skip_load();
+        for byte in buf {
+            *byte = fifo.load().to_bits();
+        }
+        request.request_data(false).store();
+    }
+
+    /// Copies the sector signalled by [`IntCause::DataReady`] from the data
+    /// FIFO into `buf` using DMA channel 3.
+    pub fn receive_data(&mut self, dma: &mut dma::CDROM, buf: &mut [u32]) -> Result<()> {
+        let mut request = cdrom::Request::skip_load();
+        request.request_data(true).store();
+        self.status.load();
+        while self.status.data_fifo_empty() {
+            self.status.load();
+        }
+        let res = dma.receive(buf).map_err(Error::DMA);
+        request.request_data(false).store();
+        res
+    }
+
+    /// Reads consecutive sectors starting at `lba` into `buf` and pauses the
+    /// drive once they've been read.
+    ///
+    /// The length of `buf` must be a multiple of the sector size set by
+    /// [`CdRom::set_mode`].
+    pub fn read(&mut self, lba: u32, buf: &mut [u8]) -> Result<()> {
+        let size = self.mode.get_sector_size().bytes();
+        if buf.len() % size != 0 {
+            return Err(Error::BadBufferSize)
+        }
+        self.set_loc(Msf::from_lba(lba))?;
+        self.read_n()?;
+        for sector in buf.chunks_exact_mut(size) {
+            self.expect(IntCause::DataReady)?;
+            self.read_data(sector);
+        }
+        self.pause()?;
+        Ok(())
+    }
+}
+
+impl SectorRead for CdRom {
+    type Error = Error;
+
+    /// Reads a sector. The sector size must be set to
+    /// [`SectorSize::Bytes2048`].
+    ///
+    /// The drive keeps reading after each sector so reading the following
+    /// sector doesn't need another seek. The run ends when a sector out of
+    /// sequence is read or another command is sent, so call [`CdRom::pause`]
+    /// once done reading. Sectors which aren't read before the drive reaches
+    /// the next one are lost, so runs should be read without long delays.
+    fn read_sector(&mut self, lba: u32, buf: &mut [u8; iso9660::SECTOR_SIZE]) -> Result<()> {
+        if self.next_lba != Some(lba) {
+            // Stop the previous run so none of its sectors arrive after the seek
+            if self.next_lba.is_some() {
+                self.pause()?;
+            }
+            self.set_loc(Msf::from_lba(lba))?;
+            self.read_n()?;
+        }
+        self.expect(IntCause::DataReady)?;
+        self.read_data(buf);
+        self.next_lba = Some(lba + 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use super::{CdRom, Error, Msf, Result};
+use crate::dma;
+use crate::hw::cdrom::{Command, IntCause};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Reading,
+    Pausing,
+    Done,
+}
+
+/// A run of consecutive sectors being read into a caller-provided buffer in
+/// the background.
+///
+/// Sectors are transferred from the data FIFO with DMA channel 3 as
+/// [`Stream::poll`] sees them arrive, so the caller can keep doing other work
+/// between polls. The drive is paused once the last sector has been read.
+pub struct Stream<'a> {
+    cd: &'a mut CdRom,
+    dma: dma::CDROM,
+    buf: &'a mut [u32],
+    words: usize,
+    next: usize,
+    state: State,
+}
+
+impl<'a> Stream<'a> {
+    /// Starts reading consecutive sectors from `lba` into `buf`.
+    ///
+    /// The length of `buf` must be a multiple of the sector size set by
+    /// [`CdRom::set_mode`]. Each sector is stored after the previous one.
+    pub fn new(cd: &'a mut CdRom, lba: u32, buf: &'a mut [u32]) -> Result<Self> {
+        let words = cd.mode().get_sector_size().words();
+        if buf.len() % words != 0 {
+            return Err(Error::BadBufferSize)
+        }
+        let state = if buf.is_empty() {
+            State::Done
+        } else {
+            cd.set_loc(Msf::from_lba(lba))?;
+            cd.read_n()?;
+            State::Reading
+        };
+        Ok(Stream {
+            cd,
+            dma: dma::CDROM::new(),
+            buf,
+            words,
+            next: 0,
+            state,
+        })
+    }
+
+    /// The number of sectors in the stream.
+    pub fn len(&self) -> usize {
+        self.buf.len() / self.words
+    }
+
+    /// Checks if the stream has no sectors.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// The number of sectors read so far.
+    pub fn sectors_read(&self) -> usize {
+        self.next
+    }
+
+    /// Checks if all sectors were read and the drive has paused.
+    pub fn done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Gets the `n`th sector if it was read.
+    pub fn sector(&self, n: usize) -> Option<&[u32]> {
+        if n < self.next {
+            Some(&self.buf[n * self.words..(n + 1) * self.words])
+        } else {
+            None
+        }
+    }
+
+    /// Checks if the controller has signalled anything, transferring the next
+    /// sector if one is ready. Returns the index of the sector if one was
+    /// read.
+    pub fn poll(&mut self) -> Result<Option<usize>> {
+        if self.state == State::Done {
+            return Ok(None)
+        }
+        let response = match self.cd.poll() {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        match (self.state, response.cause) {
+            (State::Reading, IntCause::DataReady) => {
+                let n = self.next;
+                let sector = &mut self.buf[n * self.words..(n + 1) * self.words];
+                if let Err(err) = self.cd.receive_data(&mut self.dma, sector) {
+                    self.state = State::Done;
+                    return Err(err)
+                }
+                self.next += 1;
+                if self.next == self.len() {
+                    self.cd.send(Command::Pause, &[]);
+                    self.state = State::Pausing;
+                }
+                Ok(Some(n))
+            },
+            // Sectors may still arrive before the pause takes effect
+            (State::Pausing, IntCause::DataReady | IntCause::Acknowledge) => Ok(None),
+            (State::Pausing, IntCause::Complete) => {
+                self.state = State::Done;
+                Ok(None)
+            },
+            (_, IntCause::DiskError) => {
+                self.state = State::Done;
+                Err(response.error())
+            },
+            (_, other) => Err(Error::UnexpectedInterrupt(other)),
+        }
+    }
+
+    /// Polls the stream, calling `f` with the index and contents of the sector
+    /// if one was read.
+    pub fn poll_with<F: FnOnce(usize, &[u32])>(&mut self, f: F) -> Result<()> {
+        if let Some(n) = self.poll()? {
+            let start = n * self.words;
+            f(n, &self.buf[start..start + self.words]);
+        }
+        Ok(())
+    }
+
+    /// Stops the stream and waits for the drive to pause.
+    pub fn cancel(mut self) -> Result<()> {
+        match self.state {
+            State::Reading => self.cd.pause().map(|_| ()),
+            // The pause's acknowledge may still be pending, so let `poll` skip
+            // it until the pause completes
+            State::Pausing => {
+                while self.state == State::Pausing {
+                    self.poll()?;
+                }
+                Ok(())
+            },
+            State::Done => Ok(()),
+        }
+    }
+}
@@ -247,6 +247,106 @@ impl<A: MemoryAddress, B: BlockControl, C: ChannelControl> Channel<A, B, C> {
         res
     }
 
+    /// Receives a buffer from a DMA channel in single-block mode and call `f`
+    /// while the transfer completes.
+    ///
+    /// This blocks if the function `f` returns before the transfer completes.
+    /// Returns `f`'s return value or an error if the buffer is too large.
+    pub fn receive_and<F: FnOnce() -> R, R>(&mut self, block: &mut [u32], f: F) -> Result<R> {
+        // If the block is empty, just call `f` and return
+        let addr = match block.first() {
+            Some(addr) => addr,
+            None => return Ok(f()),
+        };
+        self.madr.set_address(addr).store();
+        // If the block is too long error out
+        self.bcr.set_block(block.len())?.store();
+        // Start the DMA transfer
+        self.control
+            .set_direction(Direction::ToMemory)
+            .set_step(Step::Forward)
+            .set_mode(TransferMode::Immediate)
+            .start()
+            .store();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        let res = f();
+        self.control.wait();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        Ok(res)
+    }
+
+    /// Receives a buffer from a DMA channel in multi-block mode and call `f`
+    /// while the transfer completes.
+    ///
+    /// This blocks if the function `f` returns before the transfer completes.
+    /// Returns `f`'s return value or an error if the buffer can't be split
+    /// into `size` blocks.
+    pub fn receive_blocks_and<F: FnOnce() -> R, R>(
+        &mut self, block: &mut [u32], size: usize, f: F,
+    ) -> Result<R> {
+        // If the block is empty, just call `f` and return
+        let addr = match block.first() {
+            Some(addr) => addr,
+            None => return Ok(f()),
+        };
+        self.madr.set_address(addr).store();
+        // If the block can't be partitioned into evenly-sized sub-blocks error out
+        if block.len() % size != 0 {
+            return Err(Error::BadBlockPartition)
+        }
+        let words = (block.len() / size)
+            .try_into()
+            .map_err(|_| Error::OversizedBlock)?;
+        let block_len = BlockMode::Multi {
+            words,
+            blocks: size.try_into().map_err(|_| Error::OversizedBlock)?,
+        };
+        // This will never fail
+        self.bcr.set_block(block_len)?.store();
+        self.control
+            .set_direction(Direction::ToMemory)
+            .set_step(Step::Forward)
+            .set_mode(TransferMode::Request)
+            .start()
+            .store();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        let res = f();
+        self.control.wait();
+        // This acts like a compiler fence
+        unsafe {
+            asm!("nop");
+        }
+        Ok(res)
+    }
+
+    /// Receives a buffer from a DMA channel in single-block mode and waits
+    /// for the transfer to complete. Returns an error if the buffer is too
+    /// large.
+    pub fn receive(&mut self, block: &mut [u32]) -> Result<()> {
+        self.receive_and(block, || ())
+    }
+
+    /// Sets up the channel to send memory to its device with
+    /// [`send_blocks_and`][Self::send_blocks_and], sending each block when the
+    /// device requests it.
+    pub fn setup_send_blocks(&mut self) -> &mut Self {
+        self.control
+            .set_direction(Direction::FromMemory)
+            .set_step(Step::Forward)
+            .set_mode(TransferMode::Request)
+            .store();
+        self
+    }
+
     pub fn send_list<L: LinkedList + ?Sized>(&mut self, list: &L) {
         self.send_list_and(list, || ())
     }
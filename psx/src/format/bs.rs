@@ -0,0 +1,593 @@
+//! BS compressed image parsing
+//!
+//! BS images and STR video frames are stored as a bitstream of MPEG-1 style
+//! Huffman codes which must be expanded into the run-length codes the MDEC
+//! takes as input. Version 2 bitstreams store each block's DC coefficient as a
+//! raw 10-bit value while version 3 bitstreams store the difference from the
+//! previous block's DC coefficient.
+//!
+//! BS files don't store their dimensions so they must be known in advance. The
+//! run-length codes are usually decoded by the MDEC with
+//! [`Mdec::decode`][crate::mdec::Mdec::decode], but [`decode_rle`] decodes them
+//! in software like the MDEC does for reference and testing.
+
+use crate::mdec::{DEFAULT_QUANT_TABLE, DEFAULT_SCALE_TABLE, ZIGZAG};
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// The size of a bitstream header in bytes.
+pub const HEADER_LEN: usize = 8;
+
+const MAGIC: u16 = 0x3800;
+const END_OF_BLOCK: u16 = 0xFE00;
+// Cr, Cb and four luminance blocks
+const BLOCKS_PER_MACROBLOCK: usize = 6;
+const ESCAPE_LEN: u32 = 6;
+const MAX_CODE_LEN: u32 = 17;
+
+/// The number of pixels in a decoded macroblock.
+pub const MACROBLOCK_PIXELS: usize = 256;
+
+/// A BS decoding error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The header doesn't have the expected magic number.
+    BadMagic,
+    /// The bitstream version isn't 2 or 3.
+    UnsupportedVersion(u16),
+    /// The bitstream ended in the middle of a macroblock.
+    Truncated,
+    /// The bitstream contains an invalid code.
+    BadCode,
+    /// The output buffer is too small for the run-length codes.
+    OutputTooSmall,
+}
+
+/// The bitstream version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// DC coefficients are stored as raw 10-bit values.
+    V2,
+    /// DC coefficients are stored as Huffman coded differences.
+    V3,
+}
+
+/// A bitstream header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// The size of the decoded run-length codes in words rounded up to a
+    /// multiple of 32.
+    pub mdec_words: u16,
+    /// The quantization scale for every block in the image.
+    pub qscale: u16,
+    /// The bitstream version.
+    pub version: Version,
+}
+
+impl Header {
+    /// Parses the header at the start of a bitstream.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let field = |n: usize| u16::from_le_bytes([data[2 * n], data[2 * n + 1]]);
+        if data.len() < HEADER_LEN {
+            return Err(Error::Truncated)
+        }
+        if field(1) != MAGIC {
+            return Err(Error::BadMagic)
+        }
+        let version = match field(3) {
+            2 => Version::V2,
+            3 => Version::V3,
+            other => return Err(Error::UnsupportedVersion(other)),
+        };
+        Ok(Header {
+            mdec_words: field(0),
+            qscale: field(2),
+            version,
+        })
+    }
+}
+
+/// A BS image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bs<'a> {
+    header: Header,
+    data: &'a [u8],
+}
+
+impl<'a> Bs<'a> {
+    /// Parses a BS image's header.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        Ok(Bs {
+            header: Header::parse(data)?,
+            data,
+        })
+    }
+
+    /// The image's header.
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Decodes the image into run-length codes for the MDEC. Returns the
+    /// number of words written to `out`.
+    pub fn decode(&self, width: u16, height: u16, out: &mut [u32]) -> Result<usize> {
+        decode(self.data, width, height, out)
+    }
+}
+
+// Reads bits MSB-first from little-endian halfwords. Reading past the end of
+// the data returns zeros so codes may be peeked at without bounds checks.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    cache: u32,
+    cached: u32,
+    left: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let mut bits = Bits {
+            data,
+            pos: 0,
+            cache: 0,
+            cached: 0,
+            left: data.len() / 2 * 16,
+        };
+        bits.refill();
+        bits
+    }
+
+    fn refill(&mut self) {
+        while self.cached <= 16 {
+            let halfword = match self.data.get(self.pos..self.pos + 2) {
+                Some(bytes) => u16::from_le_bytes([bytes[0], bytes[1]]),
+                None => 0,
+            };
+            self.pos += 2;
+            self.cache |= (halfword as u32) << (16 - self.cached);
+            self.cached += 16;
+        }
+    }
+
+    // Gets the next `n` bits without consuming them. `n` must be at most 17.
+    fn peek(&self, n: u32) -> u32 {
+        self.cache >> (32 - n)
+    }
+
+    // Consumes `n` bits. `n` must be at most 17.
+    fn skip(&mut self, n: u32) -> Result<()> {
+        if n as usize > self.left {
+            return Err(Error::Truncated)
+        }
+        self.left -= n as usize;
+        self.cache <<= n;
+        self.cached -= n;
+        self.refill();
+        Ok(())
+    }
+
+    fn read(&mut self, n: u32) -> Result<u32> {
+        let bits = self.peek(n);
+        self.skip(n)?;
+        Ok(bits)
+    }
+}
+
+// Packs run-length codes into words with the first code in the low halfword.
+struct Writer<'a> {
+    out: &'a mut [u32],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn push(&mut self, code: u16) -> Result<()> {
+        let word = self
+            .out
+            .get_mut(self.len / 2)
+            .ok_or(Error::OutputTooSmall)?;
+        if self.len % 2 == 0 {
+            *word = code as u32;
+        } else {
+            *word |= (code as u32) << 16;
+        }
+        self.len += 1;
+        Ok(())
+    }
+}
+
+// AC coefficient (run, level) pairs indexed by the low bits of their codes.
+// Codes `0001xx`
+const AC_0001: [(u8, u8); 4] = [(7, 1), (6, 1), (1, 2), (5, 1)];
+// Codes `00001xx`
+const AC_00001: [(u8, u8); 4] = [(2, 2), (9, 1), (0, 4), (8, 1)];
+// Codes `00100xxx`
+const AC_00100: [(u8, u8); 8] = [
+    (13, 1),
+    (0, 6),
+    (12, 1),
+    (11, 1),
+    (3, 2),
+    (1, 3),
+    (0, 5),
+    (10, 1),
+];
+// Codes `0000001xxx`
+const AC_0000001: [(u8, u8); 8] = [
+    (16, 1),
+    (5, 2),
+    (0, 7),
+    (2, 3),
+    (1, 4),
+    (15, 1),
+    (14, 1),
+    (4, 2),
+];
+// Codes with 7 to 11 leading zeros followed by a one and 4 bits
+#[rustfmt::skip]
+const AC_LONG: [[(u8, u8); 16]; 5] = [
+    [(0, 11), (8, 2), (4, 3), (0, 10), (2, 4), (7, 2), (21, 1), (20, 1),
+     (0, 9), (19, 1), (18, 1), (1, 5), (3, 3), (0, 8), (6, 2), (17, 1)],
+    [(10, 2), (9, 2), (5, 3), (3, 4), (2, 5), (1, 7), (1, 6), (0, 15),
+     (0, 14), (0, 13), (0, 12), (26, 1), (25, 1), (24, 1), (23, 1), (22, 1)],
+    [(0, 31), (0, 30), (0, 29), (0, 28), (0, 27), (0, 26), (0, 25), (0, 24),
+     (0, 23), (0, 22), (0, 21), (0, 20), (0, 19), (0, 18), (0, 17), (0, 16)],
+    [(0, 40), (0, 39), (0, 38), (0, 37), (0, 36), (0, 35), (0, 34), (0, 33),
+     (0, 32), (1, 14), (1, 13), (1, 12), (1, 11), (1, 10), (1, 9), (1, 8)],
+    [(1, 18), (1, 17), (1, 16), (1, 15), (6, 3), (16, 2), (15, 2), (14, 2),
+     (13, 2), (12, 2), (11, 2), (31, 1), (30, 1), (29, 1), (28, 1), (27, 1)],
+];
+
+// Decodes the next AC coefficient into a run-length code or returns `None`
+// at the end of the block.
+fn ac_code(bits: &mut Bits) -> Result<Option<u16>> {
+    let peek = bits.peek(MAX_CODE_LEN);
+    let zeros = peek.leading_zeros() - (32 - MAX_CODE_LEN);
+    // The code's length without the sign bit and its low bits
+    let code = |len: u32, n: u32| (peek >> (MAX_CODE_LEN - len)) as usize & ((1 << n) - 1);
+    let (len, (run, level)) = match zeros {
+        0 if code(2, 1) == 0 => {
+            bits.skip(2)?;
+            return Ok(None)
+        },
+        0 => (2, (0, 1)),
+        1 if code(3, 1) == 1 => (3, (1, 1)),
+        1 => (4, [(0, 2), (2, 1)][code(4, 1)]),
+        2 => match code(5, 2) {
+            0 => (8, AC_00100[code(8, 3)]),
+            1 => (5, (0, 3)),
+            2 => (5, (4, 1)),
+            _ => (5, (3, 1)),
+        },
+        3 => (6, AC_0001[code(6, 2)]),
+        4 => (7, AC_00001[code(7, 2)]),
+        5 => {
+            // Escaped codes are followed by the raw 16-bit run-length code
+            bits.skip(ESCAPE_LEN)?;
+            return Ok(Some(bits.read(16)? as u16))
+        },
+        6 => (10, AC_0000001[code(10, 3)]),
+        7..=11 => (zeros + 5, AC_LONG[zeros as usize - 7][code(zeros + 5, 4)]),
+        // The zeros may be padding past the end of the data
+        _ if bits.left < MAX_CODE_LEN as usize => return Err(Error::Truncated),
+        _ => return Err(Error::BadCode),
+    };
+    let negative = code(len + 1, 1) == 1;
+    bits.skip(len + 1)?;
+    let level = if negative {
+        -(level as i16)
+    } else {
+        level as i16
+    };
+    Ok(Some((run as u16) << 10 | (level as u16 & 0x3FF)))
+}
+
+// Decodes the size of a version 3 DC difference.
+fn dc_size(bits: &mut Bits, luminance: bool) -> Result<u32> {
+    let peek = bits.peek(8);
+    let ones = (peek << 24).leading_ones();
+    let (len, size) = if luminance {
+        match (peek >> 6, ones) {
+            (0b00, _) => (2, 1),
+            (0b01, _) => (2, 2),
+            (_, 1) if (peek >> 5) & 1 == 1 => (3, 3),
+            (_, 1) => (3, 0),
+            (_, 2) => (3, 4),
+            (_, 3..=6) => (ones + 1, ones + 2),
+            _ => return Err(Error::BadCode),
+        }
+    } else {
+        match ones {
+            0 => (2, (peek >> 6) & 1),
+            1 => (2, 2),
+            2..=7 => (ones + 1, ones + 1),
+            _ => return Err(Error::BadCode),
+        }
+    };
+    bits.skip(len)?;
+    Ok(size)
+}
+
+// Decodes a version 3 DC difference.
+fn dc_diff(bits: &mut Bits, luminance: bool) -> Result<i16> {
+    let size = dc_size(bits, luminance)?;
+    if size == 0 {
+        return Ok(0)
+    }
+    let value = bits.read(size)? as i16;
+    // Differences with a leading zero bit are negative
+    if value < 1 << (size - 1) {
+        Ok(value - (1 << size) + 1)
+    } else {
+        Ok(value)
+    }
+}
+
+/// Decodes a bitstream, including its header, into run-length codes for the
+/// MDEC. `width` and `height` are the image's dimensions in pixels. Returns the
+/// number of words written to `out`.
+pub fn decode(data: &[u8], width: u16, height: u16, out: &mut [u32]) -> Result<usize> {
+    let header = Header::parse(data)?;
+    let mut bits = Bits::new(&data[HEADER_LEN..]);
+    let mut out = Writer { out, len: 0 };
+    let macroblocks = ((width as usize + 15) / 16) * ((height as usize + 15) / 16);
+    let qscale = (header.qscale & 0x3F) << 10;
+    // The previous DC coefficients for Cr, Cb and luminance
+    let mut prev_dc = [0i16; 3];
+    for _ in 0..macroblocks {
+        for block in 0..BLOCKS_PER_MACROBLOCK {
+            let dc = match header.version {
+                Version::V2 => bits.read(10)? as u16,
+                Version::V3 => {
+                    let component = block.min(2);
+                    let diff = dc_diff(&mut bits, component == 2)?;
+                    let dc = prev_dc[component] + diff;
+                    // DC coefficients are stored divided by 4
+                    if !(-128..128).contains(&dc) {
+                        return Err(Error::BadCode)
+                    }
+                    prev_dc[component] = dc;
+                    (dc * 4) as u16 & 0x3FF
+                },
+            };
+            out.push(qscale | dc)?;
+            while let Some(code) = ac_code(&mut bits)? {
+                out.push(code)?;
+            }
+            out.push(END_OF_BLOCK)?;
+        }
+    }
+    // The MDEC skips padding while it's waiting for a DC coefficient
+    if out.len % 2 != 0 {
+        out.push(END_OF_BLOCK)?;
+    }
+    Ok(out.len / 2)
+}
+
+// Sign-extends a run-length code's 10-bit value.
+fn signed10(code: u16) -> i32 {
+    ((code << 6) as i16 >> 6) as i32
+}
+
+/// Dequantizes a block's run-length codes into coefficients in row-major
+/// order like the MDEC does. Padding before the DC coefficient is skipped.
+pub fn dequantize<I: Iterator<Item = u16>>(
+    codes: &mut I, quant: &[u8; 64], block: &mut [i16; 64],
+) -> Result<()> {
+    *block = [0; 64];
+    let mut code = codes
+        .find(|&code| code != END_OF_BLOCK)
+        .ok_or(Error::Truncated)?;
+    let qscale = (code >> 10) as i32;
+    let mut value = signed10(code) * quant[0] as i32;
+    let mut k = 0;
+    loop {
+        // Blocks without a quantization scale aren't quantized or zigzagged
+        if qscale == 0 {
+            value = signed10(code) * 2;
+        }
+        let idx = if qscale == 0 { k } else { ZIGZAG[k] as usize };
+        block[idx] = value.clamp(-0x400, 0x3FF) as i16;
+        code = codes.next().ok_or(Error::Truncated)?;
+        k += (code >> 10) as usize + 1;
+        if k > 63 {
+            return Ok(())
+        }
+        value = (signed10(code) * quant[k] as i32 * qscale + 4) / 8;
+    }
+}
+
+/// Applies the inverse DCT to a block of coefficients in row-major order like
+/// the MDEC does.
+pub fn idct(block: &mut [i16; 64], scale: &[u16; 64]) {
+    let mut src = [0; 64];
+    for (src, &coefficient) in src.iter_mut().zip(block.iter()) {
+        *src = coefficient as i32;
+    }
+    // Each pass transforms the columns and transposes the result
+    for _ in 0..2 {
+        let mut dst = [0; 64];
+        for x in 0..8 {
+            for y in 0..8 {
+                let sum: i32 = (0..8)
+                    .map(|z| src[y + z * 8] * ((scale[x + z * 8] as i16 as i32) >> 3))
+                    .sum();
+                dst[x + y * 8] = (sum + 0x1000) >> 13;
+            }
+        }
+        src = dst;
+    }
+    for (sample, &value) in block.iter_mut().zip(src.iter()) {
+        *sample = value as i16;
+    }
+}
+
+// Converts a macroblock's transformed Cr, Cb and luminance blocks to unsigned
+// 15-bit pixels.
+fn to_rgb15(blocks: &[[i16; 64]; BLOCKS_PER_MACROBLOCK], pixels: &mut [u16; MACROBLOCK_PIXELS]) {
+    let component = |value: i32| ((value.clamp(-128, 127) + 128) >> 3) as u16;
+    for y in 0..16 {
+        for x in 0..16 {
+            let chroma = x / 2 + (y / 2) * 8;
+            let cr = blocks[0][chroma] as i32;
+            let cb = blocks[1][chroma] as i32;
+            let luma = blocks[2 + x / 8 + (y / 8) * 2][x % 8 + (y % 8) * 8] as i32;
+            let red = luma + ((1436 * cr) >> 10);
+            let green = luma - ((352 * cb + 731 * cr) >> 10);
+            let blue = luma + ((1815 * cb) >> 10);
+            pixels[x + y * 16] = component(red) | component(green) << 5 | component(blue) << 10;
+        }
+    }
+}
+
+/// Decodes `macroblocks` macroblocks of run-length codes into unsigned 15-bit
+/// pixels in software with the standard quantization and IDCT scale tables.
+/// `f` is called with the pixels of each 16x16 macroblock in row-major order.
+///
+/// This is much slower than the MDEC and is intended as a reference.
+pub fn decode_rle<F: FnMut(&[u16; MACROBLOCK_PIXELS])>(
+    rle: &[u32], macroblocks: usize, mut f: F,
+) -> Result<()> {
+    let mut codes = rle
+        .iter()
+        .flat_map(|&word| [word as u16, (word >> 16) as u16]);
+    let mut blocks = [[0; 64]; BLOCKS_PER_MACROBLOCK];
+    let mut pixels = [0; MACROBLOCK_PIXELS];
+    for _ in 0..macroblocks {
+        for block in &mut blocks {
+            dequantize(&mut codes, &DEFAULT_QUANT_TABLE, block)?;
+            idct(block, &DEFAULT_SCALE_TABLE);
+        }
+        to_rgb15(&blocks, &mut pixels);
+        f(&pixels);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_rle, dequantize, Bs, Error, Header, Version, END_OF_BLOCK};
+    use crate::mdec::DEFAULT_QUANT_TABLE;
+
+    // Packs bit strings MSB-first into little-endian halfwords after a header
+    fn bitstream(version: u16, qscale: u16, codes: &[&str]) -> ([u8; 64], usize) {
+        let mut data = [0; 64];
+        data[..8].copy_from_slice(&[0x40, 0, 0x00, 0x38, qscale as u8, 0, version as u8, 0]);
+        let mut n = 0;
+        for code in codes {
+            for bit in code.bytes() {
+                if bit == b'1' {
+                    let halfword = n / 16;
+                    let byte = 8 + 2 * halfword + 1 - (n % 16) / 8;
+                    data[byte] |= 0x80 >> (n % 8);
+                }
+                n += 1;
+            }
+        }
+        (data, 8 + (n + 15) / 16 * 2)
+    }
+
+    #[test_case]
+    fn header() {
+        let (data, _) = bitstream(3, 5, &[]);
+        let header = Header::parse(&data).unwrap();
+        assert!(header.mdec_words == 0x40);
+        assert!(header.qscale == 5);
+        assert!(header.version == Version::V3);
+        let (data, _) = bitstream(1, 5, &[]);
+        assert!(Header::parse(&data) == Err(Error::UnsupportedVersion(1)));
+        assert!(Header::parse(&data[..4]) == Err(Error::Truncated));
+    }
+
+    #[test_case]
+    fn version2() {
+        // DC, (1, 1), (0, -2), escaped (3, 5) and end of block
+        let block = [
+            "1111111111",
+            "0110",
+            "01001",
+            "000001",
+            "0000110000000101",
+            "10",
+        ];
+        let mut codes = [""; 36];
+        for chunk in codes.chunks_mut(6) {
+            chunk.copy_from_slice(&block);
+        }
+        let (data, len) = bitstream(2, 1, &codes);
+        let mut out = [0; 16];
+        assert!(decode(&data[..len], 16, 16, &mut out) == Ok(15));
+        assert!(out[0] == 0x07FF | 0x0401 << 16);
+        assert!(out[1] == 0x03FE | 0x0C05 << 16);
+        assert!(out[2] == END_OF_BLOCK as u32 | 0x07FF << 16);
+        assert!(out[14] == 0x0C05 | (END_OF_BLOCK as u32) << 16);
+        assert!(decode(&data[..len - 2], 16, 16, &mut out) == Err(Error::Truncated));
+        assert!(decode(&data[..len], 16, 16, &mut out[..14]) == Err(Error::OutputTooSmall));
+    }
+
+    #[test_case]
+    fn version3() {
+        // Cr +3, Cb -1, Y1 +2, Y2 0, Y3 -2, Y4 +100 each followed by the end of
+        // block code
+        let codes = [
+            "10", "11", "10", "01", "0", "10", "01", "10", "10", "100", "10", "01", "01", "10",
+            "111110", "1100100", "10",
+        ];
+        let (data, len) = bitstream(3, 0, &codes);
+        let mut out = [0; 8];
+        assert!(decode(&data[..len], 16, 16, &mut out) == Ok(6));
+        let halfword = |i: usize| (out[i / 2] >> (16 * (i % 2))) as u16;
+        assert!(halfword(0) == 12);
+        assert!(halfword(2) == (-4i16 as u16) & 0x3FF);
+        assert!(halfword(4) == 8);
+        assert!(halfword(6) == 8);
+        assert!(halfword(8) == 0);
+        assert!(halfword(10) == 400);
+        assert!(halfword(11) == END_OF_BLOCK);
+    }
+
+    #[test_case]
+    fn dequantized() {
+        // DC 4, (0, 3) and end of block with a quantization scale of 1
+        let codes = [END_OF_BLOCK, 0x0404, 0x0003, END_OF_BLOCK];
+        let mut block = [0; 64];
+        assert!(dequantize(&mut codes.into_iter(), &DEFAULT_QUANT_TABLE, &mut block) == Ok(()));
+        assert!(block[0] == 8);
+        assert!(block[1] == (3 * 16 + 4) / 8);
+        assert!(block[2..].iter().all(|&c| c == 0));
+        let mut codes = codes[..3].iter().copied();
+        assert!(dequantize(&mut codes, &DEFAULT_QUANT_TABLE, &mut block) == Err(Error::Truncated));
+    }
+
+    #[test_case]
+    fn reference() {
+        // A flat gray macroblock with no color
+        let codes = [
+            "0000000000",
+            "10",
+            "0000000000",
+            "10",
+            "0110010000",
+            "10",
+            "0110010000",
+            "10",
+            "0110010000",
+            "10",
+            "0110010000",
+            "10",
+        ];
+        let (data, len) = bitstream(2, 1, &codes);
+        let bs = Bs::new(&data[..len]).unwrap();
+        assert!(bs.header().version == Version::V2);
+        let mut rle = [0; 8];
+        let words = bs.decode(16, 16, &mut rle).unwrap();
+        let mut macroblocks = 0;
+        let res = decode_rle(&rle[..words], 1, |pixels| {
+            assert!(pixels.iter().all(|&p| p == 0x739C));
+            macroblocks += 1;
+        });
+        assert!(res == Ok(()));
+        assert!(macroblocks == 1);
+        assert!(decode_rle(&rle[..words], 2, |_| ()) == Err(Error::Truncated));
+    }
+}
@@ -0,0 +1,762 @@
+//! ISO9660 filesystem parsing
+//!
+//! This reads files from an ISO9660 filesystem without going through the BIOS.
+//! Sectors are read through the [`SectorRead`] trait which is implemented for
+//! [`CdRom`][crate::cdrom::CdRom] and for in-memory images.
+
+use core::cmp::min;
+
+/// The size of a logical block in bytes.
+pub const SECTOR_SIZE: usize = 2048;
+/// The maximum length of a file or directory name including its version.
+pub const MAX_NAME_LEN: usize = 32;
+/// The maximum number of extents in a multi-extent file.
+pub const MAX_EXTENTS: usize = 8;
+
+// The first sector of the volume descriptor set.
+const FIRST_DESCRIPTOR: u32 = 16;
+const STANDARD_ID: &[u8; 5] = b"CD001";
+const PRIMARY: u8 = 1;
+const TERMINATOR: u8 = 255;
+
+const VOLUME_ID: usize = 40;
+const VOLUME_ID_LEN: usize = 32;
+const VOLUME_SIZE: usize = 80;
+const BLOCK_SIZE: usize = 128;
+const PATH_TABLE_SIZE: usize = 132;
+const PATH_TABLE: usize = 140;
+const ROOT_RECORD: usize = 156;
+
+// The size of a directory record without its name.
+const RECORD_LEN: usize = 33;
+const PATH_RECORD_LEN: usize = 8;
+
+const HIDDEN: u8 = 0;
+const DIRECTORY: u8 = 1;
+const MULTI_EXTENT: u8 = 7;
+
+type Result<T, E> = core::result::Result<T, Error<E>>;
+
+/// An ISO9660 parsing error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error<E> {
+    /// A sector couldn't be read.
+    Read(E),
+    /// The volume descriptor set doesn't have a primary volume descriptor.
+    NoPrimaryVolume,
+    /// The logical block size isn't 2048 bytes.
+    UnsupportedBlockSize,
+    /// A directory or path table record is malformed.
+    BadRecord,
+    /// A name is longer than [`MAX_NAME_LEN`].
+    NameTooLong,
+    /// A file has more than [`MAX_EXTENTS`] extents.
+    TooManyExtents,
+    /// The file or directory doesn't exist.
+    NotFound,
+    /// The path refers to a directory rather than a file.
+    NotAFile,
+}
+
+/// A source of 2048-byte logical blocks.
+pub trait SectorRead {
+    /// The error returned when a sector can't be read.
+    type Error;
+
+    /// Reads the sector at `lba` into `buf`.
+    fn read_sector(
+        &mut self, lba: u32, buf: &mut [u8; SECTOR_SIZE],
+    ) -> core::result::Result<(), Self::Error>;
+}
+
+/// An in-memory image. Reading past the end of the image fails.
+impl SectorRead for &[u8] {
+    type Error = ();
+
+    fn read_sector(
+        &mut self, lba: u32, buf: &mut [u8; SECTOR_SIZE],
+    ) -> core::result::Result<(), ()> {
+        let start = lba as usize * SECTOR_SIZE;
+        let sector = self.get(start..start + SECTOR_SIZE).ok_or(())?;
+        buf.copy_from_slice(sector);
+        Ok(())
+    }
+}
+
+fn u16_le(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_le(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn is_separator(b: &u8) -> bool {
+    *b == b'/' || *b == b'\\'
+}
+
+fn components(path: &[u8]) -> impl Iterator<Item = &[u8]> {
+    path.split(is_separator).filter(|c| !c.is_empty())
+}
+
+/// Splits a path into its directory and final component.
+fn split_path(path: &[u8]) -> (&[u8], &[u8]) {
+    match path.iter().rposition(is_separator) {
+        Some(sep) => (&path[..sep], &path[sep + 1..]),
+        None => (&path[..0], path),
+    }
+}
+
+/// Strips the version and any trailing period from a file identifier.
+fn strip_version(name: &[u8]) -> &[u8] {
+    let name = match name.iter().position(|&b| b == b';') {
+        Some(end) => &name[..end],
+        None => name,
+    };
+    match name {
+        [rest @ .., b'.'] => rest,
+        _ => name,
+    }
+}
+
+fn names_match(identifier: &[u8], name: &[u8]) -> bool {
+    strip_version(identifier).eq_ignore_ascii_case(strip_version(name))
+}
+
+/// A contiguous run of sectors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    /// The first sector.
+    pub lba: u32,
+    /// The size in bytes.
+    pub size: u32,
+}
+
+/// A directory record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirEntry {
+    extent: Extent,
+    flags: u8,
+    name_len: u8,
+    name: [u8; MAX_NAME_LEN],
+}
+
+impl DirEntry {
+    fn parse<E>(record: &[u8]) -> Result<Self, E> {
+        if record.len() < RECORD_LEN {
+            return Err(Error::BadRecord)
+        }
+        let name_len = record[32] as usize;
+        let identifier = record
+            .get(RECORD_LEN..RECORD_LEN + name_len)
+            .ok_or(Error::BadRecord)?;
+        if name_len > MAX_NAME_LEN {
+            return Err(Error::NameTooLong)
+        }
+        let mut name = [0; MAX_NAME_LEN];
+        name[..name_len].copy_from_slice(identifier);
+        Ok(DirEntry {
+            extent: Extent {
+                lba: u32_le(record, 2),
+                size: u32_le(record, 10),
+            },
+            flags: record[25],
+            name_len: name_len as u8,
+            name,
+        })
+    }
+
+    /// The name without its version (e.g. `;1`).
+    pub fn name(&self) -> &[u8] {
+        strip_version(self.identifier())
+    }
+
+    /// The name as it's stored on the disc.
+    pub fn identifier(&self) -> &[u8] {
+        &self.name[..self.name_len as usize]
+    }
+
+    /// The sectors the entry refers to.
+    pub fn extent(&self) -> Extent {
+        self.extent
+    }
+
+    /// Checks if the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.flags & (1 << DIRECTORY) != 0
+    }
+
+    /// Checks if the entry is hidden.
+    pub fn is_hidden(&self) -> bool {
+        self.flags & (1 << HIDDEN) != 0
+    }
+
+    /// Checks if more extents of the same file follow this entry.
+    pub fn is_multi_extent(&self) -> bool {
+        self.flags & (1 << MULTI_EXTENT) != 0
+    }
+
+    // The `.` and `..` entries use the identifiers 0 and 1 respectively.
+    fn is_special(&self) -> bool {
+        matches!(self.identifier(), [0] | [1])
+    }
+}
+
+/// A file which may be made up of multiple extents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct File {
+    size: u32,
+    len: u8,
+    extents: [Extent; MAX_EXTENTS],
+}
+
+impl File {
+    fn new() -> Self {
+        File {
+            size: 0,
+            len: 0,
+            extents: [Extent::default(); MAX_EXTENTS],
+        }
+    }
+
+    fn push<E>(&mut self, extent: Extent) -> Result<(), E> {
+        let slot = self
+            .extents
+            .get_mut(self.len as usize)
+            .ok_or(Error::TooManyExtents)?;
+        *slot = extent;
+        self.len += 1;
+        self.size += extent.size;
+        Ok(())
+    }
+
+    /// The file size in bytes.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The extents making up the file in order.
+    pub fn extents(&self) -> &[Extent] {
+        &self.extents[..self.len as usize]
+    }
+}
+
+/// An ISO9660 filesystem read through a [`SectorRead`].
+pub struct Filesystem<R: SectorRead> {
+    reader: R,
+    cache: [u8; SECTOR_SIZE],
+    cached: Option<u32>,
+    volume_id: [u8; VOLUME_ID_LEN],
+    volume_size: u32,
+    path_table: Extent,
+    root: DirEntry,
+}
+
+impl<R: SectorRead> Filesystem<R> {
+    /// Reads the primary volume descriptor from `reader`.
+    pub fn new(reader: R) -> Result<Self, R::Error> {
+        let mut fs = Filesystem {
+            reader,
+            cache: [0; SECTOR_SIZE],
+            cached: None,
+            volume_id: [0; VOLUME_ID_LEN],
+            volume_size: 0,
+            path_table: Extent::default(),
+            root: DirEntry {
+                extent: Extent::default(),
+                flags: 0,
+                name_len: 0,
+                name: [0; MAX_NAME_LEN],
+            },
+        };
+        let mut lba = FIRST_DESCRIPTOR;
+        loop {
+            let descriptor = fs.sector(lba)?;
+            if &descriptor[1..6] != STANDARD_ID {
+                return Err(Error::NoPrimaryVolume)
+            }
+            match descriptor[0] {
+                PRIMARY => break,
+                TERMINATOR => return Err(Error::NoPrimaryVolume),
+                _ => lba += 1,
+            }
+        }
+        let descriptor = &fs.cache;
+        if u16_le(descriptor, BLOCK_SIZE) as usize != SECTOR_SIZE {
+            return Err(Error::UnsupportedBlockSize)
+        }
+        let volume_id = descriptor[VOLUME_ID..VOLUME_ID + VOLUME_ID_LEN]
+            .try_into()
+            .unwrap();
+        let volume_size = u32_le(descriptor, VOLUME_SIZE);
+        let path_table = Extent {
+            lba: u32_le(descriptor, PATH_TABLE),
+            size: u32_le(descriptor, PATH_TABLE_SIZE),
+        };
+        let root = DirEntry::parse(&descriptor[ROOT_RECORD..ROOT_RECORD + RECORD_LEN + 1])?;
+        fs.volume_id = volume_id;
+        fs.volume_size = volume_size;
+        fs.path_table = path_table;
+        fs.root = root;
+        Ok(fs)
+    }
+
+    /// Returns the underlying sector reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// The volume identifier without trailing padding.
+    pub fn volume_id(&self) -> &[u8] {
+        let len = self
+            .volume_id
+            .iter()
+            .rposition(|&b| b != b' ')
+            .map_or(0, |end| end + 1);
+        &self.volume_id[..len]
+    }
+
+    /// The size of the volume in sectors.
+    pub fn volume_size(&self) -> u32 {
+        self.volume_size
+    }
+
+    /// The root directory.
+    pub fn root(&self) -> DirEntry {
+        self.root
+    }
+
+    fn sector(&mut self, lba: u32) -> Result<&[u8; SECTOR_SIZE], R::Error> {
+        if self.cached != Some(lba) {
+            self.cached = None;
+            self.reader
+                .read_sector(lba, &mut self.cache)
+                .map_err(Error::Read)?;
+            self.cached = Some(lba);
+        }
+        Ok(&self.cache)
+    }
+
+    // Reads bytes from an extent which may span multiple sectors.
+    fn read_at(&mut self, lba: u32, offset: u32, buf: &mut [u8]) -> Result<(), R::Error> {
+        let mut offset = offset as usize;
+        let mut written = 0;
+        while written < buf.len() {
+            let start = offset % SECTOR_SIZE;
+            let n = min(SECTOR_SIZE - start, buf.len() - written);
+            let sector = self.sector(lba + (offset / SECTOR_SIZE) as u32)?;
+            buf[written..written + n].copy_from_slice(&sector[start..start + n]);
+            offset += n;
+            written += n;
+        }
+        Ok(())
+    }
+
+    /// Iterates through the entries in a directory, skipping `.` and `..`.
+    /// Multi-extent files have an entry for each extent.
+    pub fn read_dir(&mut self, dir: &DirEntry) -> Result<ReadDir<'_, R>, R::Error> {
+        if !dir.is_dir() {
+            return Err(Error::NotFound)
+        }
+        Ok(ReadDir {
+            fs: self,
+            extent: dir.extent,
+            offset: 0,
+        })
+    }
+
+    // Reads the `.` entry at the start of a directory to get its size.
+    fn dir_at(&mut self, lba: u32) -> Result<DirEntry, R::Error> {
+        let sector = self.sector(lba)?;
+        let len = sector[0] as usize;
+        let entry = DirEntry::parse(&sector[..len])?;
+        if !entry.is_dir() || !entry.is_special() {
+            return Err(Error::BadRecord)
+        }
+        Ok(entry)
+    }
+
+    /// Finds a directory by walking the path table.
+    pub fn find_dir<P: AsRef<[u8]>>(&mut self, path: P) -> Result<DirEntry, R::Error> {
+        // The root is always the first entry in the path table
+        let mut parent = 1;
+        let mut lba = self.root.extent.lba;
+        for component in components(path.as_ref()) {
+            let mut offset = 0;
+            let mut index = 0;
+            let mut found = None;
+            while offset < self.path_table.size {
+                let mut header = [0; PATH_RECORD_LEN];
+                self.read_at(self.path_table.lba, offset, &mut header)?;
+                let name_len = header[0] as usize;
+                if name_len == 0 {
+                    return Err(Error::BadRecord)
+                }
+                index += 1;
+                if u16_le(&header, 6) == parent && name_len <= MAX_NAME_LEN {
+                    let mut name = [0; MAX_NAME_LEN];
+                    let name = &mut name[..name_len];
+                    self.read_at(self.path_table.lba, offset + PATH_RECORD_LEN as u32, name)?;
+                    if names_match(name, component) {
+                        found = Some((index, u32_le(&header, 2)));
+                        break
+                    }
+                }
+                // Names are padded to an even length
+                offset += (PATH_RECORD_LEN + name_len + (name_len & 1)) as u32;
+            }
+            (parent, lba) = found.ok_or(Error::NotFound)?;
+        }
+        self.dir_at(lba)
+    }
+
+    /// Finds a file or directory entry by its path.
+    pub fn find<P: AsRef<[u8]>>(&mut self, path: P) -> Result<DirEntry, R::Error> {
+        let (dir, name) = split_path(path.as_ref());
+        let dir = self.find_dir(dir)?;
+        if name.is_empty() {
+            return Ok(dir)
+        }
+        for entry in self.read_dir(&dir)? {
+            let entry = entry?;
+            if names_match(entry.identifier(), name) {
+                return Ok(entry)
+            }
+        }
+        Err(Error::NotFound)
+    }
+
+    /// Opens a file, collecting all of its extents.
+    pub fn open<P: AsRef<[u8]>>(&mut self, path: P) -> Result<File, R::Error> {
+        let (dir, name) = split_path(path.as_ref());
+        let dir = self.find_dir(dir)?;
+        let mut file = File::new();
+        for entry in self.read_dir(&dir)? {
+            let entry = entry?;
+            if !names_match(entry.identifier(), name) {
+                continue
+            }
+            if entry.is_dir() {
+                return Err(Error::NotAFile)
+            }
+            file.push(entry.extent)?;
+            if !entry.is_multi_extent() {
+                return Ok(file)
+            }
+        }
+        // A multi-extent file must end with an entry without the flag set
+        if file.len == 0 {
+            Err(Error::NotFound)
+        } else {
+            Err(Error::BadRecord)
+        }
+    }
+
+    /// Reads from `file` starting at `offset` into `buf`. Returns the number
+    /// of bytes read which is less than the length of `buf` at the end of the
+    /// file.
+    pub fn read(&mut self, file: &File, offset: u32, buf: &mut [u8]) -> Result<usize, R::Error> {
+        let end = min(file.size as usize, offset as usize + buf.len());
+        let mut pos = offset as usize;
+        let mut written = 0;
+        let mut extent_start = 0;
+        for extent in file.extents() {
+            let extent_end = extent_start + extent.size as usize;
+            while pos >= extent_start && pos < min(extent_end, end) {
+                let rel = pos - extent_start;
+                let lba = extent.lba + (rel / SECTOR_SIZE) as u32;
+                let start = rel % SECTOR_SIZE;
+                let n = min(SECTOR_SIZE - start, min(extent_end, end) - pos);
+                let dst = &mut buf[written..written + n];
+                if let Ok(dst) = <&mut [u8; SECTOR_SIZE]>::try_from(&mut *dst) {
+                    // Whole sectors skip the cache
+                    self.reader.read_sector(lba, dst).map_err(Error::Read)?;
+                } else {
+                    dst.copy_from_slice(&self.sector(lba)?[start..start + n]);
+                }
+                pos += n;
+                written += n;
+            }
+            extent_start = extent_end;
+        }
+        Ok(written)
+    }
+}
+
+/// An iterator over the entries in a directory created by
+/// [`Filesystem::read_dir`].
+pub struct ReadDir<'a, R: SectorRead> {
+    fs: &'a mut Filesystem<R>,
+    extent: Extent,
+    offset: u32,
+}
+
+impl<'a, R: SectorRead> Iterator for ReadDir<'a, R> {
+    type Item = Result<DirEntry, R::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset < self.extent.size {
+            let lba = self.extent.lba + self.offset / SECTOR_SIZE as u32;
+            let start = self.offset as usize % SECTOR_SIZE;
+            let sector = match self.fs.sector(lba) {
+                Ok(sector) => sector,
+                Err(err) => {
+                    self.offset = self.extent.size;
+                    return Some(Err(err))
+                },
+            };
+            let len = sector[start] as usize;
+            // Records don't cross sector boundaries so the rest is padding
+            if len == 0 {
+                self.offset += (SECTOR_SIZE - start) as u32;
+                continue
+            }
+            let entry = sector
+                .get(start..start + len)
+                .ok_or(Error::BadRecord)
+                .and_then(DirEntry::parse);
+            self.offset += len as u32;
+            match entry {
+                Ok(entry) if entry.is_special() => continue,
+                Ok(entry) => return Some(Ok(entry)),
+                Err(err) => {
+                    self.offset = self.extent.size;
+                    return Some(Err(err))
+                },
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr::addr_of_mut;
+
+    const SECTORS: usize = 28;
+    static mut IMAGE: [u8; SECTORS * SECTOR_SIZE] = [0; SECTORS * SECTOR_SIZE];
+
+    fn sector(image: &mut [u8], lba: usize) -> &mut [u8] {
+        &mut image[lba * SECTOR_SIZE..(lba + 1) * SECTOR_SIZE]
+    }
+
+    fn put_u16(buf: &mut [u8], offset: usize, val: u16) {
+        buf[offset..offset + 2].copy_from_slice(&val.to_le_bytes());
+        buf[offset + 2..offset + 4].copy_from_slice(&val.to_be_bytes());
+    }
+
+    fn put_u32(buf: &mut [u8], offset: usize, val: u32) {
+        buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+        buf[offset + 4..offset + 8].copy_from_slice(&val.to_be_bytes());
+    }
+
+    // Writes a directory record and returns its length.
+    fn record(buf: &mut [u8], lba: u32, size: u32, flags: u8, name: &[u8]) -> usize {
+        let len = RECORD_LEN + name.len() + (!name.len() & 1);
+        buf[0] = len as u8;
+        put_u32(buf, 2, lba);
+        put_u32(buf, 10, size);
+        buf[25] = flags;
+        buf[32] = name.len() as u8;
+        buf[RECORD_LEN..RECORD_LEN + name.len()].copy_from_slice(name);
+        len
+    }
+
+    fn dir(buf: &mut [u8], lba: u32, parent: u32, entries: &[(u32, u32, u8, &[u8])]) {
+        let mut offset = record(buf, lba, 2048, 1 << DIRECTORY, &[0]);
+        offset += record(&mut buf[offset..], parent, 2048, 1 << DIRECTORY, &[1]);
+        for &(lba, size, flags, name) in entries {
+            offset += record(&mut buf[offset..], lba, size, flags, name);
+        }
+    }
+
+    fn path_record(buf: &mut [u8], lba: u32, parent: u16, name: &[u8]) -> usize {
+        buf[0] = name.len() as u8;
+        buf[2..6].copy_from_slice(&lba.to_le_bytes());
+        buf[6..8].copy_from_slice(&parent.to_le_bytes());
+        buf[8..8 + name.len()].copy_from_slice(name);
+        PATH_RECORD_LEN + name.len() + (name.len() & 1)
+    }
+
+    fn fill(image: &mut [u8], lba: usize, len: usize, val: u8) {
+        sector(image, lba)[..len].fill(val);
+    }
+
+    // Builds an image with the layout
+    // /README.TXT
+    // /BIG.BIN (two extents)
+    // /DATA/LEVEL.DAT
+    // /DATA/NESTED/DEEP.BIN
+    fn image() -> &'static [u8] {
+        let image = unsafe { &mut *addr_of_mut!(IMAGE) };
+        image.fill(0);
+
+        let pvd = sector(image, 16);
+        pvd[0] = PRIMARY;
+        pvd[1..6].copy_from_slice(STANDARD_ID);
+        pvd[6] = 1;
+        pvd[VOLUME_ID..VOLUME_ID + VOLUME_ID_LEN].fill(b' ');
+        pvd[VOLUME_ID..VOLUME_ID + 4].copy_from_slice(b"TEST");
+        put_u32(pvd, VOLUME_SIZE, SECTORS as u32);
+        put_u16(pvd, BLOCK_SIZE, SECTOR_SIZE as u16);
+        put_u32(pvd, PATH_TABLE_SIZE, 10 + 12 + 14);
+        pvd[PATH_TABLE..PATH_TABLE + 4].copy_from_slice(&18u32.to_le_bytes());
+        record(&mut pvd[ROOT_RECORD..], 19, 2048, 1 << DIRECTORY, &[0]);
+
+        let terminator = sector(image, 17);
+        terminator[0] = TERMINATOR;
+        terminator[1..6].copy_from_slice(STANDARD_ID);
+
+        let path_table = sector(image, 18);
+        let mut offset = path_record(path_table, 19, 1, &[0]);
+        offset += path_record(&mut path_table[offset..], 20, 1, b"DATA");
+        path_record(&mut path_table[offset..], 21, 2, b"NESTED");
+
+        dir(
+            sector(image, 19),
+            19,
+            19,
+            &[
+                (20, 2048, 1 << DIRECTORY, b"DATA"),
+                (22, 5, 0, b"README.TXT;1"),
+                (23, 2048, 1 << MULTI_EXTENT, b"BIG.BIN;1"),
+                (26, 100, 0, b"BIG.BIN;1"),
+            ],
+        );
+        dir(
+            sector(image, 20),
+            20,
+            19,
+            &[
+                (21, 2048, 1 << DIRECTORY, b"NESTED"),
+                (24, 10, 0, b"LEVEL.DAT;1"),
+            ],
+        );
+        dir(sector(image, 21), 21, 20, &[(25, 4, 0, b"DEEP.BIN;1")]);
+
+        sector(image, 22)[..5].copy_from_slice(b"hello");
+        fill(image, 23, 2048, 0xAA);
+        fill(image, 24, 10, 0x10);
+        fill(image, 25, 4, 0x25);
+        fill(image, 26, 100, 0xBB);
+        image
+    }
+
+    #[test_case]
+    fn primary_volume() {
+        let fs = Filesystem::new(image()).unwrap();
+        assert!(fs.volume_id() == b"TEST");
+        assert!(fs.volume_size() == SECTORS as u32);
+        assert!(
+            fs.root().extent() ==
+                Extent {
+                    lba: 19,
+                    size: 2048
+                }
+        );
+    }
+
+    #[test_case]
+    fn truncated_image() {
+        let image = image();
+        let res = Filesystem::new(&image[..16 * SECTOR_SIZE]);
+        assert!(res.err() == Some(Error::Read(())));
+    }
+
+    #[test_case]
+    fn missing_volume() {
+        image();
+        let image = unsafe { &mut *addr_of_mut!(IMAGE) };
+        // Replace the primary volume descriptor with the terminator
+        sector(image, 16)[0] = TERMINATOR;
+        let res = Filesystem::new(&image[..]);
+        assert!(res.err() == Some(Error::NoPrimaryVolume));
+        // A descriptor without the standard identifier also ends the search
+        sector(image, 16)[1..6].fill(0);
+        let res = Filesystem::new(&image[..]);
+        assert!(res.err() == Some(Error::NoPrimaryVolume));
+    }
+
+    #[test_case]
+    fn read_root() {
+        let mut fs = Filesystem::new(image()).unwrap();
+        let root = fs.root();
+        let mut entries = fs.read_dir(&root).unwrap();
+        let data = entries.next().unwrap().unwrap();
+        assert!(data.name() == b"DATA");
+        assert!(data.is_dir());
+        let readme = entries.next().unwrap().unwrap();
+        assert!(readme.name() == b"README.TXT");
+        assert!(readme.identifier() == b"README.TXT;1");
+        assert!(!readme.is_dir());
+        let big = entries.next().unwrap().unwrap();
+        assert!(big.is_multi_extent());
+        let big = entries.next().unwrap().unwrap();
+        assert!(!big.is_multi_extent());
+        assert!(entries.next().is_none());
+    }
+
+    #[test_case]
+    fn find_dirs() {
+        let mut fs = Filesystem::new(image()).unwrap();
+        assert!(fs.find_dir("/").unwrap().extent().lba == 19);
+        assert!(fs.find_dir("/DATA").unwrap().extent().lba == 20);
+        assert!(fs.find_dir("data\\nested").unwrap().extent().lba == 21);
+        assert!(fs.find_dir("/NESTED").err() == Some(Error::NotFound));
+    }
+
+    #[test_case]
+    fn open_files() {
+        let mut fs = Filesystem::new(image()).unwrap();
+        let mut buf = [0; 16];
+
+        let readme = fs.open("/README.TXT;1").unwrap();
+        assert!(fs.read(&readme, 0, &mut buf).unwrap() == 5);
+        assert!(&buf[..5] == b"hello");
+        assert!(fs.read(&readme, 3, &mut buf).unwrap() == 2);
+        assert!(&buf[..2] == b"lo");
+
+        let level = fs.open("data/level.dat").unwrap();
+        assert!(level.size() == 10);
+        assert!(fs.read(&level, 0, &mut buf).unwrap() == 10);
+        assert!(buf[..10].iter().all(|&b| b == 0x10));
+
+        let deep = fs.find("/DATA/NESTED/DEEP.BIN").unwrap();
+        assert!(deep.extent() == Extent { lba: 25, size: 4 });
+
+        assert!(fs.open("/DATA").err() == Some(Error::NotAFile));
+        assert!(fs.open("/MISSING.BIN").err() == Some(Error::NotFound));
+    }
+
+    #[test_case]
+    fn multi_extent() {
+        let mut fs = Filesystem::new(image()).unwrap();
+        let big = fs.open("BIG.BIN").unwrap();
+        assert!(big.size() == 2148);
+        assert!(
+            big.extents() ==
+                [
+                    Extent {
+                        lba: 23,
+                        size: 2048
+                    },
+                    Extent { lba: 26, size: 100 }
+                ]
+        );
+
+        let mut buf = [0; 8];
+        assert!(fs.read(&big, 2044, &mut buf).unwrap() == 8);
+        assert!(buf == [0xAA, 0xAA, 0xAA, 0xAA, 0xBB, 0xBB, 0xBB, 0xBB]);
+
+        let mut sector = [0; SECTOR_SIZE];
+        assert!(fs.read(&big, 0, &mut sector).unwrap() == SECTOR_SIZE);
+        assert!(sector.iter().all(|&b| b == 0xAA));
+        assert!(fs.read(&big, 2148, &mut buf).unwrap() == 0);
+    }
+}
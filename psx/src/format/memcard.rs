@@ -0,0 +1,828 @@
+//! Memory card filesystem and save file parsing
+//!
+//! A card has 16 blocks of 8 kB. The first block holds the card header and a
+//! directory frame for each of the other 15 blocks. A file is a chain of
+//! blocks linked through their directory frames. Save files start with a
+//! title frame holding the save's title in Shift-JIS and its icon's CLUT
+//! followed by one to three 16x16 4bpp icon frames.
+//!
+//! Frames are read and written through the [`FrameIo`] trait which is
+//! implemented for [`CardSlot`][crate::memcard::CardSlot] and for in-memory
+//! card images.
+
+use crate::format::tim::TIM;
+use crate::gpu::{Bpp, Vertex};
+use core::cmp::min;
+
+pub use crate::memcard::FRAME_SIZE;
+
+/// The number of blocks available for files.
+pub const BLOCKS: usize = 15;
+/// The number of frames in a block.
+pub const FRAMES_PER_BLOCK: u16 = 64;
+/// The size of a block in bytes.
+pub const BLOCK_SIZE: usize = FRAMES_PER_BLOCK as usize * FRAME_SIZE;
+/// The maximum length of a file name.
+pub const MAX_NAME_LEN: usize = 20;
+/// The size of a save's title in bytes.
+pub const TITLE_LEN: usize = 64;
+/// The maximum number of icon frames.
+pub const MAX_ICON_FRAMES: usize = 3;
+/// The size of an icon frame in bytes.
+pub const ICON_FRAME_SIZE: usize = FRAME_SIZE;
+
+const HEADER_MAGIC: &[u8; 2] = b"MC";
+const SAVE_MAGIC: &[u8; 2] = b"SC";
+
+// Directory frame block states.
+const FIRST: u32 = 0x51;
+const MIDDLE: u32 = 0x52;
+const LAST: u32 = 0x53;
+const FREE: u32 = 0xA0;
+// Deleting a file adds this to the states of its blocks.
+const DELETED: u32 = 0x50;
+// Marks the end of a block chain or an unused broken frame entry.
+const NONE: u16 = 0xFFFF;
+
+// The frames after the directory listing broken frames and their
+// replacements.
+const BROKEN_FRAMES: u16 = 20;
+// The frame written to test writes.
+const TEST_FRAME: u16 = 63;
+
+const STATE: usize = 0x00;
+const SIZE: usize = 0x04;
+const NEXT: usize = 0x08;
+const NAME: usize = 0x0A;
+
+const ICON_FLAG: usize = 0x02;
+const BLOCK_COUNT: usize = 0x03;
+const TITLE: usize = 0x04;
+const CLUT: usize = 0x60;
+// The icon flag is this plus the number of icon frames.
+const STATIC_ICON: u8 = 0x10;
+
+// Full-width Shift-JIS characters for ASCII punctuation.
+const PUNCTUATION: [(u8, u16); 19] = [
+    (b' ', 0x8140),
+    (b',', 0x8143),
+    (b'.', 0x8144),
+    (b':', 0x8146),
+    (b'?', 0x8148),
+    (b'!', 0x8149),
+    (b'_', 0x8151),
+    (b'/', 0x815E),
+    (b'\'', 0x8166),
+    (b'"', 0x8168),
+    (b'(', 0x8169),
+    (b')', 0x816A),
+    (b'+', 0x817B),
+    (b'-', 0x817C),
+    (b'=', 0x8181),
+    (b'%', 0x8193),
+    (b'#', 0x8194),
+    (b'&', 0x8195),
+    (b'*', 0x8196),
+];
+
+type Result<T, E> = core::result::Result<T, Error<E>>;
+
+/// A memory card filesystem error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error<E> {
+    /// A frame couldn't be read or written.
+    Io(E),
+    /// The card header is missing, i.e. the card isn't formatted.
+    BadHeader,
+    /// A frame's checksum doesn't match its contents.
+    BadChecksum,
+    /// A directory frame or block chain is malformed.
+    BadEntry,
+    /// A save's title frame is malformed.
+    BadSave,
+    /// A name is longer than [`MAX_NAME_LEN`].
+    NameTooLong,
+    /// A title doesn't fit in [`TITLE_LEN`] bytes.
+    TitleTooLong,
+    /// The file doesn't exist.
+    NotFound,
+    /// A file with the same name already exists.
+    AlreadyExists,
+    /// There aren't enough free blocks.
+    NoSpace,
+}
+
+/// A source and sink of memory card frames.
+pub trait FrameIo {
+    /// The error returned when a frame can't be read or written.
+    type Error;
+
+    /// Reads `frame` into `buf`.
+    fn read_frame(
+        &mut self, frame: u16, buf: &mut [u8; FRAME_SIZE],
+    ) -> core::result::Result<(), Self::Error>;
+
+    /// Writes `data` to `frame`.
+    fn write_frame(
+        &mut self, frame: u16, data: &[u8; FRAME_SIZE],
+    ) -> core::result::Result<(), Self::Error>;
+}
+
+/// An in-memory card image. Accessing frames past the end of the image fails.
+impl FrameIo for &mut [u8] {
+    type Error = ();
+
+    fn read_frame(
+        &mut self, frame: u16, buf: &mut [u8; FRAME_SIZE],
+    ) -> core::result::Result<(), ()> {
+        let start = frame as usize * FRAME_SIZE;
+        let src = self.get(start..start + FRAME_SIZE).ok_or(())?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: u16, data: &[u8; FRAME_SIZE]) -> core::result::Result<(), ()> {
+        let start = frame as usize * FRAME_SIZE;
+        let dst = self.get_mut(start..start + FRAME_SIZE).ok_or(())?;
+        dst.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Computes the checksum stored in the last byte of header and directory
+/// frames.
+pub fn checksum(frame: &[u8; FRAME_SIZE]) -> u8 {
+    frame[..FRAME_SIZE - 1]
+        .iter()
+        .fold(0, |sum, byte| sum ^ byte)
+}
+
+fn seal(frame: &mut [u8; FRAME_SIZE]) {
+    frame[FRAME_SIZE - 1] = checksum(frame);
+}
+
+fn u32_le(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn u16_le(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+// Gets the first frame of a block numbered from 0 for the directory block.
+fn block_frame(block: usize) -> u16 {
+    block as u16 * FRAMES_PER_BLOCK
+}
+
+/// Converts ASCII text to full-width Shift-JIS characters for a save title.
+/// Characters without a full-width equivalent are kept as single bytes.
+/// Returns the number of bytes written or `None` if `title` doesn't fit.
+pub fn ascii_to_shift_jis(ascii: &[u8], title: &mut [u8; TITLE_LEN]) -> Option<usize> {
+    let mut len = 0;
+    for &c in ascii {
+        let sjis = match c {
+            b'0'..=b'9' => Some(0x824F + (c - b'0') as u16),
+            b'A'..=b'Z' => Some(0x8260 + (c - b'A') as u16),
+            b'a'..=b'z' => Some(0x8281 + (c - b'a') as u16),
+            _ => PUNCTUATION.iter().find(|&&(a, _)| a == c).map(|&(_, s)| s),
+        };
+        match sjis {
+            Some(sjis) => {
+                title
+                    .get_mut(len..len + 2)?
+                    .copy_from_slice(&sjis.to_be_bytes());
+                len += 2;
+            },
+            None => {
+                *title.get_mut(len)? = c;
+                len += 1;
+            },
+        }
+    }
+    title[len..].fill(0);
+    Some(len)
+}
+
+/// Converts a full-width Shift-JIS character to ASCII.
+pub fn shift_jis_to_ascii(sjis: u16) -> Option<u8> {
+    match sjis {
+        0x824F..=0x8258 => Some(b'0' + (sjis - 0x824F) as u8),
+        0x8260..=0x8279 => Some(b'A' + (sjis - 0x8260) as u8),
+        0x8281..=0x829A => Some(b'a' + (sjis - 0x8281) as u8),
+        _ => PUNCTUATION
+            .iter()
+            .find(|&&(_, s)| s == sjis)
+            .map(|&(a, _)| a),
+    }
+}
+
+/// The state of a block in its directory frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockState {
+    /// The block was never used.
+    Free,
+    /// The first block of a file.
+    First,
+    /// A block in the middle of a file.
+    Middle,
+    /// The last block of a file with more than one block.
+    Last,
+    /// A block of a deleted file which may be reused.
+    Deleted,
+    /// An unrecognized state.
+    Unknown(u32),
+}
+
+impl BlockState {
+    fn from_raw(state: u32) -> Self {
+        match state {
+            FIRST => BlockState::First,
+            MIDDLE => BlockState::Middle,
+            LAST => BlockState::Last,
+            FREE => BlockState::Free,
+            s if (FIRST + DELETED..=LAST + DELETED).contains(&s) => BlockState::Deleted,
+            _ => BlockState::Unknown(state),
+        }
+    }
+
+    /// Checks if the block may be allocated to a new file.
+    pub fn is_free(&self) -> bool {
+        matches!(self, BlockState::Free | BlockState::Deleted)
+    }
+}
+
+/// A block's directory frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirEntry {
+    state: u32,
+    size: u32,
+    next: u16,
+    name: [u8; MAX_NAME_LEN],
+}
+
+impl DirEntry {
+    const FREE: Self = DirEntry {
+        state: FREE,
+        size: 0,
+        next: NONE,
+        name: [0; MAX_NAME_LEN],
+    };
+
+    fn parse<E>(frame: &[u8; FRAME_SIZE]) -> Result<Self, E> {
+        if checksum(frame) != frame[FRAME_SIZE - 1] {
+            return Err(Error::BadChecksum)
+        }
+        Ok(DirEntry {
+            state: u32_le(frame, STATE),
+            size: u32_le(frame, SIZE),
+            next: u16_le(frame, NEXT),
+            name: frame[NAME..NAME + MAX_NAME_LEN].try_into().unwrap(),
+        })
+    }
+
+    fn to_frame(&self) -> [u8; FRAME_SIZE] {
+        let mut frame = [0; FRAME_SIZE];
+        frame[STATE..STATE + 4].copy_from_slice(&self.state.to_le_bytes());
+        frame[SIZE..SIZE + 4].copy_from_slice(&self.size.to_le_bytes());
+        frame[NEXT..NEXT + 2].copy_from_slice(&self.next.to_le_bytes());
+        frame[NAME..NAME + MAX_NAME_LEN].copy_from_slice(&self.name);
+        seal(&mut frame);
+        frame
+    }
+
+    /// The block's state.
+    pub fn state(&self) -> BlockState {
+        BlockState::from_raw(self.state)
+    }
+
+    /// The file name without padding. Only the first block of a file has a
+    /// name, e.g. `BASLUS-00000SAVE`.
+    pub fn name(&self) -> &[u8] {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(MAX_NAME_LEN);
+        &self.name[..len]
+    }
+
+    /// The file size in bytes. This is only set in the first block of a file.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The index of the file's next block or `None` for the last block.
+    pub fn next(&self) -> Option<usize> {
+        match self.next {
+            NONE => None,
+            next => Some(next as usize),
+        }
+    }
+}
+
+/// A file's blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct File {
+    len: u8,
+    blocks: [u8; BLOCKS],
+}
+
+impl File {
+    /// The file size in bytes.
+    pub fn size(&self) -> u32 {
+        (self.len as usize * BLOCK_SIZE) as u32
+    }
+
+    /// The indices of the file's blocks in order, from 0 for the first block
+    /// after the directory.
+    pub fn blocks(&self) -> &[u8] {
+        &self.blocks[..self.len as usize]
+    }
+
+    // Gets the frame holding the byte at `offset`.
+    fn frame(&self, offset: usize) -> u16 {
+        let block = self.blocks[offset / BLOCK_SIZE] as usize + 1;
+        block_frame(block) + ((offset % BLOCK_SIZE) / FRAME_SIZE) as u16
+    }
+}
+
+/// A save's animated 16x16 4bpp icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Icon {
+    clut: [u16; 16],
+    len: u8,
+    frames: [[u8; ICON_FRAME_SIZE]; MAX_ICON_FRAMES],
+}
+
+impl Icon {
+    /// Creates a single frame icon. Frames are 16 rows of 8 bytes with the
+    /// left pixel in each byte's low nibble like 4bpp textures in VRAM.
+    pub fn new(clut: [u16; 16], frame: &[u8; ICON_FRAME_SIZE]) -> Self {
+        let mut frames = [[0; ICON_FRAME_SIZE]; MAX_ICON_FRAMES];
+        frames[0] = *frame;
+        Icon {
+            clut,
+            len: 1,
+            frames,
+        }
+    }
+
+    /// Creates a single frame icon from a 16x16 4bpp TIM. Returns `None` if the
+    /// TIM has a different size or depth or its CLUT is too small.
+    pub fn from_tim<const N: usize, const M: usize>(tim: &TIM<N, M>) -> Option<Self> {
+        // The bitmaps start with the GP0 command and VRAM rectangle
+        let pixels = tim.bmp.data.get(3..)?;
+        let colors = tim.clut.data.get(3..)?;
+        if tim.bpp != Bpp::Bits4 || tim.bmp.size != Vertex(4, 16) || tim.clut.size.0 < 16 {
+            return None
+        }
+        let mut clut = [0; 16];
+        for (i, color) in clut.iter_mut().enumerate() {
+            *color = (colors.get(i / 2)? >> (16 * (i % 2))) as u16;
+        }
+        let mut frame = [0; ICON_FRAME_SIZE];
+        for (bytes, word) in frame.chunks_exact_mut(4).zip(pixels) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        Some(Icon::new(clut, &frame))
+    }
+
+    /// Adds an animation frame. Returns `None` if the icon already has
+    /// [`MAX_ICON_FRAMES`] frames.
+    pub fn add_frame(&mut self, frame: &[u8; ICON_FRAME_SIZE]) -> Option<&mut Self> {
+        *self.frames.get_mut(self.len as usize)? = *frame;
+        self.len += 1;
+        Some(self)
+    }
+
+    /// The icon's 15-bit colors.
+    pub fn clut(&self) -> &[u16; 16] {
+        &self.clut
+    }
+
+    /// The icon's animation frames.
+    pub fn frames(&self) -> &[[u8; ICON_FRAME_SIZE]] {
+        &self.frames[..self.len as usize]
+    }
+}
+
+/// A save file's title frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveHeader {
+    icon_frames: u8,
+    blocks: u8,
+    title: [u8; TITLE_LEN],
+    clut: [u16; 16],
+}
+
+impl SaveHeader {
+    /// Parses a save's title frame.
+    pub fn parse<E>(frame: &[u8; FRAME_SIZE]) -> Result<Self, E> {
+        let icon_frames = frame[ICON_FLAG].wrapping_sub(STATIC_ICON);
+        let blocks = frame[BLOCK_COUNT];
+        if &frame[..2] != SAVE_MAGIC ||
+            !(1..=MAX_ICON_FRAMES as u8).contains(&icon_frames) ||
+            !(1..=BLOCKS as u8).contains(&blocks)
+        {
+            return Err(Error::BadSave)
+        }
+        let mut clut = [0; 16];
+        for (i, color) in clut.iter_mut().enumerate() {
+            *color = u16_le(frame, CLUT + 2 * i);
+        }
+        Ok(SaveHeader {
+            icon_frames,
+            blocks,
+            title: frame[TITLE..TITLE + TITLE_LEN].try_into().unwrap(),
+            clut,
+        })
+    }
+
+    fn to_frame(&self) -> [u8; FRAME_SIZE] {
+        let mut frame = [0; FRAME_SIZE];
+        frame[..2].copy_from_slice(SAVE_MAGIC);
+        frame[ICON_FLAG] = STATIC_ICON + self.icon_frames;
+        frame[BLOCK_COUNT] = self.blocks;
+        frame[TITLE..TITLE + TITLE_LEN].copy_from_slice(&self.title);
+        for (i, color) in self.clut.iter().enumerate() {
+            frame[CLUT + 2 * i..][..2].copy_from_slice(&color.to_le_bytes());
+        }
+        frame
+    }
+
+    /// The title in Shift-JIS without padding.
+    pub fn title(&self) -> &[u8] {
+        let len = self.title.iter().position(|&b| b == 0).unwrap_or(TITLE_LEN);
+        &self.title[..len]
+    }
+
+    /// The number of icon frames.
+    pub fn icon_frames(&self) -> usize {
+        self.icon_frames as usize
+    }
+
+    /// The number of blocks in the save.
+    pub fn blocks(&self) -> usize {
+        self.blocks as usize
+    }
+
+    /// The icon's 15-bit colors.
+    pub fn clut(&self) -> &[u16; 16] {
+        &self.clut
+    }
+}
+
+/// A memory card filesystem accessed through a [`FrameIo`].
+pub struct Filesystem<F: FrameIo> {
+    io: F,
+    dir: [DirEntry; BLOCKS],
+}
+
+impl<F: FrameIo> Filesystem<F> {
+    /// Reads the card header and directory from `io`.
+    pub fn new(io: F) -> Result<Self, F::Error> {
+        let mut fs = Filesystem {
+            io,
+            dir: [DirEntry::FREE; BLOCKS],
+        };
+        let mut frame = [0; FRAME_SIZE];
+        fs.read_frame(0, &mut frame)?;
+        if &frame[..2] != HEADER_MAGIC || checksum(&frame) != frame[FRAME_SIZE - 1] {
+            return Err(Error::BadHeader)
+        }
+        for block in 0..BLOCKS {
+            fs.read_frame(block as u16 + 1, &mut frame)?;
+            fs.dir[block] = DirEntry::parse(&frame)?;
+        }
+        Ok(fs)
+    }
+
+    /// Formats the card, erasing all files.
+    pub fn format(io: F) -> Result<Self, F::Error> {
+        let mut fs = Filesystem {
+            io,
+            dir: [DirEntry::FREE; BLOCKS],
+        };
+        let mut header = [0; FRAME_SIZE];
+        header[..2].copy_from_slice(HEADER_MAGIC);
+        seal(&mut header);
+        fs.write_frame(0, &header)?;
+        for block in 0..BLOCKS {
+            fs.write_entry(block)?;
+        }
+        let mut broken = [0; FRAME_SIZE];
+        broken[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        broken[NEXT..NEXT + 2].copy_from_slice(&NONE.to_le_bytes());
+        seal(&mut broken);
+        let first_broken = BLOCKS as u16 + 1;
+        for frame in first_broken..first_broken + BROKEN_FRAMES {
+            fs.write_frame(frame, &broken)?;
+        }
+        fs.write_frame(TEST_FRAME, &header)?;
+        Ok(fs)
+    }
+
+    /// Returns the underlying frame accessor.
+    pub fn into_inner(self) -> F {
+        self.io
+    }
+
+    fn read_frame(&mut self, frame: u16, buf: &mut [u8; FRAME_SIZE]) -> Result<(), F::Error> {
+        self.io.read_frame(frame, buf).map_err(Error::Io)
+    }
+
+    fn write_frame(&mut self, frame: u16, data: &[u8; FRAME_SIZE]) -> Result<(), F::Error> {
+        self.io.write_frame(frame, data).map_err(Error::Io)
+    }
+
+    fn write_entry(&mut self, block: usize) -> Result<(), F::Error> {
+        let frame = self.dir[block].to_frame();
+        self.write_frame(block as u16 + 1, &frame)
+    }
+
+    /// The directory frames of the 15 blocks after the directory.
+    pub fn entries(&self) -> &[DirEntry; BLOCKS] {
+        &self.dir
+    }
+
+    /// Iterates through the directory frames of the first block of each file.
+    pub fn files(&self) -> impl Iterator<Item = &DirEntry> {
+        self.dir.iter().filter(|e| e.state() == BlockState::First)
+    }
+
+    /// The number of blocks which may be allocated to new files.
+    pub fn free_blocks(&self) -> usize {
+        self.dir.iter().filter(|e| e.state().is_free()).count()
+    }
+
+    fn find(&self, name: &[u8]) -> Option<usize> {
+        self.dir
+            .iter()
+            .position(|e| e.state() == BlockState::First && e.name() == name)
+    }
+
+    /// Opens a file by following its block chain.
+    pub fn open<N: AsRef<[u8]>>(&self, name: N) -> Result<File, F::Error> {
+        let first = self.find(name.as_ref()).ok_or(Error::NotFound)?;
+        let mut file = File {
+            len: 0,
+            blocks: [0; BLOCKS],
+        };
+        let mut block = Some(first);
+        while let Some(idx) = block {
+            // A chain longer than the number of blocks must have a loop
+            let slot = file
+                .blocks
+                .get_mut(file.len as usize)
+                .ok_or(Error::BadEntry)?;
+            let entry = self.dir.get(idx).ok_or(Error::BadEntry)?;
+            if file.len != 0 && !matches!(entry.state(), BlockState::Middle | BlockState::Last) {
+                return Err(Error::BadEntry)
+            }
+            *slot = idx as u8;
+            file.len += 1;
+            block = entry.next();
+        }
+        if self.dir[first].size != file.size() {
+            return Err(Error::BadEntry)
+        }
+        Ok(file)
+    }
+
+    /// Creates a file of `blocks` blocks. Its contents are left as they were.
+    pub fn create<N: AsRef<[u8]>>(&mut self, name: N, blocks: usize) -> Result<File, F::Error> {
+        let name = name.as_ref();
+        if name.len() > MAX_NAME_LEN {
+            return Err(Error::NameTooLong)
+        }
+        if self.find(name).is_some() {
+            return Err(Error::AlreadyExists)
+        }
+        if blocks == 0 || blocks > self.free_blocks() {
+            return Err(Error::NoSpace)
+        }
+        let mut file = File {
+            len: blocks as u8,
+            blocks: [0; BLOCKS],
+        };
+        let free = (0..BLOCKS).filter(|&b| self.dir[b].state().is_free());
+        for (slot, block) in file.blocks[..blocks].iter_mut().zip(free) {
+            *slot = block as u8;
+        }
+        for (i, &block) in file.blocks().iter().enumerate() {
+            let mut entry = DirEntry::FREE;
+            entry.state = match i {
+                0 => FIRST,
+                _ if i + 1 == blocks => LAST,
+                _ => MIDDLE,
+            };
+            if let Some(&next) = file.blocks().get(i + 1) {
+                entry.next = next as u16;
+            }
+            if i == 0 {
+                entry.size = file.size();
+                entry.name[..name.len()].copy_from_slice(name);
+            }
+            self.dir[block as usize] = entry;
+        }
+        for i in 0..blocks {
+            self.write_entry(file.blocks[i] as usize)?;
+        }
+        Ok(file)
+    }
+
+    /// Deletes a file, marking its blocks as reusable.
+    pub fn delete<N: AsRef<[u8]>>(&mut self, name: N) -> Result<(), F::Error> {
+        let file = self.open(name)?;
+        for &block in file.blocks() {
+            self.dir[block as usize].state += DELETED;
+            self.write_entry(block as usize)?;
+        }
+        Ok(())
+    }
+
+    /// Reads from `file` starting at `offset` into `buf`. Returns the number
+    /// of bytes read which is less than the length of `buf` at the end of the
+    /// file.
+    pub fn read(&mut self, file: &File, offset: u32, buf: &mut [u8]) -> Result<usize, F::Error> {
+        let end = min(file.size() as usize, offset as usize + buf.len());
+        let mut pos = offset as usize;
+        let mut frame = [0; FRAME_SIZE];
+        while pos < end {
+            let start = pos % FRAME_SIZE;
+            let n = min(FRAME_SIZE - start, end - pos);
+            self.read_frame(file.frame(pos), &mut frame)?;
+            let written = pos - offset as usize;
+            buf[written..written + n].copy_from_slice(&frame[start..start + n]);
+            pos += n;
+        }
+        Ok(pos.saturating_sub(offset as usize))
+    }
+
+    /// Writes `data` to `file` starting at `offset`. Returns the number of
+    /// bytes written which is less than the length of `data` at the end of the
+    /// file.
+    pub fn write(&mut self, file: &File, offset: u32, data: &[u8]) -> Result<usize, F::Error> {
+        let end = min(file.size() as usize, offset as usize + data.len());
+        let mut pos = offset as usize;
+        let mut frame = [0; FRAME_SIZE];
+        while pos < end {
+            let start = pos % FRAME_SIZE;
+            let n = min(FRAME_SIZE - start, end - pos);
+            let idx = file.frame(pos);
+            // Partial frames keep the rest of their contents
+            if n != FRAME_SIZE {
+                self.read_frame(idx, &mut frame)?;
+            }
+            let read = pos - offset as usize;
+            frame[start..start + n].copy_from_slice(&data[read..read + n]);
+            self.write_frame(idx, &frame)?;
+            pos += n;
+        }
+        Ok(pos.saturating_sub(offset as usize))
+    }
+
+    /// Creates a save file with a title frame and icon. `title` is converted
+    /// with [`ascii_to_shift_jis`] and the save's data starts after the icon
+    /// frames.
+    pub fn create_save<N: AsRef<[u8]>>(
+        &mut self, name: N, title: &[u8], icon: &Icon, blocks: usize,
+    ) -> Result<File, F::Error> {
+        let mut header = SaveHeader {
+            icon_frames: icon.len,
+            blocks: blocks as u8,
+            title: [0; TITLE_LEN],
+            clut: icon.clut,
+        };
+        ascii_to_shift_jis(title, &mut header.title).ok_or(Error::TitleTooLong)?;
+        let file = self.create(name, blocks)?;
+        let first = file.frame(0);
+        self.write_frame(first, &header.to_frame())?;
+        for (i, frame) in icon.frames().iter().enumerate() {
+            self.write_frame(first + 1 + i as u16, frame)?;
+        }
+        Ok(file)
+    }
+
+    /// Reads a save file's title frame.
+    pub fn save_header(&mut self, file: &File) -> Result<SaveHeader, F::Error> {
+        let mut frame = [0; FRAME_SIZE];
+        self.read_frame(file.frame(0), &mut frame)?;
+        SaveHeader::parse(&frame)
+    }
+
+    /// Reads a save file's icon.
+    pub fn icon(&mut self, file: &File) -> Result<Icon, F::Error> {
+        let header = self.save_header(file)?;
+        let mut icon = Icon {
+            clut: header.clut,
+            len: header.icon_frames,
+            frames: [[0; ICON_FRAME_SIZE]; MAX_ICON_FRAMES],
+        };
+        let first = file.frame(0);
+        for i in 0..icon.len as usize {
+            self.read_frame(first + 1 + i as u16, &mut icon.frames[i])?;
+        }
+        Ok(icon)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr::addr_of_mut;
+
+    const CARD_SIZE: usize = (BLOCKS + 1) * BLOCK_SIZE;
+    static mut CARD: [u8; CARD_SIZE] = [0; CARD_SIZE];
+
+    fn card() -> &'static mut [u8] {
+        let card = unsafe { &mut *addr_of_mut!(CARD) };
+        card.fill(0xFF);
+        card
+    }
+
+    #[test_case]
+    fn format() {
+        let fs = Filesystem::format(card()).unwrap();
+        assert!(fs.free_blocks() == BLOCKS);
+        let card = fs.into_inner();
+        assert!(&card[..2] == b"MC" && card[FRAME_SIZE - 1] == b'M' ^ b'C');
+        assert!(card[FRAME_SIZE..FRAME_SIZE + 4] == [0xA0, 0, 0, 0]);
+        assert!(card[16 * FRAME_SIZE..16 * FRAME_SIZE + 4] == [0xFF; 4]);
+        assert!(Filesystem::new(card).is_ok());
+        assert!(Filesystem::new(&mut [0u8; 2 * FRAME_SIZE][..]).err() == Some(Error::BadHeader));
+    }
+
+    #[test_case]
+    fn files() {
+        let mut fs = Filesystem::format(card()).unwrap();
+        let save = fs.create("BASLUS-00000SAVE", 1).unwrap();
+        let level = fs.create("BASLUS-00000LEVEL", 3).unwrap();
+        assert!(save.blocks() == [0] && level.blocks() == [1, 2, 3]);
+        assert!(fs.create("BASLUS-00000SAVE", 1).err() == Some(Error::AlreadyExists));
+        assert!(fs.create("BASLUS-00000BIG", 12).err() == Some(Error::NoSpace));
+
+        let data = [0x5A; 300];
+        assert!(fs.write(&level, BLOCK_SIZE as u32 - 100, &data).unwrap() == 300);
+        let end = level.size() - 10;
+        assert!(fs.write(&level, end, &data).unwrap() == 10);
+
+        let mut fs = Filesystem::new(fs.into_inner()).unwrap();
+        let level = fs.open("BASLUS-00000LEVEL").unwrap();
+        assert!(level.blocks() == [1, 2, 3]);
+        let mut buf = [0; 302];
+        fs.read(&level, BLOCK_SIZE as u32 - 101, &mut buf).unwrap();
+        assert!(buf[0] == 0xFF && buf[1..301] == data && buf[301] == 0xFF);
+        assert!(fs.read(&level, end + 5, &mut buf).unwrap() == 5);
+        assert!(fs.files().count() == 2);
+
+        fs.delete("BASLUS-00000SAVE").unwrap();
+        assert!(fs.open("BASLUS-00000SAVE").err() == Some(Error::NotFound));
+        assert!(fs.entries()[0].state() == BlockState::Deleted);
+        let big = fs.create("BASLUS-00000BIG", 12).unwrap();
+        assert!(big.blocks()[0] == 0 && big.blocks()[11] == 14);
+    }
+
+    #[test_case]
+    fn save() {
+        let mut fs = Filesystem::format(card()).unwrap();
+        let mut clut = [0; 16];
+        clut[1] = 0x7FFF;
+        let mut icon = Icon::new(clut, &[0x11; ICON_FRAME_SIZE]);
+        icon.add_frame(&[0x22; ICON_FRAME_SIZE]).unwrap();
+        let file = fs
+            .create_save("BASLUS-00000GAME", b"Game 1", &icon, 2)
+            .unwrap();
+        let header = fs.save_header(&file).unwrap();
+        assert!(
+            header.title() ==
+                [0x82, 0x66, 0x82, 0x81, 0x82, 0x8D, 0x82, 0x85, 0x81, 0x40, 0x82, 0x50]
+        );
+        assert!(header.icon_frames() == 2 && header.blocks() == 2);
+        assert!(fs.icon(&file).unwrap() == icon);
+        let ascii = header
+            .title()
+            .chunks(2)
+            .map(|c| shift_jis_to_ascii(u16::from_be_bytes([c[0], c[1]])).unwrap());
+        assert!(ascii.eq(b"Game 1".iter().copied()));
+        let mut title = [0; TITLE_LEN];
+        assert!(ascii_to_shift_jis(&[b'a'; 33], &mut title).is_none());
+        assert!(ascii_to_shift_jis(b"~", &mut title) == Some(1));
+    }
+
+    #[test_case]
+    fn icon_from_tim() {
+        let tim = crate::include_tim!("../../test_files/icon.tim");
+        let icon = Icon::from_tim(&tim).unwrap();
+        assert!(icon.clut()[0..2] == [0x0000, 0x7FFF] && icon.clut()[15] == 0x421A);
+        assert!(icon.frames().len() == 1);
+        assert!(icon.frames()[0][0..4] == [0x10, 0x32, 0x54, 0x76]);
+        let font = crate::include_tim!("../../font.tim");
+        assert!(Icon::from_tim(&font).is_none());
+    }
+}
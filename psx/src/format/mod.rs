@@ -1,3 +1,9 @@
 //! Support for parsing various file formats
+pub mod bs;
+pub mod iso9660;
+pub mod memcard;
 pub mod obj;
+pub mod seq;
 pub mod tim;
+pub mod vab;
+pub mod vag;
@@ -313,17 +313,12 @@ macro_rules! include_obj {
                     if count_u16(obj, i) == 4 {
                         i += 2;
                         let a = parse_u16(obj, &mut i);
+                        // Skip the texture coordinate index to get the normal index
                         while obj[i] != b'/' {
                             i += 1;
                         }
-                        while obj[i] != b'/' {
-                            i += 1;
-                        }
-                        let norm = parse_u16(obj, &mut i);
-                        while obj[i] != b' ' {
-                            i += 1;
-                        }
                         i += 1;
+                        let norm = parse_u16(obj, &mut i);
                         let b = parse_u16(obj, &mut i);
                         while obj[i] != b' ' {
                             i += 1;
@@ -339,22 +334,17 @@ macro_rules! include_obj {
                             i += 1;
                         }
                         quads[n] = [a - 1, b - 1, d - 1, c - 1];
-                        quad_norms[n] = norm;
+                        quad_norms[n] = norm - 1;
                         n += 1;
                     } else if count_u16(obj, i) == 3 {
                         i += 2;
                         let a = parse_u16(obj, &mut i);
+                        // Skip the texture coordinate index to get the normal index
                         while obj[i] != b'/' {
                             i += 1;
                         }
-                        while obj[i] != b'/' {
-                            i += 1;
-                        }
-                        let norm = parse_u16(obj, &mut i);
-                        while obj[i] != b' ' {
-                            i += 1;
-                        }
                         i += 1;
+                        let norm = parse_u16(obj, &mut i);
                         let b = parse_u16(obj, &mut i);
                         while obj[i] != b' ' {
                             i += 1;
@@ -365,7 +355,7 @@ macro_rules! include_obj {
                             i += 1;
                         }
                         tris[m] = [a - 1, b - 1, c - 1];
-                        tri_norms[m] = norm;
+                        tri_norms[m] = norm - 1;
                         m += 1;
                     }
                 }
@@ -389,6 +379,19 @@ macro_rules! include_obj {
     }};
 }
 
+#[cfg(test)]
+mod face_tests {
+    #[test_case]
+    fn cube_normals() {
+        let cube = include_obj!("../../test_files/cube.obj");
+        assert!(cube.quads.len() == 6 && cube.tris.is_empty());
+        // Each face of the cube uses its own normal in order
+        assert!(*cube.quad_norms == [0, 1, 2, 3, 4, 5]);
+        assert!(cube.quads[0] == [0, 4, 2, 6]);
+        assert!(cube.normals[cube.quad_norms[0] as usize][1].0 == 0x100);
+    }
+}
+
 #[cfg(feature = "nonexistent_feature")]
 mod tests {
     use super::*;
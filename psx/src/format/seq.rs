@@ -0,0 +1,436 @@
+//! SEQ and SEP sequence parsing
+//!
+//! SEQ files hold a single MIDI-like track after a big-endian header while SEP
+//! files hold multiple sequences with their own headers. Events use MIDI
+//! channel messages with running status, but meta events don't have a length
+//! byte.
+
+/// The magic bytes at the start of SEQ and SEP files.
+pub const MAGIC: [u8; 4] = *b"pQES";
+
+const SEQ_VERSION: u32 = 1;
+const SEP_VERSION: u16 = 0;
+const SEQ_HEADER_LEN: usize = 15;
+const SEP_HEADER_LEN: usize = 6;
+const SEP_ENTRY_LEN: usize = 13;
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const KEY_PRESSURE: u8 = 0xA0;
+const CONTROL_CHANGE: u8 = 0xB0;
+const PROGRAM_CHANGE: u8 = 0xC0;
+const CHANNEL_PRESSURE: u8 = 0xD0;
+const PITCH_BEND: u8 = 0xE0;
+const META: u8 = 0xFF;
+
+const META_TEMPO: u8 = 0x51;
+const META_END: u8 = 0x2F;
+
+// The center value of a 14-bit pitch bend.
+const BEND_CENTER: i16 = 0x2000;
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// A SEQ or SEP parsing error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The file doesn't start with `pQES`.
+    BadMagic,
+    /// The version isn't 1 for SEQ files or 0 for SEP files.
+    UnsupportedVersion,
+    /// The file ends in the middle of a header or event.
+    Truncated,
+    /// An event has an unsupported status byte or meta event type.
+    BadEvent,
+}
+
+fn u16_be(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn u24_be(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([0, data[offset], data[offset + 1], data[offset + 2]])
+}
+
+fn u32_be(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// A single sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seq<'a> {
+    /// The sequence's ID in a SEP file or 0 for SEQ files.
+    pub id: u16,
+    /// The number of ticks per quarter note.
+    pub resolution: u16,
+    /// The initial tempo in microseconds per quarter note.
+    pub tempo: u32,
+    /// The time signature's numerator and denominator.
+    pub rhythm: (u8, u8),
+    /// The events.
+    pub data: &'a [u8],
+}
+
+impl<'a> Seq<'a> {
+    /// Parses a SEQ file.
+    pub fn parse(file: &'a [u8]) -> Result<Self> {
+        if file.len() < SEQ_HEADER_LEN {
+            return Err(Error::Truncated)
+        }
+        if file[0..4] != MAGIC {
+            return Err(Error::BadMagic)
+        }
+        if u32_be(file, 4) != SEQ_VERSION {
+            return Err(Error::UnsupportedVersion)
+        }
+        Ok(Seq {
+            id: 0,
+            resolution: u16_be(file, 8),
+            tempo: u24_be(file, 10),
+            rhythm: (file[13], file[14]),
+            data: &file[SEQ_HEADER_LEN..],
+        })
+    }
+
+    /// The initial tempo in beats per minute.
+    pub fn bpm(&self) -> u32 {
+        60_000_000 / self.tempo.max(1)
+    }
+
+    /// Iterates through the sequence's events.
+    pub fn events(&self) -> Events<'a> {
+        Events {
+            data: self.data,
+            pos: 0,
+            status: 0,
+            done: false,
+        }
+    }
+}
+
+/// A SEP file holding multiple sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sep<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Sep<'a> {
+    /// Parses a SEP file's header.
+    pub fn parse(file: &'a [u8]) -> Result<Self> {
+        if file.len() < SEP_HEADER_LEN {
+            return Err(Error::Truncated)
+        }
+        if file[0..4] != MAGIC {
+            return Err(Error::BadMagic)
+        }
+        if u16_be(file, 4) != SEP_VERSION {
+            return Err(Error::UnsupportedVersion)
+        }
+        Ok(Sep {
+            data: &file[SEP_HEADER_LEN..],
+        })
+    }
+
+    /// Iterates through the sequences in the file.
+    pub fn iter(&self) -> SepIter<'a> {
+        SepIter { data: self.data }
+    }
+
+    /// Finds a sequence by its ID.
+    pub fn get(&self, id: u16) -> Result<Option<Seq<'a>>> {
+        for seq in self.iter() {
+            let seq = seq?;
+            if seq.id == id {
+                return Ok(Some(seq))
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// An iterator over the sequences in a [`Sep`].
+pub struct SepIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for SepIter<'a> {
+    type Item = Result<Seq<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None
+        }
+        let data = self.data;
+        if data.len() < SEP_ENTRY_LEN {
+            self.data = &[];
+            return Some(Err(Error::Truncated))
+        }
+        let len = u32_be(data, 9) as usize;
+        let events = match data.get(SEP_ENTRY_LEN..SEP_ENTRY_LEN + len) {
+            Some(events) => events,
+            None => {
+                self.data = &[];
+                return Some(Err(Error::Truncated))
+            },
+        };
+        self.data = &data[SEP_ENTRY_LEN + len..];
+        Some(Ok(Seq {
+            id: u16_be(data, 0),
+            resolution: u16_be(data, 2),
+            tempo: u24_be(data, 4),
+            rhythm: (data[7], data[8]),
+            data: events,
+        }))
+    }
+}
+
+/// A sequence event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Starts a note. Note on events with zero velocity are parsed as
+    /// [`Event::NoteOff`].
+    NoteOn {
+        /// The MIDI channel.
+        channel: u8,
+        /// The note number where 60 is middle C.
+        note: u8,
+        /// The velocity ranging from 1 to 127.
+        velocity: u8,
+    },
+    /// Releases a note.
+    NoteOff {
+        /// The MIDI channel.
+        channel: u8,
+        /// The note number.
+        note: u8,
+    },
+    /// Changes a controller's value.
+    ControlChange {
+        /// The MIDI channel.
+        channel: u8,
+        /// The controller number.
+        controller: u8,
+        /// The new value.
+        value: u8,
+    },
+    /// Changes a channel's program.
+    ProgramChange {
+        /// The MIDI channel.
+        channel: u8,
+        /// The program number.
+        program: u8,
+    },
+    /// Bends the pitch of a channel's notes.
+    PitchBend {
+        /// The MIDI channel.
+        channel: u8,
+        /// The bend ranging from -0x2000 to 0x1FFF.
+        value: i16,
+    },
+    /// Changes the tempo in microseconds per quarter note.
+    Tempo(u32),
+    /// Marks the end of the sequence.
+    EndOfTrack,
+    /// A key or channel pressure event which doesn't affect playback.
+    Pressure,
+}
+
+/// An iterator over a sequence's events and the number of ticks before each
+/// one. Cloning the iterator saves its position.
+#[derive(Debug, Clone)]
+pub struct Events<'a> {
+    data: &'a [u8],
+    pos: usize,
+    status: u8,
+    done: bool,
+}
+
+impl<'a> Events<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self.data.get(self.pos).ok_or(Error::Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn var_len(&mut self) -> Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        Err(Error::BadEvent)
+    }
+
+    fn event(&mut self) -> Result<(u32, Event)> {
+        let delta = self.var_len()?;
+        let mut first = self.byte()?;
+        // Meta events don't affect the running status
+        if first == META {
+            let event = match self.byte()? {
+                META_TEMPO => {
+                    let tempo = u32::from_be_bytes([0, self.byte()?, self.byte()?, self.byte()?]);
+                    Event::Tempo(tempo)
+                },
+                META_END => Event::EndOfTrack,
+                _ => return Err(Error::BadEvent),
+            };
+            return Ok((delta, event))
+        }
+        if first & 0x80 != 0 {
+            self.status = first;
+            first = self.byte()?;
+        }
+        let channel = self.status & 0xF;
+        let event = match self.status & 0xF0 {
+            NOTE_OFF => {
+                self.byte()?;
+                Event::NoteOff {
+                    channel,
+                    note: first,
+                }
+            },
+            NOTE_ON => match self.byte()? {
+                0 => Event::NoteOff {
+                    channel,
+                    note: first,
+                },
+                velocity => Event::NoteOn {
+                    channel,
+                    note: first,
+                    velocity,
+                },
+            },
+            KEY_PRESSURE => {
+                self.byte()?;
+                Event::Pressure
+            },
+            CONTROL_CHANGE => Event::ControlChange {
+                channel,
+                controller: first,
+                value: self.byte()?,
+            },
+            PROGRAM_CHANGE => Event::ProgramChange {
+                channel,
+                program: first,
+            },
+            CHANNEL_PRESSURE => Event::Pressure,
+            PITCH_BEND => {
+                let value = (first as i16 | (self.byte()? as i16) << 7) - BEND_CENTER;
+                Event::PitchBend { channel, value }
+            },
+            // This also rejects data bytes without a running status
+            _ => return Err(Error::BadEvent),
+        };
+        Ok((delta, event))
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Result<(u32, Event)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.pos >= self.data.len() {
+            return None
+        }
+        let res = self.event();
+        // Stop after the end of the track or an error
+        self.done = !matches!(res, Ok((_, event)) if event != Event::EndOfTrack);
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Event, Sep, Seq};
+
+    const SEQ: [u8; 36] = [
+        b'p', b'Q', b'E', b'S', 0, 0, 0, 1, // header
+        0x01, 0xE0, // 480 ticks per quarter note
+        0x07, 0xA1, 0x20, // 500000 us per quarter note
+        4, 2, // 4/4
+        0x00, 0xC0, 0x05, // program change
+        0x00, 0x91, 60, 100, // note on
+        0x83, 0x60, 60, 0, // running status note off after 480 ticks
+        0x00, 0xE1, 0x00, 0x40, // centered pitch bend
+        0x00, 0xFF, 0x51, 0x07, 0xA1, 0x20,
+    ];
+
+    #[test_case]
+    fn parse_seq() {
+        let mut file = [0; SEQ.len() + 3];
+        file[..SEQ.len()].copy_from_slice(&SEQ);
+        file[SEQ.len()..].copy_from_slice(&[0x00, 0xFF, 0x2F]);
+        let seq = Seq::parse(&file).unwrap();
+        assert!(seq.resolution == 480);
+        assert!(seq.tempo == 500_000);
+        assert!(seq.bpm() == 120);
+        assert!(seq.rhythm == (4, 2));
+
+        let mut events = seq.events();
+        let program = Event::ProgramChange {
+            channel: 0,
+            program: 5,
+        };
+        assert!(events.next() == Some(Ok((0, program))));
+        let note_on = Event::NoteOn {
+            channel: 1,
+            note: 60,
+            velocity: 100,
+        };
+        assert!(events.next() == Some(Ok((0, note_on))));
+        let note_off = Event::NoteOff {
+            channel: 1,
+            note: 60,
+        };
+        assert!(events.next() == Some(Ok((480, note_off))));
+        let bend = Event::PitchBend {
+            channel: 1,
+            value: 0,
+        };
+        assert!(events.next() == Some(Ok((0, bend))));
+        assert!(events.next() == Some(Ok((0, Event::Tempo(500_000)))));
+        assert!(events.next() == Some(Ok((0, Event::EndOfTrack))));
+        assert!(events.next().is_none());
+    }
+
+    #[test_case]
+    fn truncated_seq() {
+        assert!(Seq::parse(&SEQ[..10]) == Err(Error::Truncated));
+        let mut bad = SEQ;
+        bad[0] = 0;
+        assert!(Seq::parse(&bad) == Err(Error::BadMagic));
+        let seq = Seq::parse(&SEQ[..SEQ.len() - 1]).unwrap();
+        assert!(seq.events().last() == Some(Err(Error::Truncated)));
+    }
+
+    #[test_case]
+    fn parse_sep() {
+        let file = [
+            b'p', b'Q', b'E', b'S', 0, 0, // header
+            0, 0, 0, 96, 0x07, 0xA1, 0x20, 4, 2, 0, 0, 0, 3, // first sequence
+            0x00, 0xFF, 0x2F, // first sequence's events
+            0, 1, 0, 96, 0x0F, 0x42, 0x40, 4, 2, 0, 0, 0, 3, // second sequence
+            0x00, 0xFF, 0x2F, // second sequence's events
+        ];
+        let sep = Sep::parse(&file).unwrap();
+        let mut seqs = sep.iter();
+        let first = seqs.next().unwrap().unwrap();
+        assert!(first.id == 0);
+        assert!(first.resolution == 96);
+        assert!(first.events().next() == Some(Ok((0, Event::EndOfTrack))));
+        let second = seqs.next().unwrap().unwrap();
+        assert!(second.id == 1);
+        assert!(second.tempo == 1_000_000);
+        assert!(seqs.next().is_none());
+        assert!(sep.get(1).unwrap() == Some(second));
+        assert!(sep.get(2).unwrap().is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! VAB instrument bank parsing
+//!
+//! VAB banks are split into a VH header with the program and tone attributes
+//! and a VB body with the concatenated SPU ADPCM data of each VAG. The VB body
+//! is uploaded to sound RAM as-is while the VH header is parsed here.
+
+/// The magic bytes at the start of a VH header.
+pub const MAGIC: [u8; 4] = *b"pBAV";
+/// The number of programs in a bank.
+pub const MAX_PROGRAMS: usize = 128;
+/// The number of tones in each program.
+pub const TONES_PER_PROGRAM: usize = 16;
+/// The maximum number of VAGs in a bank.
+pub const MAX_VAGS: usize = 254;
+
+const HEADER_LEN: usize = 32;
+const PROGRAM_LEN: usize = 16;
+const TONE_LEN: usize = 32;
+const VAG_TABLE_LEN: usize = 256 * 2;
+const PROGRAMS: usize = HEADER_LEN;
+const TONES: usize = PROGRAMS + MAX_PROGRAMS * PROGRAM_LEN;
+
+// VAG sizes are stored in units of 8 bytes.
+const VAG_SIZE_SHIFT: u32 = 3;
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// A VAB parsing error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The header doesn't start with `pBAV`.
+    BadMagic,
+    /// The header is shorter than its program count says.
+    Truncated,
+    /// The header has more programs or VAGs than a bank can hold.
+    TooMany,
+}
+
+fn u16_le(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_le(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// The attributes of a program (i.e. an instrument).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Program {
+    /// The number of tones in the program.
+    pub tones: u8,
+    /// The volume ranging from 0 to 127.
+    pub volume: u8,
+    /// The priority used when voices are stolen.
+    pub priority: u8,
+    /// The program's mode.
+    pub mode: u8,
+    /// The pan ranging from 0 (left) to 127 (right) where 64 is centered.
+    pub pan: u8,
+    /// The program's attributes.
+    pub attr: u16,
+}
+
+impl Program {
+    fn parse(data: &[u8]) -> Self {
+        Program {
+            tones: data[0],
+            volume: data[1],
+            priority: data[2],
+            mode: data[3],
+            pan: data[4],
+            attr: u16_le(data, 6),
+        }
+    }
+}
+
+/// The attributes of a tone (i.e. a sample mapped to a range of notes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tone {
+    /// The priority used when voices are stolen.
+    pub priority: u8,
+    /// The tone's mode where 4 sends it to the reverb unit.
+    pub mode: u8,
+    /// The volume ranging from 0 to 127.
+    pub volume: u8,
+    /// The pan ranging from 0 (left) to 127 (right) where 64 is centered.
+    pub pan: u8,
+    /// The note which plays the VAG at 44.1 kHz.
+    pub center: u8,
+    /// The fine tuning of the center note in 1/128ths of a semitone.
+    pub fine_tune: u8,
+    /// The lowest note the tone plays.
+    pub min_note: u8,
+    /// The highest note the tone plays.
+    pub max_note: u8,
+    /// The pitch bend range below the note in semitones.
+    pub bend_min: u8,
+    /// The pitch bend range above the note in semitones.
+    pub bend_max: u8,
+    /// The lower half of the ADSR envelope settings.
+    pub adsr1: u16,
+    /// The upper half of the ADSR envelope settings.
+    pub adsr2: u16,
+    /// The program the tone belongs to.
+    pub program: i16,
+    /// The VAG the tone plays starting at 1.
+    pub vag: i16,
+}
+
+impl Tone {
+    fn parse(data: &[u8]) -> Self {
+        Tone {
+            priority: data[0],
+            mode: data[1],
+            volume: data[2],
+            pan: data[3],
+            center: data[4],
+            fine_tune: data[5],
+            min_note: data[6],
+            max_note: data[7],
+            bend_min: data[12],
+            bend_max: data[13],
+            adsr1: u16_le(data, 16),
+            adsr2: u16_le(data, 18),
+            program: u16_le(data, 20) as i16,
+            vag: u16_le(data, 22) as i16,
+        }
+    }
+
+    /// Checks if the tone plays `note`.
+    pub fn plays(&self, note: u8) -> bool {
+        self.min_note <= note && note <= self.max_note
+    }
+
+    /// The ADSR envelope settings as a single value.
+    pub fn adsr(&self) -> u32 {
+        self.adsr1 as u32 | (self.adsr2 as u32) << 16
+    }
+}
+
+/// A parsed VH header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vab<'a> {
+    data: &'a [u8],
+    /// The bank's ID.
+    pub id: u32,
+    /// The number of programs with tones.
+    pub programs: u16,
+    /// The total number of tones.
+    pub tones: u16,
+    /// The number of VAGs.
+    pub vags: u16,
+    /// The master volume ranging from 0 to 127.
+    pub volume: u8,
+    /// The master pan ranging from 0 (left) to 127 (right) where 64 is
+    /// centered.
+    pub pan: u8,
+}
+
+impl<'a> Vab<'a> {
+    /// Parses a VH header.
+    pub fn parse(vh: &'a [u8]) -> Result<Self> {
+        if vh.len() < TONES {
+            return Err(Error::Truncated)
+        }
+        if vh[0..4] != MAGIC {
+            return Err(Error::BadMagic)
+        }
+        let programs = u16_le(vh, 0x12);
+        let vags = u16_le(vh, 0x16);
+        if programs as usize > MAX_PROGRAMS || vags as usize > MAX_VAGS {
+            return Err(Error::TooMany)
+        }
+        let len = TONES + programs as usize * TONES_PER_PROGRAM * TONE_LEN + VAG_TABLE_LEN;
+        if vh.len() < len {
+            return Err(Error::Truncated)
+        }
+        Ok(Vab {
+            data: &vh[..len],
+            id: u32_le(vh, 0x08),
+            programs,
+            tones: u16_le(vh, 0x14),
+            vags,
+            volume: vh[0x18],
+            pan: vh[0x19],
+        })
+    }
+
+    /// The size of the VH header in bytes.
+    pub fn header_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Gets a program's attributes.
+    pub fn program(&self, program: u8) -> Option<Program> {
+        let offset = PROGRAMS + program as usize * PROGRAM_LEN;
+        let data = self.data.get(offset..offset + PROGRAM_LEN)?;
+        Some(Program::parse(data))
+    }
+
+    // Tone attributes are only stored for programs with tones, so the index of
+    // the program's block of tones must be counted.
+    fn tone_block(&self, program: u8) -> Option<usize> {
+        let prog = self.program(program)?;
+        if prog.tones == 0 {
+            return None
+        }
+        let block = (0..program)
+            .filter(|&p| self.program(p).map_or(false, |p| p.tones != 0))
+            .count();
+        if block < self.programs as usize {
+            Some(block)
+        } else {
+            None
+        }
+    }
+
+    /// Iterates through a program's tones.
+    pub fn tones(&self, program: u8) -> impl Iterator<Item = Tone> + 'a {
+        let data = self.data;
+        let (block, count) = match (self.tone_block(program), self.program(program)) {
+            (Some(block), Some(prog)) => (block, prog.tones.min(TONES_PER_PROGRAM as u8)),
+            _ => (0, 0),
+        };
+        (0..count as usize).map(move |tone| {
+            let offset = TONES + (block * TONES_PER_PROGRAM + tone) * TONE_LEN;
+            Tone::parse(&data[offset..offset + TONE_LEN])
+        })
+    }
+
+    /// Gets the offset of a VAG in the VB body and its size in bytes. VAGs
+    /// are numbered starting at 1.
+    pub fn vag(&self, vag: u16) -> Option<(u32, u32)> {
+        if vag == 0 || vag > self.vags {
+            return None
+        }
+        let table = self.data.len() - VAG_TABLE_LEN;
+        let size_of = |n: u16| (u16_le(self.data, table + n as usize * 2) as u32) << VAG_SIZE_SHIFT;
+        let offset = (1..vag).map(size_of).sum();
+        Some((offset, size_of(vag)))
+    }
+
+    /// The size of the VB body in bytes.
+    pub fn body_size(&self) -> u32 {
+        self.vag(self.vags)
+            .map_or(0, |(offset, size)| offset + size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr::addr_of_mut;
+
+    const PROGS: usize = 2;
+    const LEN: usize = TONES + PROGS * TONES_PER_PROGRAM * TONE_LEN + VAG_TABLE_LEN;
+    static mut VH: [u8; LEN] = [0; LEN];
+
+    // Builds a bank where program 0 has no tones, program 1 has two tones
+    // split at middle C and program 3 has one tone.
+    fn vh() -> &'static [u8] {
+        let vh = unsafe { &mut *addr_of_mut!(VH) };
+        vh.fill(0);
+        vh[0..4].copy_from_slice(&MAGIC);
+        vh[4] = 7;
+        vh[0x12] = PROGS as u8;
+        vh[0x14] = 3;
+        vh[0x16] = 2;
+        vh[0x18] = 127;
+        vh[0x19] = 64;
+        for (program, tones) in [(1, 2), (3, 1)] {
+            let offset = PROGRAMS + program * PROGRAM_LEN;
+            vh[offset] = tones;
+            vh[offset + 1] = 100;
+            vh[offset + 4] = 64;
+        }
+        let tones: [(usize, u8, u8, u16); 3] = [(0, 0, 59, 1), (1, 60, 127, 2), (16, 0, 127, 1)];
+        for (idx, min, max, vag) in tones {
+            let offset = TONES + idx * TONE_LEN;
+            let tone = &mut vh[offset..offset + TONE_LEN];
+            tone[2] = 127;
+            tone[4] = 60;
+            tone[6] = min;
+            tone[7] = max;
+            tone[16..18].copy_from_slice(&0x80FFu16.to_le_bytes());
+            tone[22..24].copy_from_slice(&vag.to_le_bytes());
+        }
+        let table = LEN - VAG_TABLE_LEN;
+        vh[table + 2..table + 4].copy_from_slice(&(0x100u16).to_le_bytes());
+        vh[table + 4..table + 6].copy_from_slice(&(0x20u16).to_le_bytes());
+        vh
+    }
+
+    #[test_case]
+    fn parse_vab() {
+        let vab = Vab::parse(vh()).unwrap();
+        assert!(vab.header_size() == LEN);
+        assert!(vab.programs == 2);
+        assert!(vab.volume == 127);
+        assert!(vab.program(1).unwrap().tones == 2);
+        assert!(vab.program(128).is_none());
+        assert!(Vab::parse(&vh()[..LEN - 1]) == Err(Error::Truncated));
+    }
+
+    #[test_case]
+    fn tones() {
+        let vab = Vab::parse(vh()).unwrap();
+        assert!(vab.tones(0).count() == 0);
+        let mut tones = vab.tones(1);
+        let low = tones.next().unwrap();
+        let high = tones.next().unwrap();
+        assert!(tones.next().is_none());
+        assert!(low.plays(59) && !low.plays(60));
+        assert!(high.plays(60) && high.vag == 2);
+        assert!(high.adsr() == 0x80FF);
+        let only = vab.tones(3).next().unwrap();
+        assert!(only.vag == 1 && only.plays(0));
+    }
+
+    #[test_case]
+    fn vags() {
+        let vab = Vab::parse(vh()).unwrap();
+        assert!(vab.vag(0).is_none());
+        assert!(vab.vag(1) == Some((0, 0x800)));
+        assert!(vab.vag(2) == Some((0x800, 0x100)));
+        assert!(vab.vag(3).is_none());
+        assert!(vab.body_size() == 0x900);
+    }
+}
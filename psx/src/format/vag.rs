@@ -0,0 +1,190 @@
+//! VAG file parsing
+//!
+//! VAG files hold mono SPU ADPCM sample data after a 48-byte big-endian header.
+
+#[doc(hidden)]
+pub const MAGIC: u32 = u32::from_le_bytes(*b"VAGp");
+/// The size of the VAG header in 4-byte words.
+pub const HEADER_WORDS: usize = 12;
+/// The size of an SPU ADPCM block in bytes.
+pub const BLOCK_SIZE: usize = 16;
+
+const NAME_LEN: usize = 16;
+
+/// Validates and includes a [`VAG`][`crate::format::vag::VAG`] file.
+#[macro_export]
+macro_rules! include_vag {
+    ($file:literal) => {{
+        use core::mem::transmute;
+        use $crate::file_size;
+        use $crate::format::vag::{Error, Header, HEADER_WORDS, VAG};
+
+        const VAG_SIZE: usize = (file_size!($file) + 3) / 4;
+        const VAG_DATA: [u32; VAG_SIZE] = {
+            let data = *include_bytes!($file);
+            if data.len() % 4 != 0 {
+                panic!("VAG size isn't a multiple of 4 bytes");
+            }
+            unsafe { transmute(data) }
+        };
+        const HEADER: Header = match Header::parse(&VAG_DATA) {
+            Ok(header) => header,
+            Err(Error::BadMagic) => panic!("VAG file has invalid magic bytes"),
+            Err(Error::UnsupportedVersion) => panic!("VAG file has an unsupported version"),
+            Err(Error::BadSampleRate) => panic!("VAG file has an invalid sample rate"),
+            Err(Error::BadDataSize) => {
+                panic!("VAG data size isn't a multiple of the ADPCM block size")
+            },
+            Err(Error::Truncated) => panic!("VAG file is shorter than its header says"),
+        };
+        const DATA_LEN: usize = HEADER.data_size as usize / 4;
+        static DATA: [u32; DATA_LEN] = {
+            let mut data = [0; DATA_LEN];
+            let mut i = 0;
+            while i < DATA_LEN {
+                data[i] = VAG_DATA[HEADER_WORDS + i];
+                i += 1;
+            }
+            data
+        };
+        VAG {
+            version: HEADER.version,
+            sample_rate: HEADER.sample_rate,
+            name: HEADER.name,
+            data: &DATA,
+        }
+    }};
+}
+
+/// A VAG parsing error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The file doesn't start with `VAGp`.
+    BadMagic,
+    /// The version isn't 2, 3 or 0x20.
+    UnsupportedVersion,
+    /// The sample rate is zero.
+    BadSampleRate,
+    /// The data size isn't a multiple of the 16-byte ADPCM block size.
+    BadDataSize,
+    /// The file is shorter than the header says.
+    Truncated,
+}
+
+/// The fields of a VAG header.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: u32,
+    pub data_size: u32,
+    pub sample_rate: u32,
+    pub name: [u8; NAME_LEN],
+}
+
+impl Header {
+    /// Validates the header at the start of `words` against the length of
+    /// `words`.
+    pub const fn parse(words: &[u32]) -> Result<Self, Error> {
+        if words.len() < HEADER_WORDS {
+            return Err(Error::Truncated)
+        }
+        if words[0] != MAGIC {
+            return Err(Error::BadMagic)
+        }
+        let version = u32::from_be(words[1]);
+        if !matches!(version, 2 | 3 | 0x20) {
+            return Err(Error::UnsupportedVersion)
+        }
+        let data_size = u32::from_be(words[3]);
+        if data_size as usize % BLOCK_SIZE != 0 {
+            return Err(Error::BadDataSize)
+        }
+        if words.len() - HEADER_WORDS < data_size as usize / 4 {
+            return Err(Error::Truncated)
+        }
+        let sample_rate = u32::from_be(words[4]);
+        if sample_rate == 0 {
+            return Err(Error::BadSampleRate)
+        }
+        let mut name = [0; NAME_LEN];
+        let mut i = 0;
+        while i < NAME_LEN {
+            name[i] = (words[8 + i / 4] >> ((i % 4) * 8)) as u8;
+            i += 1;
+        }
+        Ok(Header {
+            version,
+            data_size,
+            sample_rate,
+            name,
+        })
+    }
+}
+
+/// A reference to VAG sample data in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VAG<'a> {
+    /// The VAG format version.
+    pub version: u32,
+    /// The sample rate in Hz.
+    pub sample_rate: u32,
+    /// The name stored in the header padded with null bytes.
+    pub name: [u8; NAME_LEN],
+    /// The SPU ADPCM blocks.
+    pub data: &'a [u32],
+}
+
+impl<'a> VAG<'a> {
+    /// Parses a VAG file loaded into memory. Any data after the size given in
+    /// the header is ignored.
+    pub fn parse(words: &'a [u32]) -> Result<Self, Error> {
+        let header = Header::parse(words)?;
+        let data_len = header.data_size as usize / 4;
+        Ok(VAG {
+            version: header.version,
+            sample_rate: header.sample_rate,
+            name: header.name,
+            data: &words[HEADER_WORDS..HEADER_WORDS + data_len],
+        })
+    }
+
+    /// The name stored in the header without padding.
+    pub fn name(&self) -> &[u8] {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+        &self.name[..len]
+    }
+
+    /// The number of ADPCM blocks.
+    pub fn blocks(&self) -> usize {
+        self.data.len() * 4 / BLOCK_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, VAG};
+
+    #[test_case]
+    fn include_vag() {
+        let beep = include_vag!("../../test_files/beep.vag");
+        assert!(beep.version == 0x20);
+        assert!(beep.sample_rate == 22050);
+        assert!(beep.name() == b"test");
+        assert!(beep.blocks() == 3);
+        assert!(beep.data[..4] == [0; 4]);
+    }
+
+    #[test_case]
+    fn parse_vag() {
+        let file = crate::include_words!("../../test_files/beep.vag");
+        let beep = VAG::parse(file).unwrap();
+        assert!(beep.sample_rate == 22050);
+        assert!(beep.data.len() == 12);
+        assert!(VAG::parse(&file[..file.len() - 1]) == Err(Error::Truncated));
+        assert!(VAG::parse(&file[..4]) == Err(Error::Truncated));
+        let mut bad = [0; 24];
+        bad.copy_from_slice(file);
+        bad[0] = 0;
+        assert!(VAG::parse(&bad) == Err(Error::BadMagic));
+    }
+}
@@ -0,0 +1,306 @@
+use super::{pack, Renderer, Transform};
+use crate::format::obj::ObjRef;
+use crate::gpu::primitives::{PolyG3, PolyGT3};
+use crate::gpu::{Color, Packet, TexColor, TexCoord};
+use crate::hw::gte;
+use crate::hw::gte::{BBK, BFC, DQA, DQB, GBK, GFC, RBK, RFC, RGB2, RGBC, VXY0, VZ0};
+use crate::hw::gte::{L11_12, L13_21, L22_23, L31_32, L33};
+use crate::hw::gte::{LR11_12, LR13_21, LR22_23, LR31_32, LR33};
+use crate::hw::Register;
+use crate::math::{Mat3, Vec3};
+
+/// The maximum number of directional lights.
+pub const MAX_LIGHTS: usize = 3;
+
+// The color which leaves textures unchanged in textured polygons.
+const NEUTRAL: Color = Color::new(0x80, 0x80, 0x80);
+
+/// A directional light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Light {
+    /// The direction the light shines in as a unit vector in world
+    /// coordinates.
+    pub direction: Vec3,
+    /// The light's color.
+    pub color: Color,
+}
+
+/// Blends colors towards a far color between two depths, e.g. for fog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthCue {
+    /// The color polygons fade to.
+    pub color: Color,
+    /// The view Z where polygons start fading.
+    pub near: u16,
+    /// The view Z where polygons are fully faded. This must be larger than
+    /// `near`.
+    pub far: u16,
+}
+
+/// The lights, ambient color and depth cueing used to shade meshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lighting {
+    /// The directional lights. Unused lights are `None`.
+    pub lights: [Option<Light>; MAX_LIGHTS],
+    /// The color added to all polygons regardless of the lights, also called
+    /// the back color.
+    pub ambient: Color,
+    /// The depth cueing or `None` to disable it.
+    pub depth_cue: Option<DepthCue>,
+}
+
+impl Lighting {
+    /// Creates lighting with only an ambient color.
+    pub const fn new(ambient: Color) -> Self {
+        Lighting {
+            lights: [None; MAX_LIGHTS],
+            ambient,
+            depth_cue: None,
+        }
+    }
+
+    /// Sets light `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` isn't less than [`MAX_LIGHTS`].
+    pub fn set_light(&mut self, i: usize, light: Light) -> &mut Self {
+        self.lights[i] = Some(light);
+        self
+    }
+
+    /// Sets the depth cueing.
+    pub fn set_depth_cue(&mut self, depth_cue: DepthCue) -> &mut Self {
+        self.depth_cue = Some(depth_cue);
+        self
+    }
+
+    // The light matrix for normals in model coordinates. Each row is the
+    // direction towards a light so multiplying a normal gives each light's
+    // intensity.
+    fn light_matrix(&self, model: &Mat3) -> Mat3 {
+        let rows = self
+            .lights
+            .map(|light| light.map_or(Vec3::ZERO, |l| -l.direction));
+        Mat3::from_rows(rows) * *model
+    }
+
+    // The light color matrix. Each column is a light's color.
+    fn color_matrix(&self) -> Mat3 {
+        let colors = self
+            .lights
+            .map(|light| light.map_or(Color::new(0, 0, 0), |l| l.color));
+        let [a, b, c] = colors.map(|c| [c.red, c.green, c.blue].map(intensity));
+        Mat3([[a[0], b[0], c[0]], [a[1], b[1], c[1]], [a[2], b[2], c[2]]])
+    }
+}
+
+// Converts a color component to 1.3.12 fixed-point where 0xFF is almost 1.0.
+fn intensity(component: u8) -> i16 {
+    (component as i16) << 4
+}
+
+// Computes DQA and DQB so IR0 goes from 0 at `near` to 1.0 at `far`. The GTE
+// interpolates linearly in H / Z so the coefficients are computed from the
+// projected distances.
+fn depth_cue_coefficients(h: u16, near: u16, far: u16) -> (i16, i32) {
+    let projected = |z: u16| ((h as i64) << 16) / (z as i64).max(1);
+    let (near, far) = (projected(near), projected(far));
+    if near <= far {
+        return (0, 0)
+    }
+    let dqa = ((1 << 24) / (far - near)).clamp(i16::MIN as i64, i16::MAX as i64);
+    (dqa as i16, (-near * dqa) as i32)
+}
+
+// Gets the normal index of triangle `n` in the order given by `triangles`.
+fn face_normal(obj: &ObjRef, n: usize) -> u16 {
+    let quad_tris = obj.quads.len() * 2;
+    if n < quad_tris {
+        obj.quad_norms[n / 2]
+    } else {
+        obj.tri_norms[n - quad_tris]
+    }
+}
+
+// Shades triangle `n` with the loaded lighting. OBJ faces have a single
+// normal so all three vertices get the same color. This uses the single vector
+// NCCS and NCDS since NCCT and NCDT would compute that color three times.
+fn shade(obj: &ObjRef, n: usize, color: Color, depth_cue: bool) -> Color {
+    let [x, y, z] = obj.normals[face_normal(obj, n) as usize];
+    VXY0::skip_load().assign(pack(x.0 << 4, y.0 << 4)).store();
+    VZ0::skip_load().assign(z.0 << 4).store();
+    let rgbc = color.red as u32 | (color.green as u32) << 8 | (color.blue as u32) << 16;
+    RGBC::skip_load().assign(rgbc).store();
+    if depth_cue {
+        gte::ncds();
+    } else {
+        gte::nccs();
+    }
+    let rgb = RGB2::new().to_bits();
+    Color::new(rgb as u8, (rgb >> 8) as u8, (rgb >> 16) as u8)
+}
+
+impl Renderer<'_> {
+    /// Loads `lighting` into the GTE for [`draw_lit`][Self::draw_lit] and
+    /// [`draw_lit_textured`][Self::draw_lit_textured].
+    pub fn set_lighting(&mut self, lighting: &Lighting) -> &mut Self {
+        let [lr11_12, lr13_21, lr22_23, lr31_32, lr33] = lighting.color_matrix().to_words();
+        LR11_12::skip_load().assign(lr11_12).store();
+        LR13_21::skip_load().assign(lr13_21).store();
+        LR22_23::skip_load().assign(lr22_23).store();
+        LR31_32::skip_load().assign(lr31_32).store();
+        LR33::skip_load().assign(lr33 as i16).store();
+        let ambient = lighting.ambient;
+        RBK::skip_load()
+            .assign(intensity(ambient.red) as i32)
+            .store();
+        GBK::skip_load()
+            .assign(intensity(ambient.green) as i32)
+            .store();
+        BBK::skip_load()
+            .assign(intensity(ambient.blue) as i32)
+            .store();
+        if let Some(depth_cue) = lighting.depth_cue {
+            let far = depth_cue.color;
+            RFC::skip_load().assign(intensity(far.red) as i32).store();
+            GFC::skip_load().assign(intensity(far.green) as i32).store();
+            BFC::skip_load().assign(intensity(far.blue) as i32).store();
+            let (dqa, dqb) = depth_cue_coefficients(self.h, depth_cue.near, depth_cue.far);
+            DQA::skip_load().assign(dqa).store();
+            DQB::skip_load().assign(dqb).store();
+        }
+        self.lighting = *lighting;
+        self
+    }
+
+    // Loads the light matrix for a model's normals.
+    fn load_lights(&self, model: &Mat3) {
+        let words = self.lighting.light_matrix(model).to_words();
+        let [l11_12, l13_21, l22_23, l31_32, l33] = words;
+        L11_12::skip_load().assign(l11_12).store();
+        L13_21::skip_load().assign(l13_21).store();
+        L22_23::skip_load().assign(l22_23).store();
+        L31_32::skip_load().assign(l31_32).store();
+        L33::skip_load().assign(l33 as i16).store();
+    }
+
+    /// Draws `obj` lit by the lighting from
+    /// [`set_lighting`][Self::set_lighting] using the face normals.
+    /// `colors` has a color for each triangle in the order given by
+    /// [`triangles`][super::triangles]. OBJ faces have a single normal, so
+    /// all of a triangle's vertices get the same color.
+    ///
+    /// Each triangle is shaded once with NCCS, or NCDS when depth cueing is
+    /// enabled, rather than with the three vector NCCT or NCDT.
+    pub fn draw_lit(
+        &mut self, obj: &ObjRef, model: &Transform, colors: &[Color],
+        packets: &mut [Packet<PolyG3>],
+    ) -> usize {
+        self.load_lights(&model.rotation);
+        let depth_cue = self.lighting.depth_cue.is_some();
+        self.draw(obj, model, packets, |poly, n, _, vertices| {
+            let color = shade(obj, n, colors[n], depth_cue);
+            poly.set_vertices(vertices).set_colors([color; 3]);
+        })
+    }
+
+    /// Draws `obj` with textured triangles lit by the lighting from
+    /// [`set_lighting`][Self::set_lighting] using the face normals.
+    /// `tex_coords` has the texture coordinates for each triangle in the order
+    /// given by [`triangles`][super::triangles]. The packets' CLUT and texture
+    /// page must already be set.
+    pub fn draw_lit_textured(
+        &mut self, obj: &ObjRef, model: &Transform, tex_coords: &[[TexCoord; 3]],
+        packets: &mut [Packet<PolyGT3>],
+    ) -> usize {
+        self.load_lights(&model.rotation);
+        let depth_cue = self.lighting.depth_cue.is_some();
+        self.draw(obj, model, packets, |poly, n, _, vertices| {
+            // Shading the neutral color gives a color which modulates the texture
+            let Color { red, green, blue } = shade(obj, n, NEUTRAL, depth_cue);
+            let color = TexColor { red, green, blue };
+            poly.set_vertices(vertices)
+                .set_tex_coords(tex_coords[n])
+                .set_colors([color; 3]);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{depth_cue_coefficients, face_normal, Light, Lighting};
+    use crate::format::obj::ObjRef;
+    use crate::gpu::Color;
+    use crate::math::{Mat3, Vec3, FRAC_PI_2};
+
+    #[test_case]
+    fn matrices() {
+        let mut lighting = Lighting::new(Color::new(0, 0, 0));
+        lighting.set_light(
+            1,
+            Light {
+                direction: Vec3::Y,
+                color: Color::new(0xFF, 0x80, 0),
+            },
+        );
+        // A normal facing up (-Y) is fully lit by a light shining down (+Y)
+        let l = lighting.light_matrix(&Mat3::IDENTITY);
+        assert!(l * -Vec3::Y == Vec3::new(0, 4096, 0));
+        // The model's X axis faces up after rotating by -π/2 about Z
+        let l = lighting.light_matrix(&Mat3::rotation_z(-FRAC_PI_2));
+        assert!(l * Vec3::X == Vec3::new(0, 4096, 0));
+        let lr = lighting.color_matrix();
+        assert!(lr.column(1) == Vec3::new(0xFF0, 0x800, 0));
+        assert!(lr.column(0) == Vec3::ZERO && lr.column(2) == Vec3::ZERO);
+    }
+
+    #[test_case]
+    fn depth_cue() {
+        // H / Z goes from 0x4000 at the near depth to 0x2000 at the far depth
+        let (dqa, dqb) = depth_cue_coefficients(256, 1024, 2048);
+        assert!(dqa == -2048 && dqb == 0x2000000);
+        let ir0 = |projected: i64| (projected * dqa as i64 + dqb as i64) >> 12;
+        assert!(ir0(0x4000) == 0 && ir0(0x2000) == 0x1000);
+        assert!(depth_cue_coefficients(256, 2048, 1024) == (0, 0));
+    }
+
+    #[test_case]
+    fn cube() {
+        let cube = crate::include_obj!("../../test_files/cube.obj");
+        let obj = cube.as_ref();
+        // Both triangles of each quad use the face's normal
+        for n in 0..12 {
+            assert!(face_normal(&obj, n) == n as u16 / 2);
+        }
+        let mut lighting = Lighting::new(Color::new(0, 0, 0));
+        lighting.set_light(
+            0,
+            Light {
+                direction: -Vec3::Y,
+                color: Color::new(0xFF, 0xFF, 0xFF),
+            },
+        );
+        let l = lighting.light_matrix(&Mat3::IDENTITY);
+        let normal = |n: usize| Vec3::from_f16(obj.normals[face_normal(&obj, n) as usize]);
+        // The top face is fully lit, the sides are unlit and the bottom faces
+        // away from the light
+        assert!((l * normal(0)).x == 4096);
+        assert!((l * normal(2)).x == 0 && (l * normal(4)).x == 0);
+        assert!((l * normal(6)).x == -4096);
+    }
+
+    #[test_case]
+    fn normals() {
+        let obj = ObjRef {
+            quads: &[[0, 1, 3, 2]],
+            tris: &[[4, 5, 6]],
+            quad_norms: &[7],
+            tri_norms: &[8],
+            vertices: &[],
+            normals: &[],
+        };
+        assert!(face_normal(&obj, 0) == 7 && face_normal(&obj, 1) == 7);
+        assert!(face_normal(&obj, 2) == 8);
+    }
+}
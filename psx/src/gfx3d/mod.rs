@@ -0,0 +1,275 @@
+//! GTE-accelerated 3D rendering
+//!
+//! This module draws [`ObjRef`] meshes with the GTE. Each triangle's vertices
+//! are transformed and projected with RTPT, back faces are culled with NCLIP
+//! and the triangle's ordering table index is computed with AVSZ3 so the GPU
+//! draws far polygons first without sorting them on the CPU.
+//!
+//! Quads are drawn as two triangles, so a mesh needs one packet for each
+//! triangle plus two for each quad. Vertices are loaded as the raw bits of
+//! their [`f16`][crate::math::f16] coordinates, i.e. 1.0 is 256 units.
+//!
+//! Meshes may also be shaded by up to three directional lights with
+//! [`Renderer::set_lighting`] and [`Renderer::draw_lit`], which use the GTE's
+//! light and light color matrices and the normals in the mesh.
+//!
+//! ```rust,ignore
+//! let ot = ordering_table::<()>(&mut ot_buf);
+//! let mut renderer = Renderer::new(&camera, ot);
+//! renderer.draw_flat(&obj.as_ref(), &model, &colors, &mut polys);
+//! gpu_dma.send_list(renderer.ordering_table());
+//! ```
+
+use crate::format::obj::ObjRef;
+use crate::gpu::primitives::{PolyF3, PolyFT3, PolyG3};
+use crate::gpu::{Color, Packet, TexCoord, Vertex};
+use crate::hw::cop0::Status;
+use crate::hw::gte;
+use crate::hw::gte::{MAC0, RT11_12, RT13_21, RT22_23, RT31_32, RT33, TRX, TRY, TRZ, ZSF3, ZSF4};
+use crate::hw::gte::{OFX, OFY, OTZ, SXY0, SXY1, SXY2, VXY0, VXY1, VXY2, VZ0, VZ1, VZ2};
+use crate::hw::Register;
+
+mod lighting;
+
+pub use crate::math::{Transform, ONE};
+pub use lighting::{DepthCue, Light, Lighting, MAX_LIGHTS};
+
+fn pack(lo: i16, hi: i16) -> u32 {
+    (lo as u16 as u32) | ((hi as u16 as u32) << 16)
+}
+
+fn unpack(xy: u32) -> Vertex {
+    Vertex(xy as i16, (xy >> 16) as i16)
+}
+
+/// The view transform and projection used to draw a scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Camera {
+    /// Transforms world coordinates to view coordinates with Z increasing away
+    /// from the camera, e.g. from [`Transform::look_at`].
+    pub view: Transform,
+    /// The screen coordinates of the center of the projection.
+    pub center: Vertex,
+    /// The distance from the camera to the projection plane. Larger values
+    /// give a narrower field of view.
+    pub h: u16,
+    /// The largest Z mapped into the ordering table. Polygons further away are
+    /// not drawn.
+    pub depth: u16,
+}
+
+impl Camera {
+    /// Creates a camera at the origin looking down the Z axis.
+    pub const fn new(center: Vertex, h: u16, depth: u16) -> Self {
+        Camera {
+            view: Transform::IDENTITY,
+            center,
+            h,
+            depth,
+        }
+    }
+}
+
+/// The winding order of the faces which are culled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Culling {
+    /// Cull faces which are counterclockwise on screen.
+    #[default]
+    CounterClockwise,
+    /// Cull faces which are clockwise on screen.
+    Clockwise,
+    /// Draw both sides of all faces.
+    None,
+}
+
+impl Culling {
+    // Checks if a face with the given NCLIP result is drawn. Degenerate faces
+    // are always culled unless culling is disabled. NCLIP is positive for
+    // clockwise faces since the screen's Y axis points down.
+    fn draws(self, nclip: i32) -> bool {
+        match self {
+            Culling::CounterClockwise => nclip > 0,
+            Culling::Clockwise => nclip < 0,
+            Culling::None => true,
+        }
+    }
+}
+
+/// Iterates over a mesh's triangles, splitting each quad into two triangles.
+/// Quads come first, in the same order as [`Renderer::draw`] uses for packets.
+pub fn triangles<'a>(obj: &'a ObjRef) -> impl Iterator<Item = [u16; 3]> + 'a {
+    // Quads are stored as [a, b, d, c]
+    let quads = obj
+        .quads
+        .iter()
+        .flat_map(|&[a, b, d, c]| [[a, b, c], [a, c, d]]);
+    quads.chain(obj.tris.iter().copied())
+}
+
+/// Draws meshes into an ordering table using the GTE.
+pub struct Renderer<'a> {
+    ot: &'a mut [Packet<()>],
+    view: Transform,
+    culling: Culling,
+    h: u16,
+    lighting: Lighting,
+}
+
+impl<'a> Renderer<'a> {
+    /// Creates a renderer which inserts polygons into `ot` and sets up the GTE
+    /// for `camera`. This enables the GTE if it's not already enabled.
+    pub fn new(camera: &Camera, ot: &'a mut [Packet<()>]) -> Self {
+        Status::new().enable_gte().store();
+        OFX::skip_load()
+            .assign((camera.center.0 as i32) << 16)
+            .store();
+        OFY::skip_load()
+            .assign((camera.center.1 as i32) << 16)
+            .store();
+        gte::H::skip_load().assign(camera.h).store();
+        // Scale the sum of the Z values so the average of `depth` maps to the end
+        // of the ordering table
+        let depth = (camera.depth as u32).max(1);
+        let zsf = |n: u32| (ot.len() as u32 * 4096 / (n * depth)).min(i16::MAX as u32) as i16;
+        ZSF3::skip_load().assign(zsf(3)).store();
+        ZSF4::skip_load().assign(zsf(4)).store();
+        Renderer {
+            ot,
+            view: camera.view,
+            culling: Culling::default(),
+            h: camera.h,
+            lighting: Lighting::new(Color::new(0, 0, 0)),
+        }
+    }
+
+    /// Sets which faces are culled.
+    pub fn set_culling(&mut self, culling: Culling) -> &mut Self {
+        self.culling = culling;
+        self
+    }
+
+    /// Gets the ordering table to send it to the GPU.
+    pub fn ordering_table(&self) -> &[Packet<()>] {
+        self.ot
+    }
+
+    /// Draws `obj` transformed by `model` using `packets` for its triangles.
+    ///
+    /// `f` is called with each visible triangle's packet contents, its index in
+    /// [`triangles`], its vertex indices and its screen coordinates, then the
+    /// packet is inserted into the ordering table. Returns the number of
+    /// packets used. Triangles are skipped once all the packets are used.
+    pub fn draw<P, F>(
+        &mut self, obj: &ObjRef, model: &Transform, packets: &mut [Packet<P>], mut f: F,
+    ) -> usize
+    where F: FnMut(&mut P, usize, [u16; 3], [Vertex; 3]) {
+        load_transform(&(self.view * *model));
+        let mut used = 0;
+        for (n, tri) in triangles(obj).enumerate() {
+            let Some(packet) = packets.get_mut(used) else {
+                break
+            };
+            let [v0, v1, v2] = tri.map(|i| obj.vertices[i as usize]);
+            VXY0::skip_load().assign(pack(v0[0].0, v0[1].0)).store();
+            VZ0::skip_load().assign(v0[2].0).store();
+            VXY1::skip_load().assign(pack(v1[0].0, v1[1].0)).store();
+            VZ1::skip_load().assign(v1[2].0).store();
+            VXY2::skip_load().assign(pack(v2[0].0, v2[1].0)).store();
+            VZ2::skip_load().assign(v2[2].0).store();
+            gte::rtpt();
+            gte::nclip();
+            if !self.culling.draws(MAC0::new().to_bits()) {
+                continue
+            }
+            gte::avsz3();
+            let otz = OTZ::new().to_bits() as usize;
+            // Skip triangles behind the camera or past the camera's depth
+            if otz == 0 || otz >= self.ot.len() {
+                continue
+            }
+            let vertices = [
+                unpack(SXY0::new().to_bits()),
+                unpack(SXY1::new().to_bits()),
+                unpack(SXY2::new().to_bits()),
+            ];
+            f(&mut packet.contents, n, tri, vertices);
+            // The ordering table is linked from first to last so far polygons
+            // go at the start
+            let idx = self.ot.len() - 1 - otz;
+            self.ot[idx].insert_packet(packet);
+            used += 1;
+        }
+        used
+    }
+
+    /// Draws `obj` with flat shaded triangles. `colors` has a color for each
+    /// triangle in the order given by [`triangles`].
+    pub fn draw_flat(
+        &mut self, obj: &ObjRef, model: &Transform, colors: &[Color],
+        packets: &mut [Packet<PolyF3>],
+    ) -> usize {
+        self.draw(obj, model, packets, |poly, n, _, vertices| {
+            poly.set_vertices(vertices).set_color(colors[n]);
+        })
+    }
+
+    /// Draws `obj` with gouraud shaded triangles. `colors` has a color for
+    /// each vertex.
+    pub fn draw_gouraud(
+        &mut self, obj: &ObjRef, model: &Transform, colors: &[Color],
+        packets: &mut [Packet<PolyG3>],
+    ) -> usize {
+        self.draw(obj, model, packets, |poly, _, tri, vertices| {
+            poly.set_vertices(vertices)
+                .set_colors(tri.map(|i| colors[i as usize]));
+        })
+    }
+
+    /// Draws `obj` with textured triangles. `tex_coords` has the texture
+    /// coordinates for each triangle in the order given by [`triangles`]. The
+    /// packets' CLUT and texture page must already be set.
+    pub fn draw_textured(
+        &mut self, obj: &ObjRef, model: &Transform, tex_coords: &[[TexCoord; 3]],
+        packets: &mut [Packet<PolyFT3>],
+    ) -> usize {
+        self.draw(obj, model, packets, |poly, n, _, vertices| {
+            poly.set_vertices(vertices).set_tex_coords(tex_coords[n]);
+        })
+    }
+}
+
+// Loads a transform into the GTE's rotation matrix and translation vector.
+fn load_transform(t: &Transform) {
+    let [rt11_12, rt13_21, rt22_23, rt31_32, rt33] = t.rotation.to_words();
+    RT11_12::skip_load().assign(rt11_12).store();
+    RT13_21::skip_load().assign(rt13_21).store();
+    RT22_23::skip_load().assign(rt22_23).store();
+    RT31_32::skip_load().assign(rt31_32).store();
+    RT33::skip_load().assign(rt33 as i16).store();
+    TRX::skip_load().assign(t.translation.x).store();
+    TRY::skip_load().assign(t.translation.y).store();
+    TRZ::skip_load().assign(t.translation.z).store();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::triangles;
+    use crate::format::obj::ObjRef;
+
+    #[test_case]
+    fn split_quads() {
+        let obj = ObjRef {
+            quads: &[[0, 1, 3, 2]],
+            tris: &[[4, 5, 6]],
+            quad_norms: &[],
+            tri_norms: &[],
+            vertices: &[],
+            normals: &[],
+        };
+        let mut tris = triangles(&obj);
+        assert!(tris.next() == Some([0, 1, 2]));
+        assert!(tris.next() == Some([0, 2, 3]));
+        assert!(tris.next() == Some([4, 5, 6]));
+        assert!(tris.next().is_none());
+    }
+}
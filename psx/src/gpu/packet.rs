@@ -138,11 +138,14 @@ impl<T> Packet<T> {
 ///
 /// list\[0\] -> list\[1\] -> list\[2\] -> ... -> list\[n\]
 ///
-/// Note the packets are linked from first to last.
+/// Note the packets are linked from first to last. The last packet terminates
+/// the list regardless of the slice's previous contents, so the same buffer
+/// may be reinitialized each frame after packets were inserted into it.
 pub fn ordering_table<T>(list: &mut [u32]) -> &mut [Packet<()>] {
     let n = list.len();
     let packets = unsafe { transmute::<&mut [u32], &mut [Packet<()>]>(list) };
     for i in 0..n {
+        packets[i].next = TERMINATION;
         packets[i].size = 0;
     }
     link_list(packets);
@@ -1,6 +1,9 @@
-use crate::hw::cdrom::{Command, Controller, Parameter};
+use crate::hw::cdrom::{Command, Controller, Parameter, Request};
 use crate::hw::Register;
 
+const SMEN: u8 = 5;
+const BFRD: u8 = 7;
+
 impl Controller {
     pub fn send_cmd(&mut self, cmd: Command) -> &mut Self {
         self.assign(cmd as u8)
@@ -12,3 +15,18 @@ impl Parameter {
         self.assign(param as u8)
     }
 }
+
+// `Request` and `Interrupt` share an address, so these methods also show up on
+// `Interrupt`. They only make sense with index 0 selected.
+impl Request {
+    /// Requests that the sector buffer be loaded into the data FIFO or resets
+    /// the data FIFO if `want_data` is false.
+    pub fn request_data(&mut self, want_data: bool) -> &mut Self {
+        self.assign((want_data as u8) << BFRD)
+    }
+
+    /// Requests a command start interrupt when the next command is sent.
+    pub fn request_cmd_start(&mut self) -> &mut Self {
+        self.assign(1 << SMEN)
+    }
+}
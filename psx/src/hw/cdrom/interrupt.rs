@@ -0,0 +1,45 @@
+use crate::hw::cdrom::{IntCause, Interrupt, InterruptEnable};
+use crate::hw::Register;
+
+const INT_CAUSE: u8 = 0b111;
+const ALL_INTS: u8 = 0x1F;
+const CLEAR_PARAMS: u8 = 6;
+
+// `Interrupt` and `Request` share an address, so these methods also show up on
+// `Request`. They only make sense with index 1 selected.
+impl Interrupt {
+    /// Gets the cause of the pending interrupt, if any.
+    pub fn cause(&self) -> Option<IntCause> {
+        match self.to_bits() & INT_CAUSE {
+            1 => Some(IntCause::DataReady),
+            2 => Some(IntCause::Complete),
+            3 => Some(IntCause::Acknowledge),
+            4 => Some(IntCause::DataEnd),
+            5 => Some(IntCause::DiskError),
+            _ => None,
+        }
+    }
+
+    /// Checks if an interrupt is pending.
+    pub fn pending(&self) -> bool {
+        self.any_set(INT_CAUSE)
+    }
+
+    /// Acknowledges all pending interrupts.
+    pub fn ack_all(&mut self) -> &mut Self {
+        self.assign(ALL_INTS)
+    }
+
+    /// Acknowledges all pending interrupts and clears the parameter FIFO.
+    pub fn ack_and_clear_params(&mut self) -> &mut Self {
+        self.assign(ALL_INTS | 1 << CLEAR_PARAMS)
+    }
+}
+
+// `InterruptEnable` shares an address with `Parameter` and `DataByte`.
+impl InterruptEnable {
+    /// Enables all interrupts. This only makes sense with index 1 selected.
+    pub fn enable_all(&mut self) -> &mut Self {
+        self.assign(ALL_INTS)
+    }
+}
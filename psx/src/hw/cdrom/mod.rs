@@ -6,7 +6,9 @@
 use crate::hw::MemRegister;
 
 mod controller;
+mod interrupt;
 mod status;
+mod volume;
 
 #[repr(u8)]
 pub enum Idx {
@@ -57,9 +59,46 @@ pub enum Command {
     SecretLock = 0x57,
 }
 
+/// The cause of an interrupt raised by the CD-ROM controller.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntCause {
+    /// INT1: A sector is ready to be read from the data FIFO.
+    DataReady = 1,
+    /// INT2: The second response of a command which takes a while to complete.
+    Complete,
+    /// INT3: The first response acknowledging a command.
+    Acknowledge,
+    /// INT4: The end of the data or track was reached.
+    DataEnd,
+    /// INT5: The drive or a command reported an error.
+    DiskError,
+}
+
 pub type Status = MemRegister<u8, 0x1F80_1800>;
 pub type Controller = MemRegister<u8, 0x1F80_1801>;
+/// The response FIFO. This can be read with any index selected.
+pub type Response = MemRegister<u8, 0x1F80_1801>;
 pub type Parameter = MemRegister<u8, 0x1F80_1802>;
 pub type Data = MemRegister<u16, 0x1F80_1802>;
+/// The data FIFO accessed one byte at a time.
+pub type DataByte = MemRegister<u8, 0x1F80_1802>;
+/// The interrupt enable register. This is written with index 1 selected.
+pub type InterruptEnable = MemRegister<u8, 0x1F80_1802>;
 pub type Request = MemRegister<u8, 0x1F80_1803>;
 pub type Interrupt = MemRegister<u8, 0x1F80_1803>;
+/// The left CD audio output to left SPU input volume. This is written with
+/// index 2 selected.
+pub type LeftToLeft = MemRegister<u8, 0x1F80_1802>;
+/// The left CD audio output to right SPU input volume. This is written with
+/// index 2 selected.
+pub type LeftToRight = MemRegister<u8, 0x1F80_1803>;
+/// The right CD audio output to right SPU input volume. This is written with
+/// index 3 selected.
+pub type RightToRight = MemRegister<u8, 0x1F80_1801>;
+/// The right CD audio output to left SPU input volume. This is written with
+/// index 3 selected.
+pub type RightToLeft = MemRegister<u8, 0x1F80_1802>;
+/// Applies changes to the CD audio volume registers. This is written with
+/// index 3 selected.
+pub type VolumeApply = MemRegister<u8, 0x1F80_1803>;
@@ -0,0 +1,15 @@
+use crate::hw::cdrom::VolumeApply;
+use crate::hw::Register;
+
+const ADPCM_MUTE: u8 = 0;
+const APPLY: u8 = 5;
+
+// `VolumeApply` shares an address with `Request` and `Interrupt`, so this also
+// shows up on them. It only makes sense with index 3 selected.
+impl VolumeApply {
+    /// Applies the volumes written to the CD audio volume registers and mutes
+    /// or unmutes XA-ADPCM audio.
+    pub fn apply_volume(&mut self, mute_adpcm: bool) -> &mut Self {
+        self.assign(1 << APPLY | (mute_adpcm as u8) << ADPCM_MUTE)
+    }
+}
@@ -23,6 +23,48 @@ macro_rules! define_cop {
     ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr $(,)?) => {
         define_cop!($(#[$($meta)*])* $name<$ty>; COP: $cop; R: $reg; "m");
     };
+    ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr; "c" $(,)?) => {
+        $(#[$($meta)*])*
+        pub type $name = crate::hw::cop::CopRegister<$ty, $cop, $reg>;
+
+        // Control registers are numbered from 32 so their types are distinct
+        // from the data registers. The assembler doesn't support `cfc`/`ctc` so
+        // they're encoded by hand and go through `$at`.
+        impl Register<$ty> for crate::hw::cop::CopRegister<$ty, $cop, $reg> {
+            fn skip_load() -> Self {
+                Self { value: 0 }
+            }
+            fn load(&mut self) -> &mut Self {
+                unsafe {
+                    core::arch::asm! {
+                        ".set noat",
+                        concat!(".word 0x40400000 | (", $cop, " << 26) | (1 << 16) | ((", $reg, " - 32) << 11)"),
+                        // Wait for the load delay slot
+                        "nop",
+                        "move {}, $1",
+                        ".set at",
+                        out(reg) self.value,
+                        options(nomem, nostack)
+                    }
+                }
+                self
+            }
+
+            fn store(&mut self) -> &mut Self {
+                unsafe {
+                    core::arch::asm! {
+                        ".set noat",
+                        "move $1, {}",
+                        concat!(".word 0x40C00000 | (", $cop, " << 26) | (1 << 16) | ((", $reg, " - 32) << 11)"),
+                        ".set at",
+                        in(reg) self.value,
+                        options(nomem, nostack)
+                    }
+                }
+                self
+            }
+        }
+    };
     ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr; $cop_ty:literal $(,)?) => {
         $(#[$($meta)*])*
         pub type $name = crate::hw::cop::CopRegister<$ty, $cop, $reg>;
@@ -58,7 +100,7 @@ macro_rules! define_cop {
             }
         }
     };
-    ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr $(;$cop_ty:literal)?, $($others:tt)*) => {
+    ($(#[$($meta:meta)*])* $name:ident <$ty:ty>; COP: $cop:expr; R: $reg:expr $(;$cop_ty:tt)?, $($others:tt)*) => {
         define_cop!($(#[$($meta)*])* $name<$ty>; COP: $cop; R: $reg $(;$cop_ty)*);
         define_cop!($($others)*);
     };
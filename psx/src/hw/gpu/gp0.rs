@@ -26,6 +26,19 @@ impl GP0 {
         self
     }
 
+    /// Starts copying a rectangle of `size` pixels to `offset` in VRAM. The
+    /// pixels must then be sent as halfwords packed into words either through
+    /// this port or the GPU DMA channel.
+    pub fn copy_to_vram(&mut self, offset: Vertex, size: Vertex) -> &mut Self {
+        self.assign(0xA0 << 24)
+            .store()
+            .assign(u32::from(offset))
+            .store()
+            .assign(u32::from(size))
+            .store();
+        self
+    }
+
     /// Sends the GP0 command `cmd` to the GPU.
     ///
     /// # Safety
@@ -1,6 +1,18 @@
 //! Geometry Transformation Engine Coprocessor
 //!
 //! This module provides access to GTE, or cop2, registers and instructions.
+//!
+//! Data registers are numbered from 0 to 31 and control registers from 32 to
+//! 63. The GTE must be enabled in the cop0 [`Status`][crate::hw::cop0::Status]
+//! register before using any of these, otherwise they raise a coprocessor
+//! unusable exception.
+//!
+//! Commands take their inputs from the registers and leave their results in
+//! them. Results are read with the usual register loads which stall until the
+//! command finishes. Commands with a `SF` parameter shift their results right
+//! by 12 bits when it's set, which is needed when the inputs are 1.3.12 or
+//! 1.19.12 fixed-point values. Commands with an `LM` parameter limit negative
+//! results in the intermediate vector to 0 when it's set.
 
 use crate::hw::Register;
 
@@ -18,9 +30,45 @@ define_cop! {
     /// The 16-bit VZ2 vector
     VZ2<i16>;  COP: 2; R: 5,
 
+    /// Color and code value used by the color commands
+    RGBC<u32>; COP: 2; R: 6,
     /// Ordering table average Z value
     OTZ<u16>;  COP: 2; R: 7,
 
+    /// The 16-bit interpolation factor
+    IR0<i16>;  COP: 2; R: 8,
+    /// The first component of the 16-bit intermediate vector
+    IR1<i16>;  COP: 2; R: 9,
+    /// The second component of the 16-bit intermediate vector
+    IR2<i16>;  COP: 2; R: 10,
+    /// The third component of the 16-bit intermediate vector
+    IR3<i16>;  COP: 2; R: 11,
+
+    /// The oldest entry in the screen XY coordinate FIFO
+    SXY0<u32>; COP: 2; R: 12,
+    /// The middle entry in the screen XY coordinate FIFO
+    SXY1<u32>; COP: 2; R: 13,
+    /// The newest entry in the screen XY coordinate FIFO
+    SXY2<u32>; COP: 2; R: 14,
+    /// The screen XY coordinate FIFO which pushes a new entry when stored
+    SXYP<u32>; COP: 2; R: 15,
+
+    /// The oldest entry in the screen Z coordinate FIFO
+    SZ0<u16>;  COP: 2; R: 16,
+    /// The second entry in the screen Z coordinate FIFO
+    SZ1<u16>;  COP: 2; R: 17,
+    /// The third entry in the screen Z coordinate FIFO
+    SZ2<u16>;  COP: 2; R: 18,
+    /// The newest entry in the screen Z coordinate FIFO
+    SZ3<u16>;  COP: 2; R: 19,
+
+    /// The oldest entry in the color FIFO
+    RGB0<u32>; COP: 2; R: 20,
+    /// The middle entry in the color FIFO
+    RGB1<u32>; COP: 2; R: 21,
+    /// The newest entry in the color FIFO
+    RGB2<u32>; COP: 2; R: 22,
+
     /// Scalar math accumulator
     MAC0<i32>; COP: 2; R: 24,
 
@@ -31,44 +79,274 @@ define_cop! {
     /// The third component of the vector math accumulator
     MAC3<i32>; COP: 2; R: 27,
 
+    /// The intermediate vector as a 15-bit color, stored to set it
+    IRGB<u32>; COP: 2; R: 28,
+    /// The intermediate vector as a 15-bit color, loaded to read it
+    ORGB<u32>; COP: 2; R: 29,
+
     /// Leading zeros count source
     LZCS<u32>; COP: 2; R: 30,
     /// Leading zeros count result
     LZCR<u32>; COP: 2; R: 31,
 
-    /*
-    TODO: LLVM doesn't support these coprocessor instructions yet (#7).
     /// Rotation matrix entries RT11 and RT12
     RT11_12<u32>; COP: 2; R: 32; "c",
     /// Rotation matrix entries RT13 and RT21
-    RT13_21<u32>; COP: 2; R: 33,
+    RT13_21<u32>; COP: 2; R: 33; "c",
     /// Rotation matrix entries RT22 and RT23
-    RT22_23<u32>; COP: 2; R: 34,
+    RT22_23<u32>; COP: 2; R: 34; "c",
     /// Rotation matrix entries RT31 and RT32
-    RT31_32<u32>; COP: 2; R: 35,
+    RT31_32<u32>; COP: 2; R: 35; "c",
     /// Rotation matrix entry RT33
-    RT33<i16>;    COP: 2; R: 36,
+    RT33<i16>;    COP: 2; R: 36; "c",
+
+    /// The X component of the translation vector
+    TRX<i32>; COP: 2; R: 37; "c",
+    /// The Y component of the translation vector
+    TRY<i32>; COP: 2; R: 38; "c",
+    /// The Z component of the translation vector
+    TRZ<i32>; COP: 2; R: 39; "c",
 
     /// Light matrix entries L11 and L12
-    L11_12<u32>; COP: 2; R: 40,
+    L11_12<u32>; COP: 2; R: 40; "c",
     /// Light matrix entries L13 and L21
-    L13_21<u32>; COP: 2; R: 41,
+    L13_21<u32>; COP: 2; R: 41; "c",
     /// Light matrix entries L22 and L23
-    L22_23<u32>; COP: 2; R: 42,
+    L22_23<u32>; COP: 2; R: 42; "c",
     /// Light matrix entries L31 and L32
-    L31_32<u32>; COP: 2; R: 43,
+    L31_32<u32>; COP: 2; R: 43; "c",
     /// Light matrix entry L33
-    L33<i16>;    COP: 2; R: 44,
+    L33<i16>;    COP: 2; R: 44; "c",
+
+    /// The red component of the background color
+    RBK<i32>; COP: 2; R: 45; "c",
+    /// The green component of the background color
+    GBK<i32>; COP: 2; R: 46; "c",
+    /// The blue component of the background color
+    BBK<i32>; COP: 2; R: 47; "c",
 
     /// Light color matrix entries LR11 and LR12
-    LR11_12<u32>; COP: 2; R: 48,
+    LR11_12<u32>; COP: 2; R: 48; "c",
     /// Light color matrix entries LR13 and LR21
-    LR13_21<u32>; COP: 2; R: 49,
+    LR13_21<u32>; COP: 2; R: 49; "c",
     /// Light color matrix entries LR22 and LR23
-    LR22_23<u32>; COP: 2; R: 50,
+    LR22_23<u32>; COP: 2; R: 50; "c",
     /// Light color matrix entries LR31 and LR32
-    LR31_32<u32>; COP: 2; R: 51,
+    LR31_32<u32>; COP: 2; R: 51; "c",
     /// Light color matrix entry LR33
-    LR33<i16>; COP: 2; R: 52,
-    */
+    LR33<i16>;    COP: 2; R: 52; "c",
+
+    /// The red component of the far color
+    RFC<i32>; COP: 2; R: 53; "c",
+    /// The green component of the far color
+    GFC<i32>; COP: 2; R: 54; "c",
+    /// The blue component of the far color
+    BFC<i32>; COP: 2; R: 55; "c",
+
+    /// The screen X offset in 16.16 fixed-point
+    OFX<i32>; COP: 2; R: 56; "c",
+    /// The screen Y offset in 16.16 fixed-point
+    OFY<i32>; COP: 2; R: 57; "c",
+    /// The projection plane distance
+    H<u16>;   COP: 2; R: 58; "c",
+    /// The depth cueing coefficient
+    DQA<i16>; COP: 2; R: 59; "c",
+    /// The depth cueing offset
+    DQB<i32>; COP: 2; R: 60; "c",
+
+    /// The scale factor for averaging three Z values
+    ZSF3<i16>; COP: 2; R: 61; "c",
+    /// The scale factor for averaging four Z values
+    ZSF4<i16>; COP: 2; R: 62; "c",
+    /// Calculation error flags
+    FLAG<u32>; COP: 2; R: 63; "c",
+}
+
+// The cop2 instruction. The command is in the low 25 bits.
+const COP2: u32 = 0x4A00_0000;
+
+const SF_BIT: u32 = 19;
+const MX: u32 = 17;
+const V: u32 = 15;
+const CV: u32 = 13;
+const LM_BIT: u32 = 10;
+
+/// The [`mvmva`] matrix, vector and translation vector selections.
+pub mod mvmva {
+    /// Multiply by the rotation matrix.
+    pub const ROTATION: u32 = 0;
+    /// Multiply by the light matrix.
+    pub const LIGHT: u32 = 1;
+    /// Multiply by the light color matrix.
+    pub const LIGHT_COLOR: u32 = 2;
+
+    /// Multiply the vector in [`VXY0`][super::VXY0] and
+    /// [`VZ0`][super::VZ0].
+    pub const V0: u32 = 0;
+    /// Multiply the vector in [`VXY1`][super::VXY1] and
+    /// [`VZ1`][super::VZ1].
+    pub const V1: u32 = 1;
+    /// Multiply the vector in [`VXY2`][super::VXY2] and
+    /// [`VZ2`][super::VZ2].
+    pub const V2: u32 = 2;
+    /// Multiply the intermediate vector.
+    pub const IR: u32 = 3;
+
+    /// Add the translation vector.
+    pub const TRANSLATION: u32 = 0;
+    /// Add the background color.
+    pub const BACKGROUND_COLOR: u32 = 1;
+    /// Add the far color. This is bugged on hardware and only the last
+    /// partial product is kept.
+    pub const FAR_COLOR: u32 = 2;
+    /// Don't add a vector.
+    pub const NONE: u32 = 3;
+}
+
+// Executes a command. The two leading nops make sure registers stored right
+// before the command are written when it starts.
+macro_rules! cop2 {
+    ($cmd:expr) => {
+        unsafe {
+            core::arch::asm! {
+                "nop",
+                "nop",
+                ".word {}",
+                const COP2 | $cmd,
+                options(nomem, nostack)
+            }
+        }
+    };
+}
+
+/// Executes a command from its 25-bit encoding.
+#[inline(always)]
+pub fn command<const CMD: u32>() {
+    cop2!(CMD)
+}
+
+/// Perspective transformation of vector 0 (RTPS).
+#[inline(always)]
+pub fn rtps() {
+    cop2!(0x018_0001)
+}
+
+/// Perspective transformation of vectors 0, 1 and 2 (RTPT).
+#[inline(always)]
+pub fn rtpt() {
+    cop2!(0x028_0030)
+}
+
+/// Computes the normal of the triangle in the screen XY FIFO for backface
+/// culling (NCLIP). The result in [`MAC0`] is negative for counterclockwise
+/// triangles.
+#[inline(always)]
+pub fn nclip() {
+    cop2!(0x140_0006)
+}
+
+/// Averages the last three screen Z values into [`OTZ`] (AVSZ3).
+#[inline(always)]
+pub fn avsz3() {
+    cop2!(0x158_002D)
+}
+
+/// Averages all four screen Z values into [`OTZ`] (AVSZ4).
+#[inline(always)]
+pub fn avsz4() {
+    cop2!(0x168_002E)
+}
+
+/// Multiplies a matrix and a vector and adds a translation vector (MVMVA). The
+/// selections are constants in [`mvmva`][mod@mvmva].
+#[inline(always)]
+pub fn mvmva<
+    const MATRIX: u32,
+    const VECTOR: u32,
+    const TRANSLATION: u32,
+    const SF: bool,
+    const LM: bool,
+>() {
+    cop2!(
+        (SF as u32) << SF_BIT |
+            (MATRIX & 3) << MX |
+            (VECTOR & 3) << V |
+            (TRANSLATION & 3) << CV |
+            (LM as u32) << LM_BIT |
+            0x12
+    )
+}
+
+/// Normal color of vector 0 (NCS).
+#[inline(always)]
+pub fn ncs() {
+    cop2!(0x0C8_041E)
+}
+
+/// Normal color of vectors 0, 1 and 2 (NCT).
+#[inline(always)]
+pub fn nct() {
+    cop2!(0x0D8_0420)
+}
+
+/// Normal color of vector 0 multiplied by [`RGBC`] (NCCS).
+#[inline(always)]
+pub fn nccs() {
+    cop2!(0x108_041B)
+}
+
+/// Normal color of vectors 0, 1 and 2 multiplied by [`RGBC`] (NCCT).
+#[inline(always)]
+pub fn ncct() {
+    cop2!(0x118_043F)
+}
+
+/// Normal color of vector 0 with depth cueing (NCDS).
+#[inline(always)]
+pub fn ncds() {
+    cop2!(0x0E8_0413)
+}
+
+/// Normal color of vectors 0, 1 and 2 with depth cueing (NCDT).
+#[inline(always)]
+pub fn ncdt() {
+    cop2!(0x0F8_0416)
+}
+
+/// Depth cueing of [`RGBC`] (DPCS).
+#[inline(always)]
+pub fn dpcs() {
+    cop2!(0x078_0010)
+}
+
+/// Interpolates between the intermediate vector and the far color (INTPL).
+#[inline(always)]
+pub fn intpl() {
+    cop2!(0x098_0011)
+}
+
+/// Squares each component of the intermediate vector (SQR).
+#[inline(always)]
+pub fn sqr<const SF: bool>() {
+    cop2!((SF as u32) << SF_BIT | 0x0A0_0428)
+}
+
+/// The cross product of the rotation matrix's diagonal and the intermediate
+/// vector (OP).
+#[inline(always)]
+pub fn op<const SF: bool>() {
+    cop2!((SF as u32) << SF_BIT | 0x170_000C)
+}
+
+/// Multiplies the intermediate vector by [`IR0`] (GPF).
+#[inline(always)]
+pub fn gpf<const SF: bool>() {
+    cop2!((SF as u32) << SF_BIT | 0x190_003D)
+}
+
+/// Multiplies the intermediate vector by [`IR0`] and adds the vector
+/// accumulator (GPL).
+#[inline(always)]
+pub fn gpl<const SF: bool>() {
+    cop2!((SF as u32) << SF_BIT | 0x1A0_003E)
 }
@@ -0,0 +1,162 @@
+//! Macroblock decoder (MDEC) registers
+//!
+//! Both MDEC registers have different functions for reads and writes. Writing
+//! to [`Command`] sends commands and their parameters while reading it returns
+//! decoded pixels. Reading [`Status`] returns the MDEC's status while writing
+//! to it (as [`Control`]) resets the MDEC and enables DMA requests.
+
+use crate::hw::{MemRegister, Register};
+
+const COMMAND: u32 = 29;
+const DEPTH: u32 = 27;
+const SIGNED: u32 = 26;
+const SET_BIT15: u32 = 25;
+const MAX_PARAMS: u32 = 0xFFFF;
+
+const DECODE: u32 = 1;
+const SET_QUANT_TABLE: u32 = 2;
+const SET_SCALE_TABLE: u32 = 3;
+
+const OUTPUT_DEPTH: u32 = 25;
+const DATA_OUT_REQUEST: u32 = 27;
+const DATA_IN_REQUEST: u32 = 28;
+const BUSY: u32 = 29;
+const DATA_IN_FULL: u32 = 30;
+const DATA_OUT_EMPTY: u32 = 31;
+
+const ENABLE_DATA_OUT: u32 = 29;
+const ENABLE_DATA_IN: u32 = 30;
+const RESET: u32 = 31;
+
+/// The MDEC command and parameter register when written or the data output
+/// register when read.
+pub type Command = MemRegister<u32, 0x1F80_1820>;
+/// The MDEC status register when read.
+pub type Status = MemRegister<u32, 0x1F80_1824>;
+/// The MDEC control register when written.
+pub type Control = MemRegister<u32, 0x1F80_1824>;
+
+/// The format of the pixels the MDEC outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// 4-bit monochrome pixels.
+    Bit4 = 0,
+    /// 8-bit monochrome pixels.
+    Bit8,
+    /// 24-bit RGB pixels.
+    Bit24,
+    /// 15-bit RGB pixels.
+    Bit15,
+}
+
+impl Depth {
+    /// The size of a decoded macroblock in words. Monochrome macroblocks are
+    /// 8x8 pixels and color macroblocks are 16x16 pixels.
+    pub const fn macroblock_words(&self) -> usize {
+        match self {
+            Depth::Bit4 => 8,
+            Depth::Bit8 => 16,
+            Depth::Bit24 => 192,
+            Depth::Bit15 => 128,
+        }
+    }
+}
+
+impl Command {
+    /// Starts decoding a run-length encoded macroblock stream of `words`
+    /// words which must then be written as parameters. If `bit15` is set, bit
+    /// 15 of each 15-bit pixel is set.
+    pub fn decode(&mut self, depth: Depth, signed: bool, bit15: bool, words: u16) -> &mut Self {
+        self.assign(
+            DECODE << COMMAND |
+                (depth as u32) << DEPTH |
+                (signed as u32) << SIGNED |
+                (bit15 as u32) << SET_BIT15 |
+                (words as u32 & MAX_PARAMS),
+        )
+    }
+
+    /// Starts setting the quantization tables. This must be followed by 64
+    /// bytes for the luminance table and if `color` is set, 64 bytes for the
+    /// color table.
+    pub fn set_quant_table(&mut self, color: bool) -> &mut Self {
+        self.assign(SET_QUANT_TABLE << COMMAND | color as u32)
+    }
+
+    /// Starts setting the IDCT scale table. This must be followed by 64
+    /// halfwords.
+    pub fn set_scale_table(&mut self) -> &mut Self {
+        self.assign(SET_SCALE_TABLE << COMMAND)
+    }
+
+    /// Sets a parameter word to be written after a command.
+    pub fn set_param(&mut self, param: u32) -> &mut Self {
+        self.assign(param)
+    }
+
+    /// Gets the output word read from the register.
+    pub fn get_data(&self) -> u32 {
+        self.to_bits()
+    }
+}
+
+impl Status {
+    /// Checks if the data output FIFO is empty.
+    pub fn data_out_empty(&self) -> bool {
+        self.all_set(1 << DATA_OUT_EMPTY)
+    }
+
+    /// Checks if the data input FIFO is full.
+    pub fn data_in_full(&self) -> bool {
+        self.all_set(1 << DATA_IN_FULL)
+    }
+
+    /// Checks if the MDEC is busy executing a command.
+    pub fn busy(&self) -> bool {
+        self.all_set(1 << BUSY)
+    }
+
+    /// Checks if the MDEC is requesting input data.
+    pub fn data_in_request(&self) -> bool {
+        self.all_set(1 << DATA_IN_REQUEST)
+    }
+
+    /// Checks if the MDEC is requesting that output data be read.
+    pub fn data_out_request(&self) -> bool {
+        self.all_set(1 << DATA_OUT_REQUEST)
+    }
+
+    /// Gets the depth of the current decode command's output.
+    pub fn output_depth(&self) -> Depth {
+        match (self.to_bits() >> OUTPUT_DEPTH) & 0b11 {
+            0 => Depth::Bit4,
+            1 => Depth::Bit8,
+            2 => Depth::Bit24,
+            _ => Depth::Bit15,
+        }
+    }
+
+    /// Gets the number of parameter words remaining for the current command.
+    pub fn remaining(&self) -> u32 {
+        (self.to_bits() + 1) & MAX_PARAMS
+    }
+
+    /// Waits until the MDEC isn't busy.
+    pub fn wait(&mut self) -> &mut Self {
+        self.load();
+        while self.busy() {
+            self.load();
+        }
+        self
+    }
+
+    /// Resets the MDEC, aborting the current command.
+    pub fn reset(&mut self) -> &mut Self {
+        self.assign(1 << RESET)
+    }
+
+    /// Enables or disables DMA requests for input and output data.
+    pub fn enable_dma(&mut self, data_in: bool, data_out: bool) -> &mut Self {
+        self.assign((data_in as u32) << ENABLE_DATA_IN | (data_out as u32) << ENABLE_DATA_OUT)
+    }
+}
@@ -18,7 +18,11 @@ pub mod dma;
 pub mod gpu;
 pub mod gte;
 pub mod irq;
+pub mod mdec;
 pub mod mmio;
+pub mod sio0;
+pub mod spu;
+pub mod timer;
 
 use mmio::MemRegister;
 
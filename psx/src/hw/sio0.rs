@@ -0,0 +1,193 @@
+//! Controller and memory card serial port (SIO0) registers
+//!
+//! Controllers and memory cards share a synchronous serial bus. The selected
+//! port is chosen by the `/JOY` outputs in [`Control`] and each byte written
+//! to [`Data`] is exchanged for a byte from the device. Devices pulse `/ACK`
+//! after each byte when they expect another one.
+
+use crate::hw::{MemRegister, Register};
+
+const TX_READY: u32 = 0;
+const RX_NOT_EMPTY: u32 = 1;
+const TX_FINISHED: u32 = 2;
+const RX_PARITY_ERROR: u32 = 3;
+const ACK_LOW: u32 = 7;
+const IRQ: u32 = 9;
+
+const BAUD_FACTOR: u16 = 0;
+const CHAR_LENGTH: u16 = 2;
+const PARITY_ENABLE: u16 = 4;
+const PARITY_ODD: u16 = 5;
+
+const TX_ENABLE: u16 = 0;
+const SELECT: u16 = 1;
+const RX_ENABLE: u16 = 2;
+const ACK: u16 = 4;
+const RESET: u16 = 6;
+const ACK_IRQ_ENABLE: u16 = 12;
+const PORT: u16 = 13;
+
+/// The data register (JOY_DATA) which sends a byte when written and returns a
+/// received byte when read.
+pub type Data = MemRegister<u8, 0x1F80_1040>;
+/// The status register (JOY_STAT).
+pub type Status = MemRegister<u32, 0x1F80_1044>;
+/// The mode register (JOY_MODE).
+pub type Mode = MemRegister<u16, 0x1F80_1048>;
+/// The control register (JOY_CTRL).
+pub type Control = MemRegister<u16, 0x1F80_104A>;
+/// The baud rate reload register (JOY_BAUD).
+pub type Baud = MemRegister<u16, 0x1F80_104E>;
+
+/// A controller and memory card port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    /// The first port.
+    Port1 = 0,
+    /// The second port.
+    Port2,
+}
+
+/// The factor the baud rate reload value is multiplied by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaudFactor {
+    /// Multiply by 1.
+    Mul1 = 1,
+    /// Multiply by 16.
+    Mul16,
+    /// Multiply by 64.
+    Mul64,
+}
+
+impl Data {
+    /// Gets the received byte.
+    pub fn get_byte(&self) -> u8 {
+        self.to_bits()
+    }
+
+    /// Sets the byte to send.
+    pub fn set_byte(&mut self, byte: u8) -> &mut Self {
+        self.assign(byte)
+    }
+}
+
+impl Status {
+    /// Checks if a byte can be written to [`Data`].
+    pub fn tx_ready(&self) -> bool {
+        self.all_set(1 << TX_READY)
+    }
+
+    /// Checks if a received byte is waiting in [`Data`].
+    pub fn rx_not_empty(&self) -> bool {
+        self.all_set(1 << RX_NOT_EMPTY)
+    }
+
+    /// Checks if all bytes were sent.
+    pub fn tx_finished(&self) -> bool {
+        self.all_set(1 << TX_FINISHED)
+    }
+
+    /// Checks if a received byte had a parity error.
+    pub fn rx_parity_error(&self) -> bool {
+        self.all_set(1 << RX_PARITY_ERROR)
+    }
+
+    /// Checks if the selected device is currently pulling `/ACK` low.
+    pub fn ack_low(&self) -> bool {
+        self.all_set(1 << ACK_LOW)
+    }
+
+    /// Checks if an IRQ was requested since it was last acknowledged in
+    /// [`Control`].
+    pub fn irq_requested(&self) -> bool {
+        self.all_set(1 << IRQ)
+    }
+}
+
+impl Mode {
+    /// Sets the factor the baud rate reload value is multiplied by.
+    pub fn set_baud_factor(&mut self, factor: BaudFactor) -> &mut Self {
+        self.clear_bits(0b11 << BAUD_FACTOR)
+            .set_bits((factor as u16) << BAUD_FACTOR)
+    }
+
+    /// Sets the number of bits per character. Returns `None` if `bits` isn't
+    /// between 5 and 8.
+    pub fn set_char_length(&mut self, bits: u8) -> Option<&mut Self> {
+        if !(5..=8).contains(&bits) {
+            return None
+        }
+        Some(
+            self.clear_bits(0b11 << CHAR_LENGTH)
+                .set_bits(((bits - 5) as u16) << CHAR_LENGTH),
+        )
+    }
+
+    /// Enables or disables a parity bit after each character.
+    pub fn enable_parity(&mut self, enabled: bool, odd: bool) -> &mut Self {
+        self.clear_bits(1 << PARITY_ENABLE | 1 << PARITY_ODD)
+            .set_bits((enabled as u16) << PARITY_ENABLE | (odd as u16) << PARITY_ODD)
+    }
+}
+
+impl Control {
+    /// Enables or disables sending bytes.
+    pub fn enable_tx(&mut self, enabled: bool) -> &mut Self {
+        self.clear_bits(1 << TX_ENABLE)
+            .set_bits((enabled as u16) << TX_ENABLE)
+    }
+
+    /// Forces receiving bytes even if no port is selected.
+    pub fn enable_rx(&mut self, enabled: bool) -> &mut Self {
+        self.clear_bits(1 << RX_ENABLE)
+            .set_bits((enabled as u16) << RX_ENABLE)
+    }
+
+    /// Selects a port by pulling its `/JOY` output low or deselects both ports
+    /// if `port` is `None`.
+    pub fn select(&mut self, port: Option<Port>) -> &mut Self {
+        self.clear_bits(1 << SELECT | 1 << PORT);
+        match port {
+            Some(port) => self.set_bits(1 << SELECT | (port as u16) << PORT),
+            None => self,
+        }
+    }
+
+    /// Gets the selected port.
+    pub fn selected(&self) -> Option<Port> {
+        if !self.all_set(1 << SELECT) {
+            return None
+        }
+        if self.all_set(1 << PORT) {
+            Some(Port::Port2)
+        } else {
+            Some(Port::Port1)
+        }
+    }
+
+    /// Requests an IRQ when the selected device pulses `/ACK`.
+    pub fn enable_ack_irq(&mut self, enabled: bool) -> &mut Self {
+        self.clear_bits(1 << ACK_IRQ_ENABLE)
+            .set_bits((enabled as u16) << ACK_IRQ_ENABLE)
+    }
+
+    /// Sets the bit which acknowledges the IRQ and parity error flags in
+    /// [`Status`] when stored. The bit should be cleared after it's stored so
+    /// later stores don't acknowledge them again.
+    pub fn ack(&mut self, ack: bool) -> &mut Self {
+        self.clear_bits(1 << ACK).set_bits((ack as u16) << ACK)
+    }
+
+    /// Resets the serial port, clearing all other settings.
+    pub fn reset(&mut self) -> &mut Self {
+        self.assign(1 << RESET)
+    }
+}
+
+impl Baud {
+    /// Sets the baud rate reload value. The baud rate is the system clock
+    /// divided by this value times the [`BaudFactor`].
+    pub fn set_reload(&mut self, reload: u16) -> &mut Self {
+        self.assign(reload)
+    }
+}
@@ -0,0 +1,136 @@
+use crate::hw::spu::{Control, Status, TransferControl, TransferMode};
+use crate::hw::Register;
+
+const CD_AUDIO: u16 = 0;
+const EXTERN_AUDIO: u16 = 1;
+const CD_REVERB: u16 = 2;
+const EXTERN_REVERB: u16 = 3;
+const TRANSFER_MODE: u16 = 4;
+const IRQ_ENABLE: u16 = 6;
+const REVERB: u16 = 7;
+const NOISE_STEP: u16 = 8;
+const NOISE_SHIFT: u16 = 10;
+const UNMUTE: u16 = 14;
+const ENABLE: u16 = 15;
+
+const IRQ: u16 = 6;
+const BUSY: u16 = 10;
+
+// The only value documented to work for the transfer control register.
+const NORMAL_TRANSFER: u16 = 0x0004;
+
+fn transfer_mode(bits: u16) -> TransferMode {
+    match (bits >> TRANSFER_MODE) & 0b11 {
+        0 => TransferMode::Stop,
+        1 => TransferMode::Manual,
+        2 => TransferMode::DMAWrite,
+        _ => TransferMode::DMARead,
+    }
+}
+
+impl Control {
+    fn set_flag(&mut self, bit: u16, enabled: bool) -> &mut Self {
+        self.clear_bits(1 << bit).set_bits((enabled as u16) << bit)
+    }
+
+    /// Enables or disables the SPU.
+    pub fn enable(&mut self, enabled: bool) -> &mut Self {
+        self.set_flag(ENABLE, enabled)
+    }
+
+    /// Mutes or unmutes the SPU's output. This doesn't affect CD audio.
+    pub fn mute(&mut self, muted: bool) -> &mut Self {
+        self.set_flag(UNMUTE, !muted)
+    }
+
+    /// Enables or disables the CD audio input.
+    pub fn cd_audio(&mut self, enabled: bool) -> &mut Self {
+        self.set_flag(CD_AUDIO, enabled)
+    }
+
+    /// Enables or disables the external audio input.
+    pub fn extern_audio(&mut self, enabled: bool) -> &mut Self {
+        self.set_flag(EXTERN_AUDIO, enabled)
+    }
+
+    /// Sends the CD audio input to the reverb unit.
+    pub fn cd_reverb(&mut self, enabled: bool) -> &mut Self {
+        self.set_flag(CD_REVERB, enabled)
+    }
+
+    /// Sends the external audio input to the reverb unit.
+    pub fn extern_reverb(&mut self, enabled: bool) -> &mut Self {
+        self.set_flag(EXTERN_REVERB, enabled)
+    }
+
+    /// Enables or disables writing to the reverb work area.
+    pub fn reverb(&mut self, enabled: bool) -> &mut Self {
+        self.set_flag(REVERB, enabled)
+    }
+
+    /// Enables or disables [`IRQ::SPU`][crate::hw::irq::IRQ::SPU]. Disabling
+    /// it acknowledges a pending interrupt.
+    pub fn irq(&mut self, enabled: bool) -> &mut Self {
+        self.set_flag(IRQ_ENABLE, enabled)
+    }
+
+    /// Sets the noise generator's frequency shift (0-15) and step (0-3).
+    pub fn set_noise(&mut self, shift: u8, step: u8) -> &mut Self {
+        let noise = ((shift as u16 & 0xF) << NOISE_SHIFT) | ((step as u16 & 0b11) << NOISE_STEP);
+        self.clear_bits(0x3F << NOISE_STEP).set_bits(noise)
+    }
+
+    /// Sets the sound RAM transfer mode.
+    pub fn set_transfer_mode(&mut self, mode: TransferMode) -> &mut Self {
+        self.clear_bits(0b11 << TRANSFER_MODE)
+            .set_bits((mode as u16) << TRANSFER_MODE)
+    }
+
+    /// Gets the sound RAM transfer mode.
+    pub fn get_transfer_mode(&self) -> TransferMode {
+        transfer_mode(self.to_bits())
+    }
+}
+
+impl Status {
+    /// Gets the transfer mode currently applied. This lags behind writes to
+    /// [`Control`].
+    pub fn transfer_mode(&self) -> TransferMode {
+        transfer_mode(self.to_bits())
+    }
+
+    /// Checks if [`IRQ::SPU`][crate::hw::irq::IRQ::SPU] was raised.
+    pub fn irq_pending(&self) -> bool {
+        self.all_set(1 << IRQ)
+    }
+
+    /// Checks if a sound RAM transfer is in progress.
+    pub fn busy(&self) -> bool {
+        self.all_set(1 << BUSY)
+    }
+
+    /// Waits until the transfer mode in [`Control`] is applied.
+    pub fn wait_for_mode(&mut self, mode: TransferMode) -> &mut Self {
+        self.load();
+        while self.transfer_mode() != mode {
+            self.load();
+        }
+        self
+    }
+
+    /// Waits until a sound RAM transfer completes.
+    pub fn wait(&mut self) -> &mut Self {
+        self.load();
+        while self.busy() {
+            self.load();
+        }
+        self
+    }
+}
+
+impl TransferControl {
+    /// Sets the transfer type used by normal transfers to sound RAM.
+    pub fn set_normal(&mut self) -> &mut Self {
+        self.assign(NORMAL_TRANSFER)
+    }
+}
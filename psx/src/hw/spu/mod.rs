@@ -0,0 +1,164 @@
+//! Sound processing unit (SPU) registers
+//!
+//! The SPU has 24 voices which play ADPCM samples from its 512 KB of sound RAM.
+//! The per-voice registers are in [`voice`] and the reverb configuration is in
+//! [`reverb`] while the registers shared by all voices are defined here.
+
+use crate::hw::{MemRegister, Register};
+
+mod control;
+pub mod reverb;
+pub mod voice;
+
+/// The number of SPU voices.
+pub const VOICES: usize = 24;
+
+/// The size of sound RAM in bytes.
+pub const RAM_SIZE: u32 = 0x8_0000;
+
+// Addresses in sound RAM are stored in registers in units of 8 bytes.
+const ADDRESS_SHIFT: u32 = 3;
+
+const SWEEP: u16 = 15;
+
+/// The main volume for the left output.
+pub type MainVolumeLeft = MemRegister<u16, 0x1F80_1D80>;
+/// The main volume for the right output.
+pub type MainVolumeRight = MemRegister<u16, 0x1F80_1D82>;
+/// The reverb output volume for the left output.
+pub type ReverbVolumeLeft = MemRegister<u16, 0x1F80_1D84>;
+/// The reverb output volume for the right output.
+pub type ReverbVolumeRight = MemRegister<u16, 0x1F80_1D86>;
+/// Starts the ADSR attack phase of the voices whose bits are set.
+pub type KeyOn = MemRegister<u32, 0x1F80_1D88>;
+/// Starts the ADSR release phase of the voices whose bits are set.
+pub type KeyOff = MemRegister<u32, 0x1F80_1D8C>;
+/// Modulates each voice's pitch with the previous voice's output.
+pub type PitchModulation = MemRegister<u32, 0x1F80_1D90>;
+/// Replaces each voice's ADPCM samples with noise.
+pub type NoiseMode = MemRegister<u32, 0x1F80_1D94>;
+/// Sends each voice's output to the reverb unit.
+pub type ReverbMode = MemRegister<u32, 0x1F80_1D98>;
+/// Flags the voices which reached an ADPCM block with the end flag set.
+pub type VoiceEnd = MemRegister<u32, 0x1F80_1D9C>;
+/// The start of the reverb work area in sound RAM.
+pub type ReverbAddress = MemRegister<u16, 0x1F80_1DA2>;
+/// The sound RAM address which raises [`IRQ::SPU`][crate::hw::irq::IRQ::SPU]
+/// when accessed.
+pub type IrqAddress = MemRegister<u16, 0x1F80_1DA4>;
+/// The sound RAM address for manual and DMA transfers.
+pub type TransferAddress = MemRegister<u16, 0x1F80_1DA6>;
+/// The FIFO for manual transfers to sound RAM.
+pub type TransferFifo = MemRegister<u16, 0x1F80_1DA8>;
+/// The SPU control register (SPUCNT).
+pub type Control = MemRegister<u16, 0x1F80_1DAA>;
+/// The sound RAM transfer control register.
+pub type TransferControl = MemRegister<u16, 0x1F80_1DAC>;
+/// The SPU status register (SPUSTAT).
+pub type Status = MemRegister<u16, 0x1F80_1DAE>;
+/// The CD audio input volume for the left output.
+pub type CdVolumeLeft = MemRegister<u16, 0x1F80_1DB0>;
+/// The CD audio input volume for the right output.
+pub type CdVolumeRight = MemRegister<u16, 0x1F80_1DB2>;
+/// The external audio input volume for the left output.
+pub type ExternVolumeLeft = MemRegister<u16, 0x1F80_1DB4>;
+/// The external audio input volume for the right output.
+pub type ExternVolumeRight = MemRegister<u16, 0x1F80_1DB6>;
+
+/// The transfer mode set in [`Control`] and reported by [`Status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferMode {
+    /// No transfer.
+    Stop = 0,
+    /// Transfers to sound RAM by writing to [`TransferFifo`].
+    Manual,
+    /// Transfers to sound RAM by DMA.
+    DMAWrite,
+    /// Transfers from sound RAM by DMA.
+    DMARead,
+}
+
+/// Converts a sound RAM byte address to the value stored in address
+/// registers.
+pub const fn to_address(addr: u32) -> u16 {
+    (addr >> ADDRESS_SHIFT) as u16
+}
+
+/// Converts the value stored in an address register to a sound RAM byte
+/// address.
+pub const fn from_address(value: u16) -> u32 {
+    (value as u32) << ADDRESS_SHIFT
+}
+
+/// A volume register for the main, reverb, CD, external or voice outputs.
+pub trait Volume: AsRef<u16> + AsMut<u16> {
+    /// Sets a fixed volume ranging from -0x8000 to 0x7FFE where negative
+    /// volumes invert the phase.
+    fn set_volume(&mut self, volume: i16) -> &mut Self {
+        *self.as_mut() = ((volume >> 1) as u16) & !(1 << SWEEP);
+        self
+    }
+
+    /// Gets the fixed volume or `None` if the volume is sweeping.
+    fn get_volume(&self) -> Option<i16> {
+        let value = *self.as_ref();
+        if value & (1 << SWEEP) != 0 {
+            None
+        } else {
+            // Sign-extend the 15-bit volume
+            Some((value << 1) as i16)
+        }
+    }
+}
+
+impl Volume for MainVolumeLeft {}
+impl Volume for MainVolumeRight {}
+impl Volume for ReverbVolumeLeft {}
+impl Volume for ReverbVolumeRight {}
+impl Volume for CdVolumeLeft {}
+impl Volume for CdVolumeRight {}
+impl Volume for ExternVolumeLeft {}
+impl Volume for ExternVolumeRight {}
+
+/// A register with one bit for each voice.
+pub trait VoiceFlags: Register<u32> {
+    /// Sets the bit for `voice`.
+    fn set_voice(&mut self, voice: usize) -> &mut Self {
+        self.set_bits(1 << voice)
+    }
+
+    /// Clears the bit for `voice`.
+    fn clear_voice(&mut self, voice: usize) -> &mut Self {
+        self.clear_bits(1 << voice)
+    }
+
+    /// Checks if the bit for `voice` is set.
+    fn voice_set(&self, voice: usize) -> bool {
+        self.all_set(1 << voice)
+    }
+}
+
+impl VoiceFlags for KeyOn {}
+impl VoiceFlags for KeyOff {}
+impl VoiceFlags for PitchModulation {}
+impl VoiceFlags for NoiseMode {}
+impl VoiceFlags for ReverbMode {}
+impl VoiceFlags for VoiceEnd {}
+
+/// A register holding a sound RAM address.
+pub trait Address: AsRef<u16> + AsMut<u16> {
+    /// Sets the address in bytes. The lowest 3 bits are ignored.
+    fn set_address(&mut self, addr: u32) -> &mut Self {
+        *self.as_mut() = to_address(addr);
+        self
+    }
+
+    /// Gets the address in bytes.
+    fn get_address(&self) -> u32 {
+        from_address(*self.as_ref())
+    }
+}
+
+impl Address for ReverbAddress {}
+impl Address for IrqAddress {}
+impl Address for TransferAddress {}
@@ -0,0 +1,73 @@
+//! Reverb configuration registers
+//!
+//! The reverb unit is configured by 32 consecutive 16-bit registers at
+//! 0x1F80_1DC0 which are always written together, so they're accessed as a
+//! single block here.
+
+use core::ptr::{read_volatile, write_volatile};
+
+const BASE: u32 = 0x1F80_1DC0;
+
+/// The number of reverb configuration registers.
+pub const REGISTERS: usize = 32;
+
+/// The reverb configuration registers in order from dAPF1 to vRIN.
+///
+/// Offsets are in units of 8 bytes relative to the start of the reverb work
+/// area set in [`ReverbAddress`][crate::hw::spu::ReverbAddress].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReverbConfig {
+    values: [u16; REGISTERS],
+}
+
+impl ReverbConfig {
+    /// Creates a handle to the reverb registers without reading their values.
+    ///
+    /// This should not do any volatile reads.
+    pub fn skip_load() -> Self {
+        ReverbConfig {
+            values: [0; REGISTERS],
+        }
+    }
+
+    /// Creates a handle to the reverb registers and immediately reads their
+    /// values.
+    ///
+    /// This does one volatile read per register.
+    pub fn new() -> Self {
+        let mut reg = Self::skip_load();
+        reg.load();
+        reg
+    }
+
+    /// Load the registers' values into a cache.
+    ///
+    /// This does one volatile read per register.
+    pub fn load(&mut self) -> &mut Self {
+        for (i, value) in self.values.iter_mut().enumerate() {
+            *value = unsafe { read_volatile((BASE as *const u16).add(i)) };
+        }
+        self
+    }
+
+    /// Store the cached values in the registers.
+    ///
+    /// This does one volatile write per register.
+    pub fn store(&mut self) -> &mut Self {
+        for (i, &value) in self.values.iter().enumerate() {
+            unsafe { write_volatile((BASE as *mut u16).add(i), value) }
+        }
+        self
+    }
+
+    /// Gets the cached values.
+    pub fn to_bits(&self) -> [u16; REGISTERS] {
+        self.values
+    }
+
+    /// Sets the cached values to `values`.
+    pub fn assign(&mut self, values: [u16; REGISTERS]) -> &mut Self {
+        self.values = values;
+        self
+    }
+}
@@ -0,0 +1,226 @@
+//! Per-voice SPU registers
+//!
+//! Each of the 24 voices has the same set of registers at 0x1F80_1C00 + voice *
+//! 0x10. Since voices are usually picked at runtime, these registers take the
+//! voice index when they're created rather than having a type per voice.
+
+use crate::hw::private::Primitive;
+use crate::hw::spu::{Address, Volume, VOICES};
+use core::fmt;
+use core::fmt::{Debug, Formatter};
+use core::ptr::{read_volatile, write_volatile};
+
+const BASE: u32 = 0x1F80_1C00;
+const STRIDE: u32 = 0x10;
+
+// A pitch of 0x1000 plays samples at 44.1 kHz.
+const BASE_RATE: u32 = 44_100;
+const PITCH_SHIFT: u32 = 12;
+const MAX_PITCH: u32 = 0x3FFF;
+
+const ATTACK_EXP: u32 = 15;
+const ATTACK_SHIFT: u32 = 10;
+const ATTACK_STEP: u32 = 8;
+const DECAY_SHIFT: u32 = 4;
+const SUSTAIN_LEVEL: u32 = 0;
+const SUSTAIN_EXP: u32 = 31;
+const SUSTAIN_DECREASE: u32 = 30;
+const SUSTAIN_SHIFT: u32 = 24;
+const SUSTAIN_STEP: u32 = 22;
+const RELEASE_EXP: u32 = 21;
+const RELEASE_SHIFT: u32 = 16;
+
+/// A register belonging to one of the 24 voices.
+#[repr(C)]
+pub struct VoiceRegister<T: Primitive, const OFFSET: u32> {
+    voice: usize,
+    value: T,
+}
+
+/// The voice's left volume.
+pub type VolumeLeft = VoiceRegister<u16, 0x0>;
+/// The voice's right volume.
+pub type VolumeRight = VoiceRegister<u16, 0x2>;
+/// The voice's sample rate.
+pub type Pitch = VoiceRegister<u16, 0x4>;
+/// The address in sound RAM of the voice's first ADPCM block.
+pub type StartAddress = VoiceRegister<u16, 0x6>;
+/// The lower half of the voice's ADSR envelope settings.
+pub type AdsrLow = VoiceRegister<u16, 0x8>;
+/// The upper half of the voice's ADSR envelope settings.
+pub type AdsrHigh = VoiceRegister<u16, 0xA>;
+/// The voice's current ADSR envelope volume.
+pub type AdsrVolume = VoiceRegister<u16, 0xC>;
+/// The address in sound RAM the voice jumps to at a block with the end flag
+/// set.
+pub type RepeatAddress = VoiceRegister<u16, 0xE>;
+
+impl<T: Primitive, const OFFSET: u32> VoiceRegister<T, OFFSET> {
+    const fn address(&self) -> u32 {
+        BASE + (self.voice as u32 * STRIDE) + OFFSET
+    }
+
+    /// Creates a handle to `voice`'s register without reading its value.
+    ///
+    /// This should not do any volatile reads.
+    pub fn skip_load(voice: usize) -> Self {
+        assert!(voice < VOICES);
+        Self {
+            voice,
+            value: T::from(0),
+        }
+    }
+
+    /// Creates a handle to `voice`'s register and immediately reads its value.
+    ///
+    /// This does a single volatile read.
+    pub fn new(voice: usize) -> Self {
+        let mut reg = Self::skip_load(voice);
+        reg.load();
+        reg
+    }
+
+    /// The voice the register belongs to.
+    pub fn voice(&self) -> usize {
+        self.voice
+    }
+
+    /// Load the register's value into a cache.
+    ///
+    /// This does a single volatile read.
+    pub fn load(&mut self) -> &mut Self {
+        self.value = unsafe { read_volatile(self.address() as *const T) };
+        self
+    }
+
+    /// Store the cached value in the register.
+    ///
+    /// This does a single volatile write.
+    pub fn store(&mut self) -> &mut Self {
+        unsafe { write_volatile(self.address() as *mut T, self.value) }
+        self
+    }
+
+    /// Gets the cached value.
+    pub fn to_bits(&self) -> T {
+        self.value
+    }
+
+    /// Sets the cached value to `bits`.
+    pub fn assign(&mut self, bits: T) -> &mut Self {
+        self.value = bits;
+        self
+    }
+}
+
+impl<T: Primitive, const OFFSET: u32> Debug for VoiceRegister<T, OFFSET> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VoiceRegister")
+            .field("voice", &self.voice)
+            .field("bits", &self.value)
+            .finish()
+    }
+}
+
+impl<T: Primitive, const OFFSET: u32> AsRef<T> for VoiceRegister<T, OFFSET> {
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Primitive, const OFFSET: u32> AsMut<T> for VoiceRegister<T, OFFSET> {
+    fn as_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl Volume for VolumeLeft {}
+impl Volume for VolumeRight {}
+impl Address for StartAddress {}
+impl Address for RepeatAddress {}
+
+impl Pitch {
+    /// Sets the sample rate in Hz. Rates above 176.4 kHz are clamped.
+    pub fn set_sample_rate(&mut self, hz: u32) -> &mut Self {
+        let pitch = ((hz << PITCH_SHIFT) / BASE_RATE).min(MAX_PITCH);
+        self.assign(pitch as u16)
+    }
+
+    /// Gets the sample rate in Hz.
+    pub fn get_sample_rate(&self) -> u32 {
+        (self.value as u32 * BASE_RATE) >> PITCH_SHIFT
+    }
+}
+
+impl AdsrLow {
+    /// Sets the attack, decay and sustain level from `adsr`.
+    pub fn set_adsr(&mut self, adsr: Adsr) -> &mut Self {
+        self.assign(adsr.0 as u16)
+    }
+}
+
+impl AdsrHigh {
+    /// Sets the sustain and release from `adsr`.
+    pub fn set_adsr(&mut self, adsr: Adsr) -> &mut Self {
+        self.assign((adsr.0 >> 16) as u16)
+    }
+}
+
+impl AdsrVolume {
+    /// Gets the current envelope volume ranging from 0 to 0x7FFF.
+    pub fn get_volume(&self) -> i16 {
+        self.value as i16
+    }
+}
+
+/// The settings for a voice's ADSR envelope.
+///
+/// Each phase changes the volume by a step scaled by a shift where larger
+/// shifts are slower. Exponential phases slow down as the volume increases or
+/// decreases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adsr(pub u32);
+
+impl Adsr {
+    /// Creates an envelope with the fastest linear attack, decay and release
+    /// which sustains at full volume.
+    pub const fn new() -> Self {
+        Adsr(0).sustain_level(0xF)
+    }
+
+    const fn with(self, bits: u32, mask: u32, shift: u32) -> Self {
+        Adsr((self.0 & !(mask << shift)) | ((bits & mask) << shift))
+    }
+
+    /// Sets the attack phase's shift (0-31) and step (0-3 for +7 to +4).
+    pub const fn attack(self, exponential: bool, shift: u8, step: u8) -> Self {
+        self.with(exponential as u32, 1, ATTACK_EXP)
+            .with(shift as u32, 0x1F, ATTACK_SHIFT)
+            .with(step as u32, 0b11, ATTACK_STEP)
+    }
+
+    /// Sets the decay phase's shift (0-15). Decay is always exponential.
+    pub const fn decay(self, shift: u8) -> Self {
+        self.with(shift as u32, 0xF, DECAY_SHIFT)
+    }
+
+    /// Sets the volume where decay ends as (level + 1) * 0x800.
+    pub const fn sustain_level(self, level: u8) -> Self {
+        self.with(level as u32, 0xF, SUSTAIN_LEVEL)
+    }
+
+    /// Sets the sustain phase's direction, shift (0-31) and step (0-3 for +7 to
+    /// +4 or -8 to -5).
+    pub const fn sustain(self, exponential: bool, decrease: bool, shift: u8, step: u8) -> Self {
+        self.with(exponential as u32, 1, SUSTAIN_EXP)
+            .with(decrease as u32, 1, SUSTAIN_DECREASE)
+            .with(shift as u32, 0x1F, SUSTAIN_SHIFT)
+            .with(step as u32, 0b11, SUSTAIN_STEP)
+    }
+
+    /// Sets the release phase's shift (0-31).
+    pub const fn release(self, exponential: bool, shift: u8) -> Self {
+        self.with(exponential as u32, 1, RELEASE_EXP)
+            .with(shift as u32, 0x1F, RELEASE_SHIFT)
+    }
+}
@@ -0,0 +1,255 @@
+//! Root counter (timer) registers
+
+use crate::hw::Register;
+
+const SYNC_ENABLE: u16 = 0;
+const SYNC_MODE: u16 = 1;
+const RESET_ON_TARGET: u16 = 3;
+const IRQ_ON_TARGET: u16 = 4;
+const IRQ_ON_OVERFLOW: u16 = 5;
+const IRQ_REPEAT: u16 = 6;
+const IRQ_TOGGLE: u16 = 7;
+const CLOCK_SOURCE: u16 = 8;
+// This bit is cleared while an IRQ is requested.
+const NO_IRQ: u16 = 10;
+const REACHED_TARGET: u16 = 11;
+const REACHED_OVERFLOW: u16 = 12;
+
+fn set_flag<R: Register<u16>>(reg: &mut R, bit: u16, enabled: bool) -> &mut R {
+    reg.clear_bits(1 << bit).set_bits((enabled as u16) << bit)
+}
+
+/// The name of a root counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Name {
+    /// Root counter 0 (system clock or dot clock)
+    Timer0 = 0,
+    /// Root counter 1 (system clock or hblank)
+    Timer1,
+    /// Root counter 2 (system clock or system clock / 8)
+    Timer2,
+}
+
+/// The clock a root counter increments on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The 33.8688 MHz system clock which is available for all counters.
+    System,
+    /// The GPU's dot clock which is only available for timer 0.
+    DotClock,
+    /// The GPU's horizontal blank which is only available for timer 1.
+    Hblank,
+    /// The system clock divided by 8 which is only available for timer 2.
+    SystemDiv8,
+}
+
+/// How a root counter synchronizes with the GPU's blanking signals.
+///
+/// Timers 0 and 1 use hblank and vblank, respectively. Timer 2 stops counting
+/// in [`SyncMode::Pause`] and [`SyncMode::PauseUntil`] and counts freely
+/// otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Pause the counter during blanking.
+    Pause = 0,
+    /// Reset the counter to zero at the start of blanking.
+    Reset,
+    /// Reset the counter to zero at the start of blanking and pause outside
+    /// of blanking.
+    ResetAndPause,
+    /// Pause until the start of blanking then switch to free-running.
+    PauseUntil,
+}
+
+/// Root counter 0.
+pub mod timer0 {
+    use super::{CounterMode, CounterTarget, CounterValue};
+    use crate::hw::timer::Name;
+    use crate::hw::MemRegister;
+
+    /// Root counter current value register.
+    pub type Current = MemRegister<u16, 0x1F80_1100>;
+    impl CounterValue for Current {}
+
+    /// Root counter mode register.
+    pub type Mode = MemRegister<u16, 0x1F80_1104>;
+    impl CounterMode for Mode {
+        const NAME: Name = Name::Timer0;
+    }
+
+    /// Root counter target value register.
+    pub type Target = MemRegister<u16, 0x1F80_1108>;
+    impl CounterTarget for Target {}
+}
+
+/// Root counter 1.
+pub mod timer1 {
+    use super::{CounterMode, CounterTarget, CounterValue};
+    use crate::hw::timer::Name;
+    use crate::hw::MemRegister;
+
+    /// Root counter current value register.
+    pub type Current = MemRegister<u16, 0x1F80_1110>;
+    impl CounterValue for Current {}
+
+    /// Root counter mode register.
+    pub type Mode = MemRegister<u16, 0x1F80_1114>;
+    impl CounterMode for Mode {
+        const NAME: Name = Name::Timer1;
+    }
+
+    /// Root counter target value register.
+    pub type Target = MemRegister<u16, 0x1F80_1118>;
+    impl CounterTarget for Target {}
+}
+
+/// Root counter 2.
+pub mod timer2 {
+    use super::{CounterMode, CounterTarget, CounterValue};
+    use crate::hw::timer::Name;
+    use crate::hw::MemRegister;
+
+    /// Root counter current value register.
+    pub type Current = MemRegister<u16, 0x1F80_1120>;
+    impl CounterValue for Current {}
+
+    /// Root counter mode register.
+    pub type Mode = MemRegister<u16, 0x1F80_1124>;
+    impl CounterMode for Mode {
+        const NAME: Name = Name::Timer2;
+    }
+
+    /// Root counter target value register.
+    pub type Target = MemRegister<u16, 0x1F80_1128>;
+    impl CounterTarget for Target {}
+}
+
+/// A current value register for a root counter.
+pub trait CounterValue: Register<u16> {
+    /// Gets the counter's value.
+    fn get_value(&self) -> u16 {
+        *self.as_ref()
+    }
+
+    /// Sets the counter's value.
+    fn set_value(&mut self, value: u16) -> &mut Self {
+        self.assign(value)
+    }
+}
+
+/// A target value register for a root counter.
+pub trait CounterTarget: Register<u16> {
+    /// Gets the counter's target value.
+    fn get_target(&self) -> u16 {
+        *self.as_ref()
+    }
+
+    /// Sets the counter's target value.
+    fn set_target(&mut self, target: u16) -> &mut Self {
+        self.assign(target)
+    }
+}
+
+/// A mode register for a root counter.
+///
+/// Storing this register resets the counter's value to zero. Loading it
+/// clears the reached target and overflow flags.
+pub trait CounterMode: Register<u16> {
+    /// The name of the root counter.
+    const NAME: Name;
+
+    /// Enables synchronization with the GPU's blanking signals or disables it
+    /// if `mode` is `None`.
+    fn set_sync(&mut self, mode: Option<SyncMode>) -> &mut Self {
+        match mode {
+            Some(mode) => self
+                .clear_bits(0b11 << SYNC_MODE)
+                .set_bits(1 << SYNC_ENABLE | (mode as u16) << SYNC_MODE),
+            None => self.clear_bits(1 << SYNC_ENABLE),
+        }
+    }
+
+    /// Gets the synchronization mode or `None` if synchronization is disabled.
+    fn get_sync(&self) -> Option<SyncMode> {
+        if !self.all_set(1 << SYNC_ENABLE) {
+            return None
+        }
+        let mode = match (self.as_ref() >> SYNC_MODE) & 0b11 {
+            0 => SyncMode::Pause,
+            1 => SyncMode::Reset,
+            2 => SyncMode::ResetAndPause,
+            _ => SyncMode::PauseUntil,
+        };
+        Some(mode)
+    }
+
+    /// Resets the counter to zero when it reaches the target value instead of
+    /// when it overflows.
+    fn reset_on_target(&mut self, enabled: bool) -> &mut Self {
+        set_flag(self, RESET_ON_TARGET, enabled)
+    }
+
+    /// Requests an IRQ when the counter reaches the target value.
+    fn irq_on_target(&mut self, enabled: bool) -> &mut Self {
+        set_flag(self, IRQ_ON_TARGET, enabled)
+    }
+
+    /// Requests an IRQ when the counter overflows.
+    fn irq_on_overflow(&mut self, enabled: bool) -> &mut Self {
+        set_flag(self, IRQ_ON_OVERFLOW, enabled)
+    }
+
+    /// Requests an IRQ each time the condition is met instead of only once
+    /// after the mode is stored.
+    fn irq_repeat(&mut self, repeat: bool) -> &mut Self {
+        set_flag(self, IRQ_REPEAT, repeat)
+    }
+
+    /// Toggles the IRQ signal instead of pulsing it.
+    fn irq_toggle(&mut self, toggle: bool) -> &mut Self {
+        set_flag(self, IRQ_TOGGLE, toggle)
+    }
+
+    /// Sets the clock the counter increments on. Returns `None` if the source
+    /// isn't available for the counter.
+    fn set_source(&mut self, source: Source) -> Option<&mut Self> {
+        let bits = match (Self::NAME, source) {
+            (_, Source::System) => 0,
+            (Name::Timer0, Source::DotClock) => 1,
+            (Name::Timer1, Source::Hblank) => 1,
+            (Name::Timer2, Source::SystemDiv8) => 2,
+            _ => return None,
+        };
+        Some(
+            self.clear_bits(0b11 << CLOCK_SOURCE)
+                .set_bits(bits << CLOCK_SOURCE),
+        )
+    }
+
+    /// Gets the clock the counter increments on.
+    fn get_source(&self) -> Source {
+        let bits = (self.as_ref() >> CLOCK_SOURCE) & 0b11;
+        match (Self::NAME, bits) {
+            (Name::Timer0, 1 | 3) => Source::DotClock,
+            (Name::Timer1, 1 | 3) => Source::Hblank,
+            (Name::Timer2, 2 | 3) => Source::SystemDiv8,
+            _ => Source::System,
+        }
+    }
+
+    /// Checks if the counter is requesting an IRQ.
+    fn irq_requested(&self) -> bool {
+        !self.all_set(1 << NO_IRQ)
+    }
+
+    /// Checks if the counter reached the target value since the mode was last
+    /// loaded.
+    fn reached_target(&self) -> bool {
+        self.all_set(1 << REACHED_TARGET)
+    }
+
+    /// Checks if the counter overflowed since the mode was last loaded.
+    fn reached_overflow(&self) -> bool {
+        self.all_set(1 << REACHED_OVERFLOW)
+    }
+}
@@ -0,0 +1,274 @@
+//! Button edge detection, auto-repeat and remapping
+//!
+//! [`InputState`] is updated once per frame with the buttons from either
+//! [`Gamepad`][crate::sys::gamepad::Gamepad] or [`Pad`][crate::pad::Pad] and
+//! keeps enough history to tell when buttons were just pressed or released,
+//! how long they've been held and when a held button should repeat, e.g. for
+//! scrolling through a menu.
+//!
+//! [`ActionMap`] binds logical actions to one or more buttons so games can
+//! query actions instead of buttons and let players remap them. Actions are
+//! indices, usually a fieldless enum cast with `as usize`.
+
+use crate::pad::{Button, Buttons};
+
+/// The number of buttons tracked by an [`InputState`].
+pub const BUTTONS: usize = 16;
+/// The default number of frames a button must be held before it repeats.
+pub const DEFAULT_REPEAT_DELAY: u16 = 20;
+/// The default number of frames between repeats.
+pub const DEFAULT_REPEAT_INTERVAL: u16 = 4;
+
+/// The buttons' state over consecutive frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputState {
+    current: u16,
+    previous: u16,
+    // The number of frames each button has been held for
+    held: [u16; BUTTONS],
+    repeat_delay: u16,
+    repeat_interval: u16,
+}
+
+impl InputState {
+    /// Creates an input state with no buttons pressed and the default repeat
+    /// timing.
+    pub const fn new() -> Self {
+        InputState {
+            current: 0,
+            previous: 0,
+            held: [0; BUTTONS],
+            repeat_delay: DEFAULT_REPEAT_DELAY,
+            repeat_interval: DEFAULT_REPEAT_INTERVAL,
+        }
+    }
+
+    /// Sets the number of frames a button must be held before it repeats and
+    /// the number of frames between repeats. An interval of 0 repeats every
+    /// frame.
+    pub fn set_repeat(&mut self, delay: u16, interval: u16) -> &mut Self {
+        self.repeat_delay = delay;
+        self.repeat_interval = interval.max(1);
+        self
+    }
+
+    /// Updates the state with the buttons polled this frame. This should be
+    /// called exactly once per frame.
+    pub fn update(&mut self, buttons: Buttons) {
+        self.previous = self.current;
+        self.current = buttons.to_bits();
+        for (bit, frames) in self.held.iter_mut().enumerate() {
+            if self.current & (1 << bit) != 0 {
+                *frames = frames.saturating_add(1);
+            } else {
+                *frames = 0;
+            }
+        }
+    }
+
+    /// Clears all buttons, e.g. after switching screens so a held button isn't
+    /// seen as a new press on the next one.
+    pub fn reset(&mut self) {
+        self.current = 0;
+        self.previous = 0;
+        self.held = [0; BUTTONS];
+    }
+
+    /// The buttons pressed in the latest frame.
+    pub fn buttons(&self) -> Buttons {
+        Buttons::new(self.current)
+    }
+
+    /// Checks if `button` is pressed.
+    pub fn pressed(&self, button: Button) -> bool {
+        self.any_pressed(1 << button as u16)
+    }
+
+    /// Checks if `button` is released.
+    pub fn released(&self, button: Button) -> bool {
+        !self.pressed(button)
+    }
+
+    /// Checks if `button` was pressed in the latest frame but not the one
+    /// before it.
+    pub fn just_pressed(&self, button: Button) -> bool {
+        self.any_just_pressed(1 << button as u16)
+    }
+
+    /// Checks if `button` was released in the latest frame but not the one
+    /// before it.
+    pub fn just_released(&self, button: Button) -> bool {
+        self.any_just_released(1 << button as u16)
+    }
+
+    /// The number of frames `button` has been held for, including the latest
+    /// one. This is 0 if the button is released.
+    pub fn held_frames(&self, button: Button) -> u16 {
+        self.held[button as usize]
+    }
+
+    /// Checks if `button` was just pressed or has been held long enough to
+    /// repeat in the latest frame.
+    pub fn repeated(&self, button: Button) -> bool {
+        self.any_repeated(1 << button as u16)
+    }
+
+    fn any_pressed(&self, mask: u16) -> bool {
+        self.current & mask != 0
+    }
+
+    // Checks if any of the buttons in `mask` are pressed after none were.
+    fn any_just_pressed(&self, mask: u16) -> bool {
+        self.current & mask != 0 && self.previous & mask == 0
+    }
+
+    // Checks if all of the buttons in `mask` are released after any were
+    // pressed.
+    fn any_just_released(&self, mask: u16) -> bool {
+        self.current & mask == 0 && self.previous & mask != 0
+    }
+
+    fn any_repeated(&self, mask: u16) -> bool {
+        (0..BUTTONS)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .any(|bit| self.repeats_after(self.held[bit]))
+    }
+
+    fn repeats_after(&self, frames: u16) -> bool {
+        match frames {
+            0 => false,
+            1 => true,
+            _ => {
+                let frames = frames - 1;
+                frames >= self.repeat_delay &&
+                    (frames - self.repeat_delay) % self.repeat_interval == 0
+            },
+        }
+    }
+}
+
+/// Bindings from `N` logical actions to buttons.
+///
+/// An action may be bound to any number of buttons and is pressed while any of
+/// them are. Actions are numbered from 0 to `N - 1`, e.g. with the variants of
+/// a fieldless enum cast to `usize`.
+///
+/// # Panics
+///
+/// The methods taking an `action` panic if it isn't less than `N`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionMap<const N: usize> {
+    bindings: [u16; N],
+}
+
+impl<const N: usize> ActionMap<N> {
+    /// Creates an action map with no bindings.
+    pub const fn new() -> Self {
+        ActionMap { bindings: [0; N] }
+    }
+
+    /// Binds `button` to `action` in addition to its existing bindings.
+    pub fn bind(&mut self, action: usize, button: Button) -> &mut Self {
+        self.bindings[action] |= 1 << button as u16;
+        self
+    }
+
+    /// Removes `button` from `action`'s bindings.
+    pub fn unbind(&mut self, action: usize, button: Button) -> &mut Self {
+        self.bindings[action] &= !(1 << button as u16);
+        self
+    }
+
+    /// Replaces `action`'s bindings with `button`, e.g. when a player remaps
+    /// it.
+    pub fn rebind(&mut self, action: usize, button: Button) -> &mut Self {
+        self.bindings[action] = 0;
+        self.bind(action, button)
+    }
+
+    /// The buttons bound to `action`.
+    pub fn bindings(&self, action: usize) -> Buttons {
+        Buttons::new(self.bindings[action])
+    }
+
+    /// Checks if `action` is pressed.
+    pub fn pressed(&self, input: &InputState, action: usize) -> bool {
+        input.any_pressed(self.bindings[action])
+    }
+
+    /// Checks if `action` was pressed in the latest frame but not the one
+    /// before it.
+    pub fn just_pressed(&self, input: &InputState, action: usize) -> bool {
+        input.any_just_pressed(self.bindings[action])
+    }
+
+    /// Checks if `action` was released in the latest frame but not the one
+    /// before it.
+    pub fn just_released(&self, input: &InputState, action: usize) -> bool {
+        input.any_just_released(self.bindings[action])
+    }
+
+    /// Checks if `action` was just pressed or any of its buttons have been held
+    /// long enough to repeat in the latest frame.
+    pub fn repeated(&self, input: &InputState, action: usize) -> bool {
+        input.any_repeated(self.bindings[action])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActionMap, InputState};
+    use crate::pad::{Button, Buttons};
+
+    fn frame(input: &mut InputState, buttons: &[Button]) {
+        let bits = buttons.iter().fold(0, |bits, &b| bits | 1 << b as u16);
+        input.update(Buttons::new(bits));
+    }
+
+    #[test_case]
+    fn edges() {
+        let mut input = InputState::new();
+        frame(&mut input, &[Button::Cross]);
+        assert!(input.just_pressed(Button::Cross));
+        assert!(input.held_frames(Button::Cross) == 1);
+        frame(&mut input, &[Button::Cross]);
+        assert!(input.pressed(Button::Cross) && !input.just_pressed(Button::Cross));
+        assert!(input.held_frames(Button::Cross) == 2);
+        frame(&mut input, &[]);
+        assert!(input.just_released(Button::Cross));
+        assert!(input.held_frames(Button::Cross) == 0);
+        frame(&mut input, &[]);
+        assert!(!input.just_released(Button::Cross));
+    }
+
+    #[test_case]
+    fn repeat() {
+        let mut input = InputState::new();
+        input.set_repeat(3, 2);
+        let mut repeats = [false; 8];
+        for repeated in &mut repeats {
+            frame(&mut input, &[Button::Down]);
+            *repeated = input.repeated(Button::Down);
+        }
+        assert!(repeats == [true, false, false, true, false, true, false, true]);
+    }
+
+    #[test_case]
+    fn actions() {
+        const CONFIRM: usize = 0;
+        const CANCEL: usize = 1;
+        let mut map = ActionMap::<2>::new();
+        map.bind(CONFIRM, Button::Cross)
+            .bind(CONFIRM, Button::Start)
+            .bind(CANCEL, Button::Circle);
+        let mut input = InputState::new();
+        frame(&mut input, &[Button::Start]);
+        assert!(map.just_pressed(&input, CONFIRM) && !map.pressed(&input, CANCEL));
+        // Pressing another bound button doesn't press the action again
+        frame(&mut input, &[Button::Start, Button::Cross]);
+        assert!(map.pressed(&input, CONFIRM) && !map.just_pressed(&input, CONFIRM));
+        map.rebind(CANCEL, Button::Cross);
+        assert!(map.just_pressed(&input, CANCEL));
+        assert!(map.bindings(CANCEL).to_bits() == 1 << Button::Cross as u16);
+    }
+}
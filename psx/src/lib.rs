@@ -58,21 +58,31 @@
 #[macro_use]
 mod test;
 
+pub mod cdrom;
 pub mod dma;
 pub mod format;
 mod framebuffer;
+pub mod gfx3d;
 pub mod gpu;
 #[doc(hidden)]
 pub mod heap;
 pub mod hw;
+pub mod input;
 mod macros;
 pub mod math;
+pub mod mdec;
+pub mod memcard;
+pub mod pad;
 mod panic;
+pub mod profile;
 #[doc(hidden)]
 pub mod runtime;
+pub mod spu;
 #[doc(hidden)]
 pub mod std;
 pub mod sys;
+pub mod timer;
+pub mod video;
 
 /// Re-exported constants in a module for easy glob importing.
 pub mod constants {
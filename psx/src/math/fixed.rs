@@ -0,0 +1,298 @@
+//! Generic 32-bit fixed-point numbers
+//!
+//! [`Fixed`] covers values with more range or precision than [`f16`], such as
+//! world coordinates and the GTE's 20.12 registers. The number of fractional
+//! bits is a const parameter so conversions between formats are explicit.
+
+use crate::math::{f16, isqrt};
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// A signed 32-bit fixed-point number with `FRAC` fractional bits.
+///
+/// Arithmetic overflows like integer arithmetic. Products and quotients are
+/// computed with 64-bit intermediates so only the result has to fit. `FRAC`
+/// must be less than 31 so 1.0 is representable, which is checked at compile
+/// time by [`ONE`][Self::ONE] and [`from_int`][Self::from_int].
+#[repr(transparent)]
+#[derive(Debug, Default, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct Fixed<const FRAC: u32>(pub i32);
+
+/// A fixed-point number with 20 integral and 12 fractional bits, the format of
+/// the GTE's 32-bit values.
+pub type I20F12 = Fixed<12>;
+
+/// A fixed-point number with 16 integral and 16 fractional bits.
+pub type I16F16 = Fixed<16>;
+
+// Shifts left for positive `shift` or right for negative `shift`.
+const fn shift(x: i64, shift: i32) -> i64 {
+    if shift >= 0 {
+        x << shift
+    } else {
+        x >> -shift
+    }
+}
+
+const fn saturate(x: i64) -> i32 {
+    if x > i32::MAX as i64 {
+        i32::MAX
+    } else if x < i32::MIN as i64 {
+        i32::MIN
+    } else {
+        x as i32
+    }
+}
+
+impl<const FRAC: u32> Fixed<FRAC> {
+    // Fails to compile formats where 1.0 doesn't fit in an `i32`.
+    const VALID: () = assert!(FRAC < 31, "Fixed must have fewer than 31 fractional bits");
+
+    /// The value 0.0.
+    pub const ZERO: Self = Fixed(0);
+
+    /// The value 1.0.
+    pub const ONE: Self = {
+        let () = Self::VALID;
+        Fixed(1 << FRAC)
+    };
+
+    /// The smallest representable value.
+    pub const MIN: Self = Fixed(i32::MIN);
+
+    /// The largest representable value.
+    pub const MAX: Self = Fixed(i32::MAX);
+
+    /// Converts an integer to fixed-point.
+    pub const fn from_int(x: i32) -> Self {
+        let () = Self::VALID;
+        Fixed(x << FRAC)
+    }
+
+    /// Converts to an integer rounding towards negative infinity.
+    pub const fn to_int_lossy(self) -> i32 {
+        self.0 >> FRAC
+    }
+
+    /// Raw transmutation from an `i32`.
+    pub const fn from_bits(x: i32) -> Self {
+        Fixed(x)
+    }
+
+    /// Raw transmutation to an `i32`.
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// Converts to a format with a different number of fractional bits. This
+    /// drops fractional bits or overflows if the new format doesn't have
+    /// enough of them.
+    pub const fn convert<const TO: u32>(self) -> Fixed<TO> {
+        Fixed(shift(self.0 as i64, TO as i32 - FRAC as i32) as i32)
+    }
+
+    /// Converts an `f16`.
+    pub const fn from_f16(x: f16) -> Self {
+        Fixed(shift(x.0 as i64, FRAC as i32 - f16::FRAC as i32) as i32)
+    }
+
+    /// Converts to an `f16`, saturating if the value is out of range.
+    pub const fn to_f16(self) -> f16 {
+        let x = shift(self.0 as i64, f16::FRAC as i32 - FRAC as i32);
+        if x > i16::MAX as i64 {
+            f16(i16::MAX)
+        } else if x < i16::MIN as i64 {
+            f16(i16::MIN)
+        } else {
+            f16(x as i16)
+        }
+    }
+
+    /// Returns the absolute value.
+    pub const fn abs(self) -> Self {
+        Fixed(self.0.abs())
+    }
+
+    /// Returns the integer part.
+    pub const fn trunc(self) -> Self {
+        Fixed(self.0 & !((1 << FRAC) - 1))
+    }
+
+    /// Returns the fractional part.
+    pub const fn fract(self) -> Self {
+        Fixed(self.0 & ((1 << FRAC) - 1))
+    }
+
+    /// Adds, saturating at the numeric bounds.
+    pub const fn saturating_add(self, other: Self) -> Self {
+        Fixed(self.0.saturating_add(other.0))
+    }
+
+    /// Subtracts, saturating at the numeric bounds.
+    pub const fn saturating_sub(self, other: Self) -> Self {
+        Fixed(self.0.saturating_sub(other.0))
+    }
+
+    /// Multiplies, saturating at the numeric bounds.
+    pub const fn saturating_mul(self, other: Self) -> Self {
+        Fixed(saturate((self.0 as i64 * other.0 as i64) >> FRAC))
+    }
+
+    /// Computes `1 / self`, saturating at the numeric bounds. The reciprocal
+    /// of 0 is [`MAX`][Self::MAX].
+    pub const fn recip(self) -> Self {
+        if self.0 == 0 {
+            return Self::MAX
+        }
+        Fixed(saturate((1 << (2 * FRAC)) / self.0 as i64))
+    }
+
+    /// Computes the square root. Negative values give 0.
+    pub fn sqrt(self) -> Self {
+        if self.0 <= 0 {
+            return Self::ZERO
+        }
+        Fixed(isqrt((self.0 as u64) << FRAC) as i32)
+    }
+
+    /// Interpolates linearly from `self` to `other`, where `t` is 0.0 at
+    /// `self` and 1.0 at `other`.
+    pub fn lerp(self, other: Self, t: Self) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl<const FRAC: u32> From<f16> for Fixed<FRAC> {
+    fn from(x: f16) -> Self {
+        Self::from_f16(x)
+    }
+}
+
+impl<const FRAC: u32> From<Fixed<FRAC>> for f16 {
+    fn from(x: Fixed<FRAC>) -> Self {
+        x.to_f16()
+    }
+}
+
+impl<const FRAC: u32> From<i16> for Fixed<FRAC> {
+    fn from(x: i16) -> Self {
+        Self::from_int(x as i32)
+    }
+}
+
+impl<const FRAC: u32> Neg for Fixed<FRAC> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Fixed(-self.0)
+    }
+}
+impl<const FRAC: u32> Add for Fixed<FRAC> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Fixed(self.0 + other.0)
+    }
+}
+impl<const FRAC: u32> Sub for Fixed<FRAC> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Fixed(self.0 - other.0)
+    }
+}
+impl<const FRAC: u32> Mul for Fixed<FRAC> {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Fixed(((self.0 as i64 * other.0 as i64) >> FRAC) as i32)
+    }
+}
+impl<const FRAC: u32> Mul<i32> for Fixed<FRAC> {
+    type Output = Self;
+    fn mul(self, other: i32) -> Self {
+        Fixed(self.0 * other)
+    }
+}
+/// Division panics if the divisor is zero.
+impl<const FRAC: u32> Div for Fixed<FRAC> {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        Fixed((((self.0 as i64) << FRAC) / other.0 as i64) as i32)
+    }
+}
+/// Division panics if the divisor is zero.
+impl<const FRAC: u32> Div<i32> for Fixed<FRAC> {
+    type Output = Self;
+    fn div(self, other: i32) -> Self {
+        Fixed(self.0 / other)
+    }
+}
+impl<const FRAC: u32> AddAssign for Fixed<FRAC> {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+impl<const FRAC: u32> SubAssign for Fixed<FRAC> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+impl<const FRAC: u32> MulAssign for Fixed<FRAC> {
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other;
+    }
+}
+impl<const FRAC: u32> MulAssign<i32> for Fixed<FRAC> {
+    fn mul_assign(&mut self, other: i32) {
+        *self = *self * other;
+    }
+}
+/// Division panics if the divisor is zero.
+impl<const FRAC: u32> DivAssign for Fixed<FRAC> {
+    fn div_assign(&mut self, other: Self) {
+        *self = *self / other;
+    }
+}
+/// Division panics if the divisor is zero.
+impl<const FRAC: u32> DivAssign<i32> for Fixed<FRAC> {
+    fn div_assign(&mut self, other: i32) {
+        *self = *self / other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{I16F16, I20F12};
+    use crate::math::f16;
+
+    #[test_case]
+    fn arithmetic() {
+        let a = I20F12::from_int(3);
+        let b = I20F12::from_bits(0x800);
+        assert!(a * b == I20F12::from_bits(0x1800));
+        assert!(a / b == I20F12::from_int(6));
+        assert!((-a).to_int_lossy() == -3);
+        assert!(I20F12::from_bits(-1).to_int_lossy() == -1);
+        assert!((a + b).trunc() == a && (a + b).fract() == b);
+        assert!(I20F12::MAX.saturating_add(b) == I20F12::MAX);
+        assert!(I20F12::MIN.saturating_sub(b) == I20F12::MIN);
+        assert!(I20F12::from_int(0x40000).saturating_mul(a) == I20F12::MAX);
+    }
+
+    #[test_case]
+    fn conversions() {
+        let x = I20F12::from_bits(0x1_800);
+        let y: I16F16 = x.convert();
+        assert!(y == I16F16::from_bits(0x1_8000));
+        assert!(y.convert::<12>() == x);
+        assert!(I20F12::from(f16(-0x180)) == I20F12::from_bits(-0x1800));
+        assert!(f16::from(x) == f16(0x180));
+        assert!(I20F12::from_int(1000).to_f16() == f16(i16::MAX));
+    }
+
+    #[test_case]
+    fn roots() {
+        assert!(I20F12::from_int(4).recip() == I20F12::from_bits(0x400));
+        assert!(I20F12::ZERO.recip() == I20F12::MAX);
+        assert!(I20F12::from_int(9).sqrt() == I20F12::from_int(3));
+        assert!(I16F16::from_bits(0x4000).sqrt() == I16F16::from_bits(0x8000));
+        let (a, b) = (I20F12::from_int(2), I20F12::from_int(6));
+        assert!(a.lerp(b, I20F12::from_bits(0x400)) == I20F12::from_int(3));
+    }
+}
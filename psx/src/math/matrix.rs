@@ -0,0 +1,227 @@
+//! 3x3 fixed-point matrices
+//!
+//! Rotation matrices are built from a 12-bit sine table which is interpolated
+//! between its entries, so their entries are within one unit of the exact
+//! 1.3.12 values.
+
+use crate::math::{Rad, Vec3, FRAC_PI_2, PI, SINE_TABLE, SINE_TABLE_SIZE};
+use core::ops::{Mul, MulAssign};
+
+/// 1.0 in the 1.3.12 fixed-point format used by the GTE.
+pub const ONE: i16 = 1 << 12;
+
+/// A 3x3 matrix in 1.3.12 fixed-point.
+///
+/// The entries are stored by rows, which is the same layout as the GTE's
+/// rotation, light and light color matrices so [`Mat3::to_words`] gives the
+/// values of their five control registers.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mat3(pub [[i16; 3]; 3]);
+
+// Gets the sine of `x` in 1.3.12 fixed-point by interpolating between the
+// entries of a quarter cycle table.
+fn sine(x: u16) -> i16 {
+    let quarter_cycle = FRAC_PI_2.0 as u32;
+    let offset = x as u32 % quarter_cycle;
+    let offset = if x & FRAC_PI_2.0 != 0 {
+        quarter_cycle - offset
+    } else {
+        offset
+    };
+    let step = quarter_cycle / SINE_TABLE_SIZE as u32;
+    let idx = (offset / step) as usize;
+    let frac = (offset % step) as i32;
+    let lo = SINE_TABLE[idx] as i32;
+    let hi = SINE_TABLE[(idx + 1).min(SINE_TABLE_SIZE)] as i32;
+    let res = (lo + ((hi - lo) * frac + step as i32 / 2) / step as i32) as i16;
+    if x >= PI.0 {
+        -res
+    } else {
+        res
+    }
+}
+
+// Gets the sine and cosine of `theta` in 1.3.12 fixed-point.
+pub(crate) fn sin_cos(theta: Rad) -> (i16, i16) {
+    (sine(theta.0), sine(theta.0.wrapping_add(FRAC_PI_2.0)))
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mat3 {
+    /// The identity matrix.
+    pub const IDENTITY: Self = Mat3([[ONE, 0, 0], [0, ONE, 0], [0, 0, ONE]]);
+
+    /// A rotation by `theta` radians about the x axis.
+    pub fn rotation_x(theta: Rad) -> Self {
+        let (s, c) = sin_cos(theta);
+        Mat3([[ONE, 0, 0], [0, c, -s], [0, s, c]])
+    }
+
+    /// A rotation by `theta` radians about the y axis.
+    pub fn rotation_y(theta: Rad) -> Self {
+        let (s, c) = sin_cos(theta);
+        Mat3([[c, 0, s], [0, ONE, 0], [-s, 0, c]])
+    }
+
+    /// A rotation by `theta` radians about the z axis.
+    pub fn rotation_z(theta: Rad) -> Self {
+        let (s, c) = sin_cos(theta);
+        Mat3([[c, -s, 0], [s, c, 0], [0, 0, ONE]])
+    }
+
+    /// A rotation about the x axis, then the y axis, then the z axis.
+    pub fn from_euler(x: Rad, y: Rad, z: Rad) -> Self {
+        Self::rotation_z(z) * Self::rotation_y(y) * Self::rotation_x(x)
+    }
+
+    /// Creates a matrix with the given rows. The components are truncated to
+    /// 16 bits so they should be between -8.0 and 8.0 in 20.12 fixed-point.
+    pub fn from_rows(rows: [Vec3; 3]) -> Self {
+        Mat3(rows.map(|r| [r.x as i16, r.y as i16, r.z as i16]))
+    }
+
+    /// Gets row `i`.
+    pub fn row(&self, i: usize) -> Vec3 {
+        let [x, y, z] = self.0[i];
+        Vec3::new(x as i32, y as i32, z as i32)
+    }
+
+    /// Gets column `j`.
+    pub fn column(&self, j: usize) -> Vec3 {
+        let m = &self.0;
+        Vec3::new(m[0][j] as i32, m[1][j] as i32, m[2][j] as i32)
+    }
+
+    /// Swaps the rows and columns. This inverts rotation matrices.
+    pub fn transpose(&self) -> Self {
+        let m = &self.0;
+        Mat3([
+            [m[0][0], m[1][0], m[2][0]],
+            [m[0][1], m[1][1], m[2][1]],
+            [m[0][2], m[1][2], m[2][2]],
+        ])
+    }
+
+    /// Packs the entries into the words stored in the GTE's matrix control
+    /// registers, e.g. [`RT11_12`][crate::hw::gte::RT11_12] to
+    /// [`RT33`][crate::hw::gte::RT33].
+    pub fn to_words(&self) -> [u32; 5] {
+        let m = &self.0;
+        let pack = |lo: i16, hi: i16| (lo as u16 as u32) | ((hi as u16 as u32) << 16);
+        [
+            pack(m[0][0], m[0][1]),
+            pack(m[0][2], m[1][0]),
+            pack(m[1][1], m[1][2]),
+            pack(m[2][0], m[2][1]),
+            m[2][2] as u16 as u32,
+        ]
+    }
+}
+
+/// Entries of the product which don't fit in 1.3.12 are truncated to 16 bits.
+impl Mul<Mat3> for Mat3 {
+    type Output = Mat3;
+    fn mul(self, other: Mat3) -> Mat3 {
+        let mut res = [[0; 3]; 3];
+        for (i, row) in res.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                let dot = (0..3)
+                    .map(|k| self.0[i][k] as i64 * other.0[k][j] as i64)
+                    .sum::<i64>();
+                *entry = (dot >> 12) as i16;
+            }
+        }
+        Mat3(res)
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+    fn mul(self, v: Vec3) -> Vec3 {
+        let row = |i: usize| {
+            let [a, b, c] = self.0[i];
+            let sum = a as i64 * v.x as i64 + b as i64 * v.y as i64 + c as i64 * v.z as i64;
+            (sum >> 12) as i32
+        };
+        Vec3::new(row(0), row(1), row(2))
+    }
+}
+
+impl MulAssign<Mat3> for Mat3 {
+    fn mul_assign(&mut self, other: Mat3) {
+        *self = *self * other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sin_cos, Mat3, ONE};
+    use crate::math::tests::{cos_f64, near, rad_f64, sin_f64};
+    use crate::math::{Rad, Vec3, FRAC_PI_2, FRAC_PI_4};
+
+    #[test_case]
+    fn rotation() {
+        // Rotating X by π/2 about Z gives Y
+        let rz = Mat3::rotation_z(FRAC_PI_2);
+        assert!(rz * Vec3::X == Vec3::Y);
+        assert!(rz.transpose() * Vec3::Y == Vec3::X);
+        assert!(rz * rz.transpose() == Mat3::IDENTITY);
+        // cos(π/4) is 0.7071 or 2896 in 4.12
+        let rx = Mat3::rotation_x(FRAC_PI_4);
+        let v = rx * Vec3::new(0, 4096, 0);
+        assert!((v.y - 2896).abs() <= 1 && (v.z - 2896).abs() <= 1);
+    }
+
+    #[test_case]
+    fn reference() {
+        for theta in (0..=u16::MAX).step_by(0x0123).map(Rad) {
+            let (s, c) = sin_cos(theta);
+            let x = rad_f64(theta);
+            assert!(near(s as i32, sin_f64(x), 1.0) && near(c as i32, cos_f64(x), 1.0));
+            // Rotating (1, 2, 3) about each axis
+            let v = Vec3::new(4096, 8192, 12288);
+            let (s, c) = (sin_f64(x), cos_f64(x));
+            let r = Mat3::rotation_x(theta) * v;
+            assert!(near(r.x, 1.0, 0.0) && near(r.y, 2.0 * c - 3.0 * s, 4.0));
+            assert!(near(r.z, 2.0 * s + 3.0 * c, 4.0));
+            let r = Mat3::rotation_y(theta) * v;
+            assert!(near(r.x, c + 3.0 * s, 4.0) && near(r.z, 3.0 * c - s, 4.0));
+            let r = Mat3::rotation_z(theta) * v;
+            assert!(near(r.x, c - 2.0 * s, 4.0) && near(r.y, s + 2.0 * c, 4.0));
+        }
+    }
+
+    #[test_case]
+    fn euler() {
+        let m = Mat3::from_euler(FRAC_PI_2, FRAC_PI_2, Rad(0));
+        // X is rotated to -Z by the y rotation and Y is rotated to Z by the x
+        // rotation then to X by the y rotation
+        assert!(m * Vec3::X == -Vec3::Z);
+        assert!(m * Vec3::Y == Vec3::X);
+        assert!(m.row(2) == -Vec3::X && m.column(1) == Vec3::X);
+    }
+
+    #[test_case]
+    fn words() {
+        let m = Mat3([[1, 2, 3], [4, 5, 6], [7, 8, -ONE]]);
+        let words = m.to_words();
+        assert!(words[0] == 0x0002_0001 && words[1] == 0x0004_0003);
+        assert!(words[3] == 0x0008_0007 && words[4] == 0xF000);
+    }
+
+    #[test_case]
+    fn large_entries() {
+        // The sum of three products of -8.0 and 8.0 doesn't fit in 32 bits
+        let m = Mat3([[0x7FFF; 3]; 3]) * Mat3([[-0x8000; 3]; 3]);
+        assert!(m.0[0][0] == ((3 * 0x7FFF * -0x8000i64) >> 12) as i16);
+        let scaled = Mat3::from_rows([Vec3::X * 2, Vec3::Y * 2, Vec3::Z * 2]);
+        let squared = scaled * scaled;
+        assert!(squared.0 == [[0x4000, 0, 0], [0, 0x4000, 0], [0, 0, 0x4000]]);
+    }
+}
@@ -0,0 +1,261 @@
+//! Quaternions for interpolating rotations
+//!
+//! Rotations are usually composed as [`Mat3`]s, but quaternions can be
+//! interpolated smoothly with [`Quat::slerp`] and converted back with
+//! [`Quat::to_mat3`] before loading them into the GTE.
+
+use crate::math::matrix::sin_cos;
+use crate::math::{acos, isqrt, Mat3, Rad, Vec3, Vec4, I20F12, ONE};
+use core::ops::{Mul, MulAssign, Neg};
+
+// Quaternions closer than this are interpolated linearly since their angle's
+// sine is too small to divide by accurately.
+const SLERP_THRESHOLD: i32 = ONE as i32 - 64;
+
+/// A quaternion with 20.12 fixed-point components.
+///
+/// Rotations are represented by unit quaternions, i.e. with a length of
+/// [`ONE`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quat {
+    /// The real part.
+    pub w: i32,
+    /// The first imaginary part.
+    pub x: i32,
+    /// The second imaginary part.
+    pub y: i32,
+    /// The third imaginary part.
+    pub z: i32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quat {
+    /// The quaternion which doesn't rotate.
+    pub const IDENTITY: Self = Quat::new(ONE as i32, 0, 0, 0);
+
+    /// Creates a quaternion from its components.
+    pub const fn new(w: i32, x: i32, y: i32, z: i32) -> Self {
+        Quat { w, x, y, z }
+    }
+
+    /// A rotation by `angle` radians about `axis`, which must be a unit
+    /// vector.
+    pub fn from_axis_angle(axis: Vec3, angle: Rad) -> Self {
+        let (s, c) = sin_cos(Rad(angle.0 / 2));
+        let v = axis.scale(s as i32);
+        Quat::new(c as i32, v.x, v.y, v.z)
+    }
+
+    /// The imaginary parts as a vector.
+    pub const fn vector(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    const fn to_vec4(self) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, self.w)
+    }
+
+    const fn from_vec4(v: Vec4) -> Self {
+        Quat::new(v.w, v.x, v.y, v.z)
+    }
+
+    /// Negates the imaginary parts. This inverts unit quaternions.
+    pub fn conjugate(self) -> Self {
+        Quat::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Computes the dot product in 20.12 fixed-point.
+    pub fn dot(self, other: Self) -> i32 {
+        self.to_vec4().dot(other.to_vec4())
+    }
+
+    /// Scales the quaternion to a length of [`ONE`].
+    pub fn normalize(self) -> Self {
+        let len = self.dot(self) as i64;
+        if len <= 0 {
+            return Self::IDENTITY
+        }
+        // The dot product is the squared length in 20.12
+        let len = isqrt((len as u64) << 12) as i64;
+        let scale = |c: i32| (((c as i64) << 12) / len) as i32;
+        Quat::new(scale(self.w), scale(self.x), scale(self.y), scale(self.z))
+    }
+
+    /// Rotates `v` by the quaternion.
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        self.to_mat3() * v
+    }
+
+    /// Converts a unit quaternion to a rotation matrix.
+    pub fn to_mat3(self) -> Mat3 {
+        let Quat { w, x, y, z } = self;
+        // Products scaled back to 20.12
+        let p = |a: i32, b: i32| ((a as i64 * b as i64) >> 12) as i32;
+        let one = ONE as i32;
+        let m = |e: i32| e as i16;
+        Mat3([
+            [
+                m(one - 2 * (p(y, y) + p(z, z))),
+                m(2 * (p(x, y) - p(w, z))),
+                m(2 * (p(x, z) + p(w, y))),
+            ],
+            [
+                m(2 * (p(x, y) + p(w, z))),
+                m(one - 2 * (p(x, x) + p(z, z))),
+                m(2 * (p(y, z) - p(w, x))),
+            ],
+            [
+                m(2 * (p(x, z) - p(w, y))),
+                m(2 * (p(y, z) + p(w, x))),
+                m(one - 2 * (p(x, x) + p(y, y))),
+            ],
+        ])
+    }
+
+    /// Interpolates linearly from `self` to `other` and normalizes the result.
+    /// `t` is 20.12 fixed-point from 0 to [`ONE`].
+    pub fn nlerp(self, other: Self, t: i32) -> Self {
+        let other = if self.dot(other) < 0 { -other } else { other };
+        let a = self.to_vec4().scale(ONE as i32 - t);
+        let b = other.to_vec4().scale(t);
+        Quat::from_vec4(a + b).normalize()
+    }
+
+    /// Interpolates from `self` to `other` at a constant angular velocity
+    /// along the shortest path. `t` is 20.12 fixed-point from 0 to [`ONE`].
+    pub fn slerp(self, other: Self, t: i32) -> Self {
+        let mut cos_theta = self.dot(other);
+        let other = if cos_theta < 0 {
+            cos_theta = -cos_theta;
+            -other
+        } else {
+            other
+        };
+        if cos_theta >= SLERP_THRESHOLD {
+            return self.nlerp(other, t)
+        }
+        // The arccosine table is within 8 units of `Rad` for the dot products
+        // reaching here, which is closer than inverting the sine table
+        let theta = acos(I20F12::from_bits(cos_theta)).0 as i32;
+        let angle = |t: i32| Rad(((theta * t) >> 12) as u16);
+        let sin_theta = sin_cos(Rad(theta as u16)).0 as i32;
+        // The weights are sin((1 - t)θ) / sin(θ) and sin(tθ) / sin(θ)
+        let weight = |t: i32| ((sin_cos(angle(t)).0 as i32) << 12) / sin_theta;
+        let a = self.to_vec4().scale(weight(ONE as i32 - t));
+        let b = other.to_vec4().scale(weight(t));
+        Quat::from_vec4(a + b).normalize()
+    }
+}
+
+/// Composes two rotations so that `a * b` rotates by `b` first, then `a`.
+impl Mul<Quat> for Quat {
+    type Output = Quat;
+    fn mul(self, other: Quat) -> Quat {
+        let (a, b) = (self, other);
+        let p = |x: i32, y: i32| x as i64 * y as i64;
+        let w = p(a.w, b.w) - p(a.x, b.x) - p(a.y, b.y) - p(a.z, b.z);
+        let x = p(a.w, b.x) + p(a.x, b.w) + p(a.y, b.z) - p(a.z, b.y);
+        let y = p(a.w, b.y) - p(a.x, b.z) + p(a.y, b.w) + p(a.z, b.x);
+        let z = p(a.w, b.z) + p(a.x, b.y) - p(a.y, b.x) + p(a.z, b.w);
+        Quat::new(
+            (w >> 12) as i32,
+            (x >> 12) as i32,
+            (y >> 12) as i32,
+            (z >> 12) as i32,
+        )
+    }
+}
+
+impl MulAssign<Quat> for Quat {
+    fn mul_assign(&mut self, other: Quat) {
+        *self = *self * other;
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+    fn neg(self) -> Quat {
+        Quat::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quat;
+    use crate::math::tests::{cos_f64, near, rad_f64, sin_f64};
+    use crate::math::{Mat3, Rad, Vec3, FRAC_PI_2, PI};
+
+    fn close(a: i32, b: i32) -> bool {
+        (a - b).abs() <= 24
+    }
+
+    fn close_quat(a: Quat, b: Quat) -> bool {
+        close(a.w, b.w) && close(a.x, b.x) && close(a.y, b.y) && close(a.z, b.z)
+    }
+
+    #[test_case]
+    fn rotation() {
+        let q = Quat::from_axis_angle(Vec3::Z, FRAC_PI_2);
+        // cos(π/4) and sin(π/4) are 0.7071 or 2896 in 20.12
+        assert!(close_quat(q, Quat::new(2896, 0, 0, 2896)));
+        let v = q.rotate(Vec3::X);
+        assert!(close(v.x, 0) && close(v.y, 4096) && close(v.z, 0));
+        let m = Quat::from_axis_angle(Vec3::Z, PI).to_mat3();
+        assert!(m == Mat3::rotation_z(PI));
+        let qq = q * q;
+        assert!(close_quat(qq, Quat::new(0, 0, 0, 4096)));
+        assert!(close_quat(q * q.conjugate(), Quat::IDENTITY));
+    }
+
+    #[test_case]
+    fn slerp() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        assert!(close_quat(a.slerp(b, 0), a));
+        assert!(close_quat(a.slerp(b, 4096), b));
+        // Halfway is a rotation by π/4 so w = cos(π/8) = 0.9239 and y = sin(π/8)
+        // = 0.3827
+        let mid = a.slerp(b, 2048);
+        assert!(close_quat(mid, Quat::new(3784, 0, 1567, 0)));
+        // A quarter of the way is π/8 so w = cos(π/16) = 0.9808 and y =
+        // sin(π/16) = 0.1951
+        let quarter = a.slerp(b, 1024);
+        assert!(close_quat(quarter, Quat::new(4017, 0, 799, 0)));
+        // The shortest path is taken for negated quaternions
+        assert!(close_quat(a.slerp(-b, 2048), mid));
+    }
+
+    #[test_case]
+    fn reference() {
+        for angle in (0..=u16::MAX).step_by(0x0345).map(Rad) {
+            let half = rad_f64(angle) / 2.0;
+            let (s, c) = (sin_f64(half), cos_f64(half));
+            // Halving odd angles rounds them down
+            let q = Quat::from_axis_angle(Vec3::Y, angle);
+            assert!(near(q.w, c, 2.0) && near(q.y, s, 2.0) && q.x == 0 && q.z == 0);
+            // Rotating (1, 2, 3) about the y axis
+            let (s2, c2) = (sin_f64(2.0 * half), cos_f64(2.0 * half));
+            let v = q.rotate(Vec3::new(4096, 8192, 12288));
+            assert!(near(v.x, c2 + 3.0 * s2, 16.0) && near(v.y, 2.0, 16.0));
+            assert!(near(v.z, 3.0 * c2 - s2, 16.0));
+            // Interpolating from the identity is a rotation by a fraction of
+            // the angle along the shorter path
+            let half = if c < 0.0 {
+                half - core::f64::consts::PI
+            } else {
+                half
+            };
+            for t in (0..=4096).step_by(512) {
+                let q = Quat::IDENTITY.slerp(q, t);
+                let half = half * t as f64 / 4096.0;
+                let (s, c) = (sin_f64(half), cos_f64(half));
+                assert!(near(q.w, c, 8.0) && near(q.y, s, 8.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Rigid transforms combining a rotation and a translation
+//!
+//! These map directly onto the GTE's rotation matrix and translation vector
+//! registers and are composed on the CPU.
+
+use crate::math::{Mat3, Rad, Vec3};
+use core::ops::{Mul, MulAssign};
+
+/// A rotation and translation applied to points as `rotation * p +
+/// translation`.
+///
+/// The fields are in the same format as the GTE's rotation matrix and
+/// translation vector.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Transform {
+    /// The rotation matrix.
+    pub rotation: Mat3,
+    /// The translation vector.
+    pub translation: Vec3,
+}
+
+impl Transform {
+    /// The transform which leaves points unchanged.
+    pub const IDENTITY: Self = Transform::new(Mat3::IDENTITY, Vec3::ZERO);
+
+    /// Creates a transform which rotates then translates points.
+    pub const fn new(rotation: Mat3, translation: Vec3) -> Self {
+        Transform {
+            rotation,
+            translation,
+        }
+    }
+
+    /// A rotation by `theta` radians about the x axis.
+    pub fn rotation_x(theta: Rad) -> Self {
+        Transform::new(Mat3::rotation_x(theta), Vec3::ZERO)
+    }
+
+    /// A rotation by `theta` radians about the y axis.
+    pub fn rotation_y(theta: Rad) -> Self {
+        Transform::new(Mat3::rotation_y(theta), Vec3::ZERO)
+    }
+
+    /// A rotation by `theta` radians about the z axis.
+    pub fn rotation_z(theta: Rad) -> Self {
+        Transform::new(Mat3::rotation_z(theta), Vec3::ZERO)
+    }
+
+    /// A translation by `(x, y, z)`.
+    pub const fn translation(x: i32, y: i32, z: i32) -> Self {
+        Transform::new(Mat3::IDENTITY, Vec3::new(x, y, z))
+    }
+
+    /// A view transform for a camera at `eye` looking at `target`.
+    ///
+    /// The view looks down its Z axis with Y pointing down on screen, which is
+    /// the orientation the GTE projects. `up` is the world direction which
+    /// should appear up on screen and must not be parallel to the view
+    /// direction.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let forward = (target - eye).normalize();
+        let right = forward.cross(up).normalize();
+        let down = forward.cross(right);
+        let rotation = Mat3::from_rows([right, down, forward]);
+        Transform::new(rotation, -(rotation * eye))
+    }
+
+    /// Applies the transform to a point.
+    pub fn apply(&self, p: Vec3) -> Vec3 {
+        self.rotation * p + self.translation
+    }
+
+    /// The transform which undoes this one. This assumes the matrix is a
+    /// rotation.
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.transpose();
+        Transform::new(rotation, -(rotation * self.translation))
+    }
+}
+
+/// Composes two transforms so that `a * b` applies `b` first, then `a`.
+impl Mul<Transform> for Transform {
+    type Output = Transform;
+    fn mul(self, other: Transform) -> Transform {
+        Transform::new(
+            self.rotation * other.rotation,
+            self.apply(other.translation),
+        )
+    }
+}
+
+impl MulAssign<Transform> for Transform {
+    fn mul_assign(&mut self, other: Transform) {
+        *self = *self * other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transform;
+    use crate::math::tests::{cos_f64, near, rad_f64, sin_f64};
+    use crate::math::{Rad, Vec3, FRAC_PI_2};
+
+    #[test_case]
+    fn compose() {
+        let t = Transform::translation(1, 2, 3) * Transform::rotation_z(FRAC_PI_2);
+        assert!(t.apply(Vec3::X) == Vec3::new(1, 4096 + 2, 3));
+        let t = Transform::rotation_z(FRAC_PI_2) * Transform::translation(1, 2, 3);
+        assert!(t.apply(Vec3::ZERO) == Vec3::new(-2, 1, 3));
+        assert!(Transform::IDENTITY * t == t);
+        assert!((t * t.inverse()).apply(Vec3::new(5, 6, 7)) == Vec3::new(5, 6, 7));
+    }
+
+    #[test_case]
+    fn look_at() {
+        // A camera on the -X axis looking at the origin with -Y up
+        let eye = Vec3::new(-1000, 0, 0);
+        let view = Transform::look_at(eye, Vec3::ZERO, -Vec3::Y);
+        assert!(view.apply(eye) == Vec3::ZERO);
+        assert!(view.apply(Vec3::ZERO) == Vec3::new(0, 0, 1000));
+        // Points at +Z are on the camera's left and points at -Y are up
+        assert!(view.apply(Vec3::new(0, 0, 100)) == Vec3::new(-100, 0, 1000));
+        assert!(view.apply(Vec3::new(0, -100, 0)) == Vec3::new(0, -100, 1000));
+    }
+
+    #[test_case]
+    fn reference() {
+        for theta in (0..=u16::MAX).step_by(0x0345).map(Rad) {
+            let t = Transform::translation(100, -200, 300) *
+                Transform::rotation_y(theta) *
+                Transform::rotation_x(theta);
+            let (s, c) = (sin_f64(rad_f64(theta)), cos_f64(rad_f64(theta)));
+            // (1, 2, 3) rotated about the x axis, then the y axis
+            let (y, z) = (2.0 * c - 3.0 * s, 2.0 * s + 3.0 * c);
+            let (x, z) = (c + s * z, c * z - s);
+            let p = t.apply(Vec3::new(4096, 8192, 12288));
+            assert!(near(p.x - 100, x, 8.0) && near(p.y + 200, y, 8.0));
+            assert!(near(p.z - 300, z, 8.0));
+            // Both products round down
+            let p = t.inverse().apply(p);
+            assert!(near(p.x, 1.0, 16.0) && near(p.y, 2.0, 16.0) && near(p.z, 3.0, 16.0));
+        }
+    }
+}
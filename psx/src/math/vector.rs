@@ -0,0 +1,284 @@
+//! 3D and 4D vectors with 32-bit components
+//!
+//! Lengths are computed with the integer square root [`isqrt`] and products use
+//! 64-bit intermediates so 20.12 components don't overflow.
+
+use crate::math::{f16, isqrt, ONE};
+use core::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+/// A 3D vector with 32-bit components.
+///
+/// Multiplying by a [`Mat3`][crate::math::Mat3] keeps the vector's scale so
+/// integer coordinates may be used directly. Methods which multiply two
+/// vectors treat the components as 20.12 fixed-point, so unit vectors have a
+/// length of [`ONE`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Vec3 {
+    /// The X component.
+    pub x: i32,
+    /// The Y component.
+    pub y: i32,
+    /// The Z component.
+    pub z: i32,
+}
+
+/// A 4D vector with 32-bit components.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Vec4 {
+    /// The X component.
+    pub x: i32,
+    /// The Y component.
+    pub y: i32,
+    /// The Z component.
+    pub z: i32,
+    /// The W component.
+    pub w: i32,
+}
+
+// Multiplies two components without overflowing.
+fn wide_mul(a: i32, b: i32) -> i64 {
+    a as i64 * b as i64
+}
+
+impl Vec3 {
+    /// The zero vector.
+    pub const ZERO: Self = Vec3::new(0, 0, 0);
+    /// The unit vector along the X axis.
+    pub const X: Self = Vec3::new(ONE as i32, 0, 0);
+    /// The unit vector along the Y axis.
+    pub const Y: Self = Vec3::new(0, ONE as i32, 0);
+    /// The unit vector along the Z axis.
+    pub const Z: Self = Vec3::new(0, 0, ONE as i32);
+
+    /// Creates a new vector.
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    /// Converts `f16` coordinates to 20.12 fixed-point.
+    pub const fn from_f16([x, y, z]: [f16; 3]) -> Self {
+        Vec3::new((x.0 as i32) << 4, (y.0 as i32) << 4, (z.0 as i32) << 4)
+    }
+
+    /// Computes the dot product in 20.12 fixed-point.
+    pub fn dot(self, other: Self) -> i32 {
+        let sum = wide_mul(self.x, other.x) + wide_mul(self.y, other.y) + wide_mul(self.z, other.z);
+        (sum >> 12) as i32
+    }
+
+    /// Computes the cross product in 20.12 fixed-point.
+    pub fn cross(self, other: Self) -> Self {
+        let x = wide_mul(self.y, other.z) - wide_mul(self.z, other.y);
+        let y = wide_mul(self.z, other.x) - wide_mul(self.x, other.z);
+        let z = wide_mul(self.x, other.y) - wide_mul(self.y, other.x);
+        Vec3::new((x >> 12) as i32, (y >> 12) as i32, (z >> 12) as i32)
+    }
+
+    /// Computes the length with the same scale as the components.
+    pub fn length(self) -> u32 {
+        let sq = |c: i32| (c as i64 * c as i64) as u64;
+        isqrt(sq(self.x) + sq(self.y) + sq(self.z))
+    }
+
+    /// Scales the vector to a length of [`ONE`]. The zero vector is returned
+    /// unchanged.
+    pub fn normalize(self) -> Self {
+        let len = self.length() as i64;
+        if len == 0 {
+            return self
+        }
+        let scale = |c: i32| (((c as i64) << 12) / len) as i32;
+        Vec3::new(scale(self.x), scale(self.y), scale(self.z))
+    }
+
+    /// Scales the vector by a 20.12 fixed-point factor.
+    pub fn scale(self, factor: i32) -> Self {
+        let scale = |c: i32| (wide_mul(c, factor) >> 12) as i32;
+        Vec3::new(scale(self.x), scale(self.y), scale(self.z))
+    }
+}
+
+impl Vec4 {
+    /// The zero vector.
+    pub const ZERO: Self = Vec4::new(0, 0, 0, 0);
+
+    /// Creates a new vector.
+    pub const fn new(x: i32, y: i32, z: i32, w: i32) -> Self {
+        Vec4 { x, y, z, w }
+    }
+
+    /// Creates a vector from `v`'s components and `w`.
+    pub const fn extend(v: Vec3, w: i32) -> Self {
+        Vec4::new(v.x, v.y, v.z, w)
+    }
+
+    /// Gets the X, Y and Z components.
+    pub const fn xyz(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    /// Computes the dot product in 20.12 fixed-point.
+    pub fn dot(self, other: Self) -> i32 {
+        (self.xyz().dot(other.xyz()) as i64 + (wide_mul(self.w, other.w) >> 12)) as i32
+    }
+
+    /// Scales the vector by a 20.12 fixed-point factor.
+    pub fn scale(self, factor: i32) -> Self {
+        Vec4::extend(
+            self.xyz().scale(factor),
+            (wide_mul(self.w, factor) >> 12) as i32,
+        )
+    }
+}
+
+impl From<[i32; 3]> for Vec3 {
+    fn from([x, y, z]: [i32; 3]) -> Self {
+        Vec3::new(x, y, z)
+    }
+}
+
+impl From<Vec3> for [i32; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl From<[i32; 4]> for Vec4 {
+    fn from([x, y, z, w]: [i32; 4]) -> Self {
+        Vec4::new(x, y, z, w)
+    }
+}
+
+impl From<Vec4> for [i32; 4] {
+    fn from(v: Vec4) -> Self {
+        [v.x, v.y, v.z, v.w]
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Mul<i32> for Vec3 {
+    type Output = Self;
+    fn mul(self, other: i32) -> Self {
+        Vec3::new(self.x * other, self.y * other, self.z * other)
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Add for Vec4 {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Vec4::extend(self.xyz() + other.xyz(), self.w + other.w)
+    }
+}
+
+impl Sub for Vec4 {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Vec4::extend(self.xyz() - other.xyz(), self.w - other.w)
+    }
+}
+
+impl Neg for Vec4 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Vec4::extend(-self.xyz(), -self.w)
+    }
+}
+
+impl Mul<i32> for Vec4 {
+    type Output = Self;
+    fn mul(self, other: i32) -> Self {
+        Vec4::extend(self.xyz() * other, self.w * other)
+    }
+}
+
+impl AddAssign for Vec4 {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Vec4 {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Vec3;
+    use crate::math::isqrt;
+    use crate::math::tests::{near, sqrt_f64};
+
+    #[test_case]
+    fn products() {
+        let a = Vec3::new(4096, 8192, -4096);
+        let b = Vec3::new(2048, 0, 4096);
+        // 1 * 0.5 + 2 * 0 + -1 * 1 = -0.5
+        assert!(a.dot(b) == -2048);
+        assert!(Vec3::X.cross(Vec3::Y) == Vec3::Z);
+        assert!(Vec3::Y.cross(Vec3::X) == -Vec3::Z);
+        assert!(a.cross(b).dot(a) == 0);
+    }
+
+    #[test_case]
+    fn normalize() {
+        assert!(isqrt(0) == 0 && isqrt(15) == 3 && isqrt(16) == 4);
+        assert!(Vec3::new(3, 0, 4).length() == 5);
+        let n = Vec3::new(300, 0, -400).normalize();
+        // (0.6, 0, -0.8)
+        assert!(n == Vec3::new(2457, 0, -3276));
+        assert!(Vec3::ZERO.normalize() == Vec3::ZERO);
+    }
+
+    #[test_case]
+    fn reference() {
+        let vecs = [
+            (4096, 8192, 12288),
+            (-40960, 1000, 70),
+            (12345, -23456, 34567),
+        ];
+        for (x, y, z) in vecs {
+            let v = Vec3::new(x, y, z);
+            let (x, y, z) = (x as f64, y as f64, z as f64);
+            let len = sqrt_f64(x * x + y * y + z * z);
+            // The length is rounded down
+            let diff = len - v.length() as f64;
+            assert!((0.0..1.0).contains(&diff));
+            let n = v.normalize();
+            assert!(near(n.x, x / len, 2.0) && near(n.y, y / len, 2.0));
+            assert!(near(n.z, z / len, 2.0));
+        }
+    }
+}
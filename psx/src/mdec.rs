@@ -0,0 +1,248 @@
+//! Macroblock decoder (MDEC) operations
+//!
+//! The MDEC decodes run-length encoded macroblocks into 15-bit or 24-bit RGB
+//! pixels (or 4-bit and 8-bit monochrome pixels). [`Mdec`] sends the encoded
+//! stream through [`dma::MDECIn`] while receiving the decoded pixels through
+//! [`dma::MDECOut`].
+
+use crate::dma;
+use crate::hw::mdec::{Command, Control, Status};
+use crate::hw::Register;
+
+pub use crate::hw::mdec::Depth;
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// The maximum size of an encoded stream in words.
+pub const MAX_INPUT_WORDS: usize = 0xFFFF;
+
+// The largest DMA block size for the MDEC in words.
+const DMA_BLOCK: usize = 32;
+
+/// The order coefficients are stored in run-length encoded blocks and
+/// quantization tables. `ZIGZAG[i]` is the row-major index of the `i`th
+/// coefficient.
+pub const ZIGZAG: [u8; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// The MPEG-1 default intra quantization matrix in row-major order with the DC
+// entry replaced by 2 as in the official SDK.
+const QUANT_MATRIX: [u8; 64] = [
+    2, 16, 19, 22, 26, 27, 29, 34, 16, 16, 22, 24, 27, 29, 34, 37, 19, 22, 26, 27, 29, 34, 34, 38,
+    22, 22, 26, 27, 29, 34, 37, 40, 22, 26, 27, 29, 32, 35, 40, 48, 26, 27, 29, 32, 35, 40, 48, 58,
+    26, 27, 29, 34, 38, 46, 56, 69, 27, 29, 35, 38, 46, 56, 69, 83,
+];
+
+/// The standard quantization table in zigzag order which is used for both
+/// luminance and color.
+pub const DEFAULT_QUANT_TABLE: [u8; 64] = {
+    let mut table = [0; 64];
+    let mut i = 0;
+    while i < 64 {
+        table[i] = QUANT_MATRIX[ZIGZAG[i] as usize];
+        i += 1;
+    }
+    table
+};
+
+/// The standard IDCT scale table. Each entry is `c(k) * cos((2n + 1) * k * pi
+/// / 16)` in 1.15 fixed-point where `k` is the row, `n` is the column and `c(0)
+/// = 1 / sqrt(2)`.
+#[rustfmt::skip]
+pub const DEFAULT_SCALE_TABLE: [u16; 64] = [
+    0x5A82, 0x5A82, 0x5A82, 0x5A82, 0x5A82, 0x5A82, 0x5A82, 0x5A82,
+    0x7D8A, 0x6A6D, 0x471C, 0x18F8, 0xE707, 0xB8E3, 0x9592, 0x8275,
+    0x7641, 0x30FB, 0xCF04, 0x89BE, 0x89BE, 0xCF04, 0x30FB, 0x7641,
+    0x6A6D, 0xE707, 0x8275, 0xB8E3, 0x471C, 0x7D8A, 0x18F8, 0x9592,
+    0x5A82, 0xA57D, 0xA57D, 0x5A82, 0x5A82, 0xA57D, 0xA57D, 0x5A82,
+    0x471C, 0x8275, 0x18F8, 0x6A6D, 0x9592, 0xE707, 0x7D8A, 0xB8E3,
+    0x30FB, 0x89BE, 0x7641, 0xCF04, 0xCF04, 0x7641, 0x89BE, 0x30FB,
+    0x18F8, 0xB8E3, 0x6A6D, 0x8275, 0x7D8A, 0x9592, 0x471C, 0xE707,
+];
+
+/// An MDEC error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The encoded stream is longer than [`MAX_INPUT_WORDS`].
+    InputTooLarge,
+    /// The output buffer isn't a whole number of macroblocks.
+    BadOutputSize,
+    /// The data couldn't be transferred by DMA.
+    DMA(dma::Error),
+}
+
+// The largest block size up to `DMA_BLOCK` words that `len` can be split into.
+fn dma_blocks(len: usize) -> usize {
+    let mut words = DMA_BLOCK;
+    while len % words != 0 {
+        words /= 2;
+    }
+    len / words
+}
+
+/// A handle to the MDEC and its DMA channels.
+pub struct Mdec {
+    command: Command,
+    status: Status,
+    dma_in: dma::MDECIn,
+    dma_out: dma::MDECOut,
+    signed: bool,
+    bit15: bool,
+}
+
+impl Mdec {
+    /// Resets the MDEC and uploads the standard quantization and IDCT scale
+    /// tables.
+    pub fn new() -> Self {
+        Control::skip_load().reset().store();
+        Control::skip_load().enable_dma(true, true).store();
+        let mut mdec = Mdec {
+            command: Command::skip_load(),
+            status: Status::skip_load(),
+            dma_in: dma::MDECIn::new(),
+            dma_out: dma::MDECOut::new(),
+            signed: false,
+            bit15: false,
+        };
+        mdec.set_quant_tables(&DEFAULT_QUANT_TABLE, &DEFAULT_QUANT_TABLE);
+        mdec.set_scale_table(&DEFAULT_SCALE_TABLE);
+        mdec
+    }
+
+    // Writes parameters to the command register without DMA.
+    fn send_params<I: Iterator<Item = u32>>(&mut self, params: I) {
+        for param in params {
+            while self.status.load().data_in_full() {}
+            self.command.set_param(param).store();
+        }
+    }
+
+    /// Sets the luminance and color quantization tables in zigzag order.
+    pub fn set_quant_tables(&mut self, luminance: &[u8; 64], color: &[u8; 64]) -> &mut Self {
+        self.status.wait();
+        self.command.set_quant_table(true).store();
+        let words = luminance
+            .chunks(4)
+            .chain(color.chunks(4))
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        self.send_params(words);
+        self
+    }
+
+    /// Sets the IDCT scale table in row-major order.
+    pub fn set_scale_table(&mut self, table: &[u16; 64]) -> &mut Self {
+        self.status.wait();
+        self.command.set_scale_table().store();
+        let words = table
+            .chunks(2)
+            .map(|halves| halves[0] as u32 | (halves[1] as u32) << 16);
+        self.send_params(words);
+        self
+    }
+
+    /// Outputs signed monochrome pixels or signed RGB components.
+    pub fn set_signed(&mut self, signed: bool) -> &mut Self {
+        self.signed = signed;
+        self
+    }
+
+    /// Sets bit 15 of each 15-bit pixel to make them semi-transparent or mask
+    /// them.
+    pub fn set_bit15(&mut self, bit15: bool) -> &mut Self {
+        self.bit15 = bit15;
+        self
+    }
+
+    /// Decodes a run-length encoded macroblock stream into `output`, which
+    /// must be a whole number of macroblocks in size. The stream must not
+    /// include the decode command header some encoders add to it.
+    pub fn decode(&mut self, input: &[u32], output: &mut [u32], depth: Depth) -> Result<()> {
+        if input.len() > MAX_INPUT_WORDS {
+            return Err(Error::InputTooLarge)
+        }
+        if output.len() % depth.macroblock_words() != 0 {
+            return Err(Error::BadOutputSize)
+        }
+        self.status.wait();
+        self.command
+            .decode(depth, self.signed, self.bit15, input.len() as u16)
+            .store();
+        let in_blocks = dma_blocks(input.len());
+        let out_blocks = dma_blocks(output.len());
+        let dma_in = &mut self.dma_in;
+        // The output must be read while the input is sent since the MDEC stops
+        // taking input when its output FIFO is full
+        self.dma_out
+            .receive_blocks_and(output, out_blocks, || {
+                dma_in
+                    .setup_send_blocks()
+                    .send_blocks_and(input, in_blocks, || ())
+            })
+            .and_then(|res| res)
+            .map_err(Error::DMA)?;
+        self.status.wait();
+        Ok(())
+    }
+
+    /// Decodes a run-length encoded macroblock stream in `slices` slices of
+    /// `slice.len()` words, calling `f` with the index and contents of each
+    /// slice as it's decoded. The stream must decode to exactly `slices`
+    /// slices or the transfer won't complete.
+    pub fn decode_slices<F: FnMut(usize, &[u32])>(
+        &mut self, input: &[u32], slice: &mut [u32], slices: usize, depth: Depth, mut f: F,
+    ) -> Result<()> {
+        if input.len() > MAX_INPUT_WORDS {
+            return Err(Error::InputTooLarge)
+        }
+        if slice.len() % depth.macroblock_words() != 0 {
+            return Err(Error::BadOutputSize)
+        }
+        self.status.wait();
+        self.command
+            .decode(depth, self.signed, self.bit15, input.len() as u16)
+            .store();
+        let in_blocks = dma_blocks(input.len());
+        let out_blocks = dma_blocks(slice.len());
+        let dma_out = &mut self.dma_out;
+        self.dma_in
+            .setup_send_blocks()
+            .send_blocks_and(input, in_blocks, || {
+                for i in 0..slices {
+                    dma_out.receive_blocks_and(slice, out_blocks, || ())?;
+                    f(i, slice);
+                }
+                Ok(())
+            })
+            .and_then(|res| res)
+            .map_err(Error::DMA)?;
+        self.status.wait();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dma_blocks, DEFAULT_QUANT_TABLE, ZIGZAG};
+
+    #[test_case]
+    fn zigzag() {
+        let mut seen = [false; 64];
+        for &i in &ZIGZAG {
+            seen[i as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert!(DEFAULT_QUANT_TABLE[..4] == [2, 16, 16, 19]);
+        assert!(DEFAULT_QUANT_TABLE[63] == 83);
+    }
+
+    #[test_case]
+    fn blocks() {
+        assert!(dma_blocks(64) == 2);
+        assert!(dma_blocks(48) == 3);
+        assert!(dma_blocks(7) == 7);
+        assert!(dma_blocks(0) == 0);
+    }
+}
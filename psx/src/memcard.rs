@@ -0,0 +1,281 @@
+//! Direct memory card access
+//!
+//! This module reads and writes memory card frames through the controller
+//! serial port without the BIOS card handler, so `psx_init_card` isn't needed
+//! and the BIOS card events aren't involved. It shares the serial port with
+//! controllers so [`Card`] borrows a [`Pad`] for its transfers. The BIOS card
+//! handler must not be running at the same time since it also uses the port.
+//!
+//! A card is an array of [`FRAMES`] frames of [`FRAME_SIZE`] bytes. Each
+//! transfer includes a checksum of the frame number and data which both the
+//! card and this driver verify. Cards need some time to finish writing a
+//! frame, so a card may not respond to a command sent immediately after a
+//! write. Waiting for the next vblank is usually enough.
+//!
+//! [`CardSlot`] implements [`FrameIo`] so the card's files can be accessed
+//! with [`format::memcard`][crate::format::memcard].
+
+use crate::format::memcard::FrameIo;
+use crate::pad::{Pad, Port};
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// The address byte which selects the memory card rather than the controller
+/// on a port.
+pub const MEMORY_CARD: u8 = 0x81;
+/// The number of bytes in a frame.
+pub const FRAME_SIZE: usize = 128;
+/// The number of frames on a card.
+pub const FRAMES: u16 = 1024;
+
+const READ: u8 = b'R';
+const WRITE: u8 = b'W';
+// The card IDs sent after the command byte.
+const ID: [u8; 2] = [0x5A, 0x5D];
+// The command acknowledgment sent before the data is read or after it's
+// written.
+const COMMAND_ACK: [u8; 2] = [0x5C, 0x5D];
+// The status bytes ending each command.
+const GOOD: u8 = b'G';
+const BAD_CHECKSUM: u8 = b'N';
+const BAD_FRAME: u8 = 0xFF;
+
+// The offset of the ID after the address byte and flags.
+const ID_OFFSET: usize = 2;
+// The number of bytes before the data when reading a frame.
+const READ_HEADER: usize = 10;
+// The number of bytes before the data when writing a frame.
+const WRITE_HEADER: usize = 6;
+// The response lengths including the address byte.
+const READ_LEN: usize = READ_HEADER + FRAME_SIZE + 2;
+const WRITE_LEN: usize = WRITE_HEADER + FRAME_SIZE + 4;
+
+const NEW_CARD: u8 = 3;
+const WRITE_ERROR: u8 = 2;
+
+/// A memory card error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// No card responded on the port.
+    NoCard,
+    /// The card's response was malformed.
+    BadResponse,
+    /// The data's checksum didn't match.
+    BadChecksum,
+    /// The frame number is out of range.
+    BadFrame,
+}
+
+/// The flags byte a card sends at the start of each command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u8);
+
+impl Flags {
+    /// Checks if the card was inserted or the console was powered on since the
+    /// card was last written to. Games use this to tell when a card may have
+    /// been swapped and its directory should be read again.
+    pub fn new_card(&self) -> bool {
+        self.0 & (1 << NEW_CARD) != 0
+    }
+
+    /// Checks if the previous write failed.
+    pub fn write_error(&self) -> bool {
+        self.0 & (1 << WRITE_ERROR) != 0
+    }
+
+    /// Gets the raw flags byte.
+    pub fn to_bits(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Computes the checksum sent with a frame's data.
+pub fn checksum(frame: u16, data: &[u8]) -> u8 {
+    let [msb, lsb] = frame.to_be_bytes();
+    data.iter().fold(msb ^ lsb, |sum, byte| sum ^ byte)
+}
+
+// Checks the bytes every command starts with and returns the flags.
+fn parse_flags(response: &[u8]) -> Result<Flags> {
+    match response {
+        [_, flags, id @ ..] if id.starts_with(&ID) => Ok(Flags(*flags)),
+        // A port without a card doesn't acknowledge the address byte
+        [] | [_] => Err(Error::NoCard),
+        _ => Err(Error::BadResponse),
+    }
+}
+
+fn parse_end(status: u8) -> Result<()> {
+    match status {
+        GOOD => Ok(()),
+        BAD_CHECKSUM => Err(Error::BadChecksum),
+        BAD_FRAME => Err(Error::BadFrame),
+        _ => Err(Error::BadResponse),
+    }
+}
+
+fn check_frame(frame: u16) -> Result<()> {
+    if frame >= FRAMES {
+        return Err(Error::BadFrame)
+    }
+    Ok(())
+}
+
+// Parses the response to a read command for `frame`, copying the data into
+// `buf`.
+fn parse_read(response: &[u8], frame: u16, buf: &mut [u8; FRAME_SIZE]) -> Result<Flags> {
+    let flags = parse_flags(response)?;
+    if response.len() != READ_LEN {
+        return Err(Error::BadResponse)
+    }
+    let (header, rest) = response.split_at(READ_HEADER);
+    let (data, end) = rest.split_at(FRAME_SIZE);
+    if header[6..8] != COMMAND_ACK {
+        return Err(Error::BadResponse)
+    }
+    // The card echoes the frame number or 0xFFFF if it's out of range
+    let confirmed = u16::from_be_bytes([header[8], header[9]]);
+    if confirmed != frame {
+        return Err(Error::BadFrame)
+    }
+    if end[0] != checksum(frame, data) {
+        return Err(Error::BadChecksum)
+    }
+    parse_end(end[1])?;
+    buf.copy_from_slice(data);
+    Ok(flags)
+}
+
+// Parses the response to a write command.
+fn parse_write(response: &[u8]) -> Result<Flags> {
+    let flags = parse_flags(response)?;
+    match response.get(WRITE_LEN - 3..) {
+        Some([ack0, ack1, end]) if [*ack0, *ack1] == COMMAND_ACK => parse_end(*end)?,
+        _ => return Err(Error::BadResponse),
+    }
+    Ok(flags)
+}
+
+/// A handle to the memory cards which borrows the controller serial port.
+pub struct Card<'a> {
+    pad: &'a mut Pad,
+}
+
+impl<'a> Card<'a> {
+    /// Creates a memory card handle using `pad`'s serial port.
+    pub fn new(pad: &'a mut Pad) -> Self {
+        Card { pad }
+    }
+
+    /// Checks if a card is inserted in `port` and gets its flags.
+    pub fn status(&mut self, port: Port) -> Result<Flags> {
+        // Start a read command and stop after the card's ID
+        let mut response = [0; ID_OFFSET + ID.len()];
+        let len = self.pad.exchange(port, &[MEMORY_CARD, READ], &mut response);
+        parse_flags(&response[..len])
+    }
+
+    /// Reads `frame` from the card in `port` into `buf`.
+    pub fn read(&mut self, port: Port, frame: u16, buf: &mut [u8; FRAME_SIZE]) -> Result<Flags> {
+        check_frame(frame)?;
+        let [msb, lsb] = frame.to_be_bytes();
+        let tx = [MEMORY_CARD, READ, 0, 0, msb, lsb];
+        let mut response = [0; READ_LEN];
+        let len = self.pad.exchange(port, &tx, &mut response);
+        parse_read(&response[..len], frame, buf)
+    }
+
+    /// Writes `data` to `frame` on the card in `port`. This clears the card's
+    /// [new card flag][Flags::new_card].
+    pub fn write(&mut self, port: Port, frame: u16, data: &[u8; FRAME_SIZE]) -> Result<Flags> {
+        check_frame(frame)?;
+        let [msb, lsb] = frame.to_be_bytes();
+        let mut tx = [0; WRITE_LEN];
+        tx[..WRITE_HEADER].copy_from_slice(&[MEMORY_CARD, WRITE, 0, 0, msb, lsb]);
+        tx[WRITE_HEADER..][..FRAME_SIZE].copy_from_slice(data);
+        tx[WRITE_HEADER + FRAME_SIZE] = checksum(frame, data);
+        let mut response = [0; WRITE_LEN];
+        let len = self.pad.exchange(port, &tx, &mut response);
+        parse_write(&response[..len])
+    }
+
+    /// Gets a handle to the card in `port` which implements [`FrameIo`].
+    pub fn slot(self, port: Port) -> CardSlot<'a> {
+        CardSlot { card: self, port }
+    }
+}
+
+/// A handle to the memory card in one port.
+pub struct CardSlot<'a> {
+    card: Card<'a>,
+    port: Port,
+}
+
+impl<'a> CardSlot<'a> {
+    /// Returns the memory card handle.
+    pub fn into_inner(self) -> Card<'a> {
+        self.card
+    }
+}
+
+impl FrameIo for CardSlot<'_> {
+    type Error = Error;
+
+    /// Reads a frame with [`Card::read`].
+    fn read_frame(&mut self, frame: u16, buf: &mut [u8; FRAME_SIZE]) -> Result<()> {
+        self.card.read(self.port, frame, buf).map(|_| ())
+    }
+
+    /// Writes a frame with [`Card::write`].
+    fn write_frame(&mut self, frame: u16, data: &[u8; FRAME_SIZE]) -> Result<()> {
+        self.card.write(self.port, frame, data).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, parse_read, parse_write, Error, FRAME_SIZE, READ_LEN, WRITE_LEN};
+
+    fn read_response(frame: u16, data: &[u8; FRAME_SIZE]) -> [u8; READ_LEN] {
+        let [msb, lsb] = frame.to_be_bytes();
+        let mut response = [0; READ_LEN];
+        response[..10].copy_from_slice(&[0xFF, 0x08, 0x5A, 0x5D, 0x00, msb, 0x5C, 0x5D, msb, lsb]);
+        response[10..][..FRAME_SIZE].copy_from_slice(data);
+        response[READ_LEN - 2] = checksum(frame, data);
+        response[READ_LEN - 1] = b'G';
+        response
+    }
+
+    #[test_case]
+    fn read() {
+        let mut data = [0; FRAME_SIZE];
+        data[..2].copy_from_slice(b"MC");
+        let mut response = read_response(0x0102, &data);
+        let mut buf = [0xFF; FRAME_SIZE];
+        let flags = parse_read(&response, 0x0102, &mut buf).unwrap();
+        assert!(flags.new_card() && !flags.write_error());
+        assert!(buf == data);
+        assert!(parse_read(&response, 0x0103, &mut buf) == Err(Error::BadFrame));
+        response[20] ^= 1;
+        assert!(parse_read(&response, 0x0102, &mut buf) == Err(Error::BadChecksum));
+        assert!(parse_read(&response[..1], 0x0102, &mut buf) == Err(Error::NoCard));
+    }
+
+    #[test_case]
+    fn write() {
+        let mut response = [0; WRITE_LEN];
+        response[..4].copy_from_slice(&[0xFF, 0x00, 0x5A, 0x5D]);
+        response[WRITE_LEN - 3..].copy_from_slice(&[0x5C, 0x5D, b'G']);
+        assert!(parse_write(&response).unwrap().to_bits() == 0);
+        response[WRITE_LEN - 1] = b'N';
+        assert!(parse_write(&response) == Err(Error::BadChecksum));
+        response[WRITE_LEN - 1] = 0xFF;
+        assert!(parse_write(&response) == Err(Error::BadFrame));
+    }
+
+    #[test_case]
+    fn checksums() {
+        assert!(checksum(0x0000, &[0; FRAME_SIZE]) == 0);
+        assert!(checksum(0x03FF, &[0x01, 0x10]) == 0x03 ^ 0xFF ^ 0x11);
+    }
+}
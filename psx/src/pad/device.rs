@@ -0,0 +1,233 @@
+use super::{Error, Result};
+use crate::sys::gamepad::Button;
+
+/// The maximum number of data bytes in a controller's response.
+pub const MAX_DATA: usize = 32;
+/// The maximum number of bytes in a controller's response including the bytes
+/// before the data.
+pub const MAX_RESPONSE: usize = HEADER_LEN + MAX_DATA;
+
+// The byte controllers send after their ID.
+const READY: u8 = 0x5A;
+// The number of bytes before the data in a response.
+const HEADER_LEN: usize = 3;
+
+/// The type of device connected to a port as reported by its ID byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    /// A mouse.
+    Mouse,
+    /// A NeGcon.
+    NeGcon,
+    /// A Konami Justifier or Hyperblaster lightgun.
+    KonamiLightgun,
+    /// A digital pad or a DualShock in digital mode.
+    Digital,
+    /// An analog joystick or a DualShock in analog joystick mode.
+    AnalogJoystick,
+    /// A Namco GunCon lightgun.
+    GunCon,
+    /// A DualShock or dual analog pad in analog mode.
+    Analog,
+    /// A multitap.
+    Multitap,
+    /// A Namco Jogcon.
+    Jogcon,
+    /// A DualShock in configuration mode.
+    Config,
+    /// An unrecognized device with the given ID byte.
+    Unknown(u8),
+}
+
+impl Device {
+    /// Gets the device type from the high nibble of an ID byte.
+    pub const fn from_id(id: u8) -> Self {
+        match id >> 4 {
+            0x1 => Device::Mouse,
+            0x2 => Device::NeGcon,
+            0x3 => Device::KonamiLightgun,
+            0x4 => Device::Digital,
+            0x5 => Device::AnalogJoystick,
+            0x6 => Device::GunCon,
+            0x7 => Device::Analog,
+            0x8 => Device::Multitap,
+            0xE => Device::Jogcon,
+            0xF => Device::Config,
+            _ => Device::Unknown(id),
+        }
+    }
+}
+
+/// The buttons reported by a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Buttons(u16);
+
+impl Buttons {
+    /// Creates buttons from a mask of pressed buttons.
+    pub const fn new(pressed: u16) -> Self {
+        Buttons(pressed)
+    }
+
+    /// Checks if `button` is pressed.
+    pub fn pressed(&self, button: Button) -> bool {
+        self.0 & (1 << button as u16) != 0
+    }
+
+    /// Checks if `button` is released.
+    pub fn released(&self, button: Button) -> bool {
+        !self.pressed(button)
+    }
+
+    /// Gets a mask of the pressed buttons indexed by [`Button`].
+    pub const fn to_bits(&self) -> u16 {
+        self.0
+    }
+}
+
+/// The position of an analog stick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stick {
+    /// The horizontal position from 0 (left) to 0xFF (right).
+    pub x: u8,
+    /// The vertical position from 0 (up) to 0xFF (down).
+    pub y: u8,
+}
+
+impl Stick {
+    /// The stick's horizontal offset from the center.
+    pub fn horizontal(&self) -> i8 {
+        self.x.wrapping_sub(0x80) as i8
+    }
+
+    /// The stick's vertical offset from the center.
+    pub fn vertical(&self) -> i8 {
+        self.y.wrapping_sub(0x80) as i8
+    }
+}
+
+/// A controller's response to a read command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct State {
+    id: u8,
+    len: u8,
+    data: [u8; MAX_DATA],
+}
+
+impl State {
+    /// Parses a controller's response to a read command including the byte
+    /// received while sending the address.
+    pub fn parse(response: &[u8]) -> Result<Self> {
+        match response {
+            [_, reply @ ..] => Self::parse_reply(reply),
+            [] => Err(Error::NoDevice),
+        }
+    }
+
+    // Parses a response starting from the ID byte.
+    pub(super) fn parse_reply(reply: &[u8]) -> Result<Self> {
+        let (id, ready, data) = match reply {
+            [id, ready, data @ ..] => (*id, *ready, data),
+            _ => return Err(Error::NoDevice),
+        };
+        if ready != READY {
+            return Err(Error::BadResponse)
+        }
+        // The low nibble is the number of halfwords with 0 meaning 16
+        let len = match id & 0xF {
+            0 => MAX_DATA,
+            halfwords => halfwords as usize * 2,
+        };
+        let data = data.get(..len).ok_or(Error::BadResponse)?;
+        let mut state = State {
+            id,
+            len: len as u8,
+            data: [0; MAX_DATA],
+        };
+        state.data[..len].copy_from_slice(data);
+        Ok(state)
+    }
+
+    /// The device's ID byte.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// The type of device which sent the response.
+    pub fn device(&self) -> Device {
+        Device::from_id(self.id)
+    }
+
+    /// The response's data bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+
+    /// The pressed buttons. Buttons the device doesn't have are never pressed.
+    pub fn buttons(&self) -> Buttons {
+        // Buttons are active low
+        let bits = u16::from_le_bytes([self.data[0], self.data[1]]);
+        match self.device() {
+            Device::Mouse | Device::Multitap | Device::Unknown(_) => Buttons(0),
+            _ => Buttons(!bits),
+        }
+    }
+
+    // Gets a stick from the data bytes at `offset` for devices in analog mode.
+    fn stick(&self, offset: usize) -> Option<Stick> {
+        match self.device() {
+            Device::Analog | Device::AnalogJoystick => Some(Stick {
+                x: self.data[offset],
+                y: self.data[offset + 1],
+            }),
+            _ => None,
+        }
+    }
+
+    /// The right stick's position if the device is in analog mode.
+    pub fn right_stick(&self) -> Option<Stick> {
+        self.stick(2)
+    }
+
+    /// The left stick's position if the device is in analog mode.
+    pub fn left_stick(&self) -> Option<Stick> {
+        self.stick(4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Device, State};
+    use crate::pad::Error;
+    use crate::sys::gamepad::Button;
+
+    #[test_case]
+    fn digital() {
+        let state = State::parse(&[0xFF, 0x41, 0x5A, 0xFE, 0x7F]).unwrap();
+        assert!(state.device() == Device::Digital);
+        assert!(state.data() == [0xFE, 0x7F]);
+        assert!(state.buttons().pressed(Button::Select));
+        assert!(state.buttons().pressed(Button::Square));
+        assert!(state.buttons().released(Button::Start));
+        assert!(state.right_stick().is_none());
+    }
+
+    #[test_case]
+    fn analog() {
+        let response = [0xFF, 0x73, 0x5A, 0xFF, 0xFF, 0x80, 0x80, 0x00, 0xFF];
+        let state = State::parse(&response).unwrap();
+        assert!(state.device() == Device::Analog);
+        assert!(state.buttons().to_bits() == 0);
+        let right = state.right_stick().unwrap();
+        assert!(right.horizontal() == 0 && right.vertical() == 0);
+        let left = state.left_stick().unwrap();
+        assert!(left.horizontal() == -128 && left.vertical() == 127);
+    }
+
+    #[test_case]
+    fn errors() {
+        assert!(State::parse(&[0xFF]) == Err(Error::NoDevice));
+        assert!(State::parse(&[0xFF, 0x41, 0x00, 0xFF, 0xFF]) == Err(Error::BadResponse));
+        assert!(State::parse(&[0xFF, 0x73, 0x5A, 0xFF, 0xFF]) == Err(Error::BadResponse));
+        assert!(Device::from_id(0x00) == Device::Unknown(0));
+    }
+}
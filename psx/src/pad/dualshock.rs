@@ -0,0 +1,117 @@
+use super::{Device, Error, Pad, Port, Result, State, CONTROLLER, MAX_RESPONSE};
+
+// Configuration commands which are only available on DualShocks and dual
+// analog pads.
+const CONFIG_MODE: u8 = 0x43;
+const SET_ANALOG: u8 = 0x44;
+const MAP_MOTORS: u8 = 0x4D;
+
+const LOCKED: u8 = 3;
+const UNLOCKED: u8 = 2;
+// Motor mapping values for the bytes after a read command.
+const SMALL_MOTOR: u8 = 0x00;
+const LARGE_MOTOR: u8 = 0x01;
+const UNMAPPED: u8 = 0xFF;
+// The number of parameter bytes after each configuration command.
+const CONFIG_PARAMS: usize = 6;
+
+/// The speeds of a DualShock's two vibration motors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rumble {
+    /// Turns the small motor on.
+    pub small: bool,
+    /// The large motor's speed. Speeds below about 0x40 may not start it.
+    pub large: u8,
+}
+
+impl Rumble {
+    /// Both motors turned off.
+    pub const OFF: Self = Rumble {
+        small: false,
+        large: 0,
+    };
+
+    // The bytes sent after a read command with the default motor mapping.
+    pub(super) fn to_bytes(self) -> [u8; 2] {
+        [if self.small { 0xFF } else { 0x00 }, self.large]
+    }
+}
+
+impl Pad {
+    // Sends a configuration command and its parameters.
+    fn config_command(
+        &mut self, port: Port, cmd: u8, params: [u8; CONFIG_PARAMS],
+    ) -> Result<State> {
+        let mut tx = [0; 3 + CONFIG_PARAMS];
+        tx[..3].copy_from_slice(&[CONTROLLER, cmd, 0]);
+        tx[3..].copy_from_slice(&params);
+        let mut response = [0; MAX_RESPONSE];
+        let len = self.exchange(port, &tx, &mut response);
+        State::parse(&response[..len])
+    }
+
+    // Sends a command which is only accepted in configuration mode.
+    fn config_only(&mut self, port: Port, cmd: u8, params: [u8; CONFIG_PARAMS]) -> Result<()> {
+        let state = self.config_command(port, cmd, params)?;
+        if state.device() != Device::Config {
+            return Err(Error::Unsupported)
+        }
+        Ok(())
+    }
+
+    /// Enters or exits a DualShock's configuration mode. The controller
+    /// reports itself as [`Device::Config`] while in configuration mode.
+    pub fn set_config_mode(&mut self, port: Port, enabled: bool) -> Result<()> {
+        self.config_command(port, CONFIG_MODE, [enabled as u8, 0, 0, 0, 0, 0])
+            .map(|_| ())
+    }
+
+    /// Switches between analog and digital mode. If `locked` is set, the
+    /// analog button can't change the mode. This must be sent in configuration
+    /// mode.
+    pub fn set_analog(&mut self, port: Port, analog: bool, locked: bool) -> Result<()> {
+        let lock = if locked { LOCKED } else { UNLOCKED };
+        self.config_only(port, SET_ANALOG, [analog as u8, lock, 0, 0, 0, 0])
+    }
+
+    /// Maps the small and large motors to the first two bytes sent after each
+    /// read command, enabling vibration. This must be sent in configuration
+    /// mode.
+    pub fn map_motors(&mut self, port: Port) -> Result<()> {
+        let mapping = [
+            SMALL_MOTOR,
+            LARGE_MOTOR,
+            UNMAPPED,
+            UNMAPPED,
+            UNMAPPED,
+            UNMAPPED,
+        ];
+        self.config_only(port, MAP_MOTORS, mapping)
+    }
+
+    /// Runs the configuration command sequence which sets the analog mode and
+    /// enables vibration if `rumble` is set. Returns [`Error::Unsupported`] if
+    /// the controller doesn't have a configuration mode.
+    pub fn configure_dualshock(
+        &mut self, port: Port, analog: bool, locked: bool, rumble: bool,
+    ) -> Result<()> {
+        self.set_config_mode(port, true)?;
+        let res = self.set_analog(port, analog, locked).and_then(|()| {
+            if rumble {
+                self.map_motors(port)
+            } else {
+                Ok(())
+            }
+        });
+        // Always try to leave configuration mode so the controller still works
+        self.set_config_mode(port, false)?;
+        res
+    }
+
+    /// Sets the motor speeds sent to the controller on `port` each time it's
+    /// polled. The motors must have been mapped with [`Pad::map_motors`].
+    pub fn set_rumble(&mut self, port: Port, rumble: Rumble) -> &mut Self {
+        self.rumble[port as usize] = rumble;
+        self
+    }
+}
@@ -0,0 +1,170 @@
+//! Controller driver
+//!
+//! This module talks to controllers directly through the SIO0 registers in
+//! [`hw::sio0`][crate::hw::sio0] rather than through the BIOS pad handler.
+//! Each poll selects a port, sends the controller address and a command, then
+//! exchanges bytes for as long as the controller acknowledges them. Ports are
+//! only polled when [`Pad::poll`] is called so the timing is up to the caller.
+//!
+//! DualShocks have a configuration mode which can lock them in analog mode and
+//! enable their vibration motors with [`Pad::configure_dualshock`]. The motor
+//! speeds set by [`Pad::set_rumble`] are then sent each time the controller is
+//! polled.
+//!
+//! [`State::reading`] interprets a response according to the device type,
+//! giving typed readings for mice, NeGcons, GunCons and multitaps.
+//! [`Pad::poll_players`] expands multitaps into up to eight controllers.
+
+use crate::hw::sio0::{Baud, BaudFactor, Control, Data, Mode, Status};
+use crate::hw::Register;
+
+mod device;
+mod dualshock;
+mod reading;
+
+pub use crate::hw::sio0::Port;
+pub use crate::sys::gamepad::Button;
+pub use device::{Buttons, Device, State, Stick, MAX_DATA, MAX_RESPONSE};
+pub use dualshock::Rumble;
+pub use reading::{GunCon, Mouse, NeGcon, Reading, MULTITAP_SLOTS};
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// The address byte which selects the controller rather than the memory card
+/// on a port.
+pub const CONTROLLER: u8 = 0x01;
+
+// The command which reads a controller's buttons and axes.
+const READ: u8 = 0x42;
+// Asks a multitap to return all of its slots. Other controllers ignore it.
+const MULTITAP_READ: u8 = 0x01;
+// The baud rate reload value for the standard 250 kHz clock.
+const BAUD_250KHZ: u16 = 0x88;
+// The number of status register loads to wait after selecting a port.
+const SELECT_DELAY: u32 = 100;
+// The number of status register loads to wait for `/ACK` before assuming the
+// device won't send any more bytes.
+const ACK_TIMEOUT: u32 = 1000;
+
+/// A controller driver error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// No device responded on the port.
+    NoDevice,
+    /// The device's response was malformed or shorter than its ID says.
+    BadResponse,
+    /// The device doesn't support the command.
+    Unsupported,
+}
+
+/// A handle to the controller serial port.
+pub struct Pad {
+    data: Data,
+    status: Status,
+    control: Control,
+    rumble: [Rumble; 2],
+}
+
+impl Pad {
+    /// Resets the serial port and sets it up for controllers.
+    pub fn new() -> Self {
+        let mut control = Control::skip_load();
+        control.reset().store();
+        let mut mode = Mode::skip_load();
+        // 8 bits is always a valid character length
+        mode.set_char_length(8);
+        mode.set_baud_factor(BaudFactor::Mul1).store();
+        Baud::skip_load().set_reload(BAUD_250KHZ).store();
+        control.clear_all().store();
+        Pad {
+            data: Data::skip_load(),
+            status: Status::skip_load(),
+            control,
+            rumble: [Rumble::OFF; 2],
+        }
+    }
+
+    fn delay(&mut self, loads: u32) {
+        for _ in 0..loads {
+            self.status.load();
+        }
+    }
+
+    // Sends a byte and returns the byte received in exchange.
+    fn transfer(&mut self, byte: u8) -> u8 {
+        while !self.status.load().tx_ready() {}
+        self.data.set_byte(byte).store();
+        while !self.status.load().rx_not_empty() {}
+        self.data.load().get_byte()
+    }
+
+    // Waits for the device to acknowledge the last byte. Returns false if it
+    // timed out.
+    fn wait_ack(&mut self) -> bool {
+        for _ in 0..ACK_TIMEOUT {
+            if self.status.load().irq_requested() {
+                self.control.ack(true).store().ack(false);
+                return true
+            }
+        }
+        false
+    }
+
+    /// Selects `port` and exchanges bytes until `rx` is full or the device
+    /// stops acknowledging them. The bytes in `tx` are sent first followed by
+    /// zeros. Returns the number of bytes received.
+    pub fn exchange(&mut self, port: Port, tx: &[u8], rx: &mut [u8]) -> usize {
+        self.control
+            .clear_all()
+            .enable_tx(true)
+            .enable_ack_irq(true)
+            .ack(true)
+            .select(Some(port))
+            .store()
+            .ack(false);
+        self.delay(SELECT_DELAY);
+        let len = rx.len();
+        let mut received = 0;
+        for (i, byte) in rx.iter_mut().enumerate() {
+            *byte = self.transfer(tx.get(i).copied().unwrap_or(0));
+            received += 1;
+            // Devices don't acknowledge the last byte they expect
+            if i + 1 == len || !self.wait_ack() {
+                break
+            }
+        }
+        self.control.select(None).store();
+        received
+    }
+
+    /// Reads the state of the controller on `port`.
+    pub fn poll(&mut self, port: Port) -> Result<State> {
+        let mut response = [0; MAX_RESPONSE];
+        let [small, large] = self.rumble[port as usize].to_bytes();
+        let tx = [CONTROLLER, READ, MULTITAP_READ, small, large];
+        let len = self.exchange(port, &tx, &mut response);
+        State::parse(&response[..len])
+    }
+
+    /// Reads the state of the controllers on both ports.
+    pub fn poll_all(&mut self) -> [Result<State>; 2] {
+        [self.poll(Port::Port1), self.poll(Port::Port2)]
+    }
+
+    /// Reads the state of up to eight controllers. Controllers on a multitap in
+    /// port 1 are in the first four entries and those on a multitap in port 2
+    /// are in the last four. A controller connected directly to a port is in
+    /// the first entry for that port.
+    pub fn poll_players(&mut self) -> [Option<State>; 2 * MULTITAP_SLOTS] {
+        let mut players = [None; 2 * MULTITAP_SLOTS];
+        for (port, res) in self.poll_all().into_iter().enumerate() {
+            let slots = &mut players[port * MULTITAP_SLOTS..][..MULTITAP_SLOTS];
+            match res.map(|state| (state, state.reading())) {
+                Ok((_, Reading::Multitap(tap))) => slots.copy_from_slice(&tap),
+                Ok((state, _)) => slots[0] = Some(state),
+                Err(_) => (),
+            }
+        }
+        players
+    }
+}
@@ -0,0 +1,206 @@
+use super::{Button, Buttons, Device, State, Stick};
+
+/// The number of controller slots on a multitap.
+pub const MULTITAP_SLOTS: usize = 4;
+
+// The size of each slot in a multitap's response including the ID and ready
+// bytes.
+const SLOT_LEN: usize = 8;
+// Mouse buttons in the response's button halfword.
+const MOUSE_RIGHT: u16 = 10;
+const MOUSE_LEFT: u16 = 11;
+// The GunCon reports these coordinates when it doesn't see the screen.
+const GUNCON_NO_LIGHT: (u16, u16) = (0x0001, 0x000A);
+
+/// A PlayStation Mouse's movement since it was last polled and its buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mouse {
+    /// The horizontal movement with positive values to the right.
+    pub dx: i8,
+    /// The vertical movement with positive values downwards.
+    pub dy: i8,
+    /// The left button is pressed.
+    pub left: bool,
+    /// The right button is pressed.
+    pub right: bool,
+}
+
+/// A NeGcon's analog controls.
+///
+/// Its digital buttons are reported as [`Button::Start`], the D-pad,
+/// [`Button::R1`] for R, [`Button::Triangle`] for B and [`Button::Circle`] for
+/// A.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeGcon {
+    /// The digital buttons.
+    pub buttons: Buttons,
+    /// The twist from 0 (fully counterclockwise) to 0xFF (fully clockwise).
+    pub twist: u8,
+    /// How far the I button is pressed.
+    pub i: u8,
+    /// How far the II button is pressed.
+    pub ii: u8,
+    /// How far the L button is pressed.
+    pub l: u8,
+}
+
+impl NeGcon {
+    /// The twist's offset from the center.
+    pub fn twist_offset(&self) -> i8 {
+        self.twist.wrapping_sub(0x80) as i8
+    }
+}
+
+/// A GunCon's buttons and the position of the beam it last saw.
+///
+/// The position is latched by the GunCon itself when it sees the beam. `x` is
+/// the number of 8 MHz clock cycles since the start of the scanline's hsync and
+/// `y` is the scanline, so converting them to screen coordinates depends on
+/// the display's horizontal range and video mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GunCon {
+    /// The trigger is pressed.
+    pub trigger: bool,
+    /// The A button on the side is pressed.
+    pub a: bool,
+    /// The B button on the side is pressed.
+    pub b: bool,
+    /// The beam's horizontal position in 8 MHz clock cycles.
+    pub x: u16,
+    /// The beam's vertical position in scanlines.
+    pub y: u16,
+}
+
+impl GunCon {
+    /// Checks if the GunCon saw the beam, i.e. if it's pointed at the screen.
+    pub fn on_screen(&self) -> bool {
+        (self.x, self.y) != GUNCON_NO_LIGHT
+    }
+}
+
+/// A controller's state interpreted for its device type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reading {
+    /// A digital pad's buttons.
+    Digital(Buttons),
+    /// An analog pad or joystick's buttons and sticks.
+    Analog {
+        /// The buttons.
+        buttons: Buttons,
+        /// The left stick.
+        left: Stick,
+        /// The right stick.
+        right: Stick,
+    },
+    /// A mouse.
+    Mouse(Mouse),
+    /// A NeGcon.
+    NeGcon(NeGcon),
+    /// A GunCon.
+    GunCon(GunCon),
+    /// The controllers on a multitap's slots or `None` for empty slots.
+    Multitap([Option<State>; MULTITAP_SLOTS]),
+    /// A device without a typed reading. Its raw data is still available from
+    /// [`State::data`].
+    Other,
+}
+
+impl State {
+    /// Interprets the state according to the device type.
+    pub fn reading(&self) -> Reading {
+        let data = self.data();
+        match self.device() {
+            Device::Digital => Reading::Digital(self.buttons()),
+            // The sticks are always available for these devices
+            Device::Analog | Device::AnalogJoystick => Reading::Analog {
+                buttons: self.buttons(),
+                left: self.left_stick().unwrap(),
+                right: self.right_stick().unwrap(),
+            },
+            Device::Mouse => {
+                let pressed = !u16::from_le_bytes([data[0], data[1]]);
+                Reading::Mouse(Mouse {
+                    dx: data[2] as i8,
+                    dy: data[3] as i8,
+                    left: pressed & (1 << MOUSE_LEFT) != 0,
+                    right: pressed & (1 << MOUSE_RIGHT) != 0,
+                })
+            },
+            Device::NeGcon => Reading::NeGcon(NeGcon {
+                buttons: self.buttons(),
+                twist: data[2],
+                i: data[3],
+                ii: data[4],
+                l: data[5],
+            }),
+            Device::GunCon => {
+                let buttons = self.buttons();
+                Reading::GunCon(GunCon {
+                    trigger: buttons.pressed(Button::Circle),
+                    a: buttons.pressed(Button::Start),
+                    b: buttons.pressed(Button::Cross),
+                    x: u16::from_le_bytes([data[2], data[3]]),
+                    y: u16::from_le_bytes([data[4], data[5]]),
+                })
+            },
+            Device::Multitap => {
+                let mut slots = [None; MULTITAP_SLOTS];
+                for (slot, reply) in slots.iter_mut().zip(data.chunks(SLOT_LEN)) {
+                    *slot = State::parse_reply(reply).ok();
+                }
+                Reading::Multitap(slots)
+            },
+            _ => Reading::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GunCon, Mouse, Reading};
+    use crate::pad::{Button, Device, State};
+
+    #[test_case]
+    fn mouse() {
+        let state = State::parse(&[0xFF, 0x12, 0x5A, 0xFF, 0xF7, 0x05, 0xFE]).unwrap();
+        let mouse = Mouse {
+            dx: 5,
+            dy: -2,
+            left: true,
+            right: false,
+        };
+        assert!(state.reading() == Reading::Mouse(mouse));
+    }
+
+    #[test_case]
+    fn guncon() {
+        let response = [0xFF, 0x63, 0x5A, 0xFF, 0xDF, 0x00, 0x01, 0x20, 0x00];
+        let gun = match State::parse(&response).unwrap().reading() {
+            Reading::GunCon(gun) => gun,
+            _ => panic!(),
+        };
+        assert!(gun.trigger && !gun.a && !gun.b);
+        assert!(gun.x == 0x100 && gun.y == 0x20);
+        assert!(gun.on_screen());
+        let off = GunCon { x: 1, y: 10, ..gun };
+        assert!(!off.on_screen());
+    }
+
+    #[test_case]
+    fn multitap() {
+        let mut response = [0xFF; 35];
+        response[1..3].copy_from_slice(&[0x80, 0x5A]);
+        // A digital pad in the first slot and an analog pad in the third
+        response[3..8].copy_from_slice(&[0x41, 0x5A, 0xEF, 0xFF, 0x00]);
+        response[19..23].copy_from_slice(&[0x73, 0x5A, 0xFF, 0xFF]);
+        let slots = match State::parse(&response).unwrap().reading() {
+            Reading::Multitap(slots) => slots,
+            _ => panic!(),
+        };
+        let first = slots[0].unwrap();
+        assert!(first.device() == Device::Digital);
+        assert!(first.buttons().pressed(Button::Up));
+        assert!(slots[1].is_none() && slots[3].is_none());
+        assert!(slots[2].unwrap().device() == Device::Analog);
+    }
+}
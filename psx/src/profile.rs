@@ -0,0 +1,317 @@
+//! Code region profiling with a root counter
+//!
+//! [`Profiler`] times named regions of code with root counter 2 and keeps the
+//! minimum, maximum and average time spent in each region per frame. Regions
+//! are timed with either a scoped [`Guard`] or a closure and each region may
+//! be entered multiple times per frame.
+//!
+//! Root counter 2 is 16 bits so the profiler adds up how far it advanced each
+//! time it's read by entering or leaving a region or calling
+//! [`Profiler::now`]. This is only exact if the counter didn't wrap around more
+//! than once between reads. The counter's target is set half way so its target
+//! and overflow flags are both set when that may have happened, and regions
+//! spanning such a gap are counted in [`Stats::dropped`] instead of being
+//! timed. Reading the counter at least every 0.97 ms at the system clock or
+//! every 7.7 ms with [`Profiler::new_div8`] avoids dropping regions.
+
+use crate::timer::{Source, Timer2, SYSTEM_CLOCK};
+use crate::{dprintln, println, TextBox};
+
+/// The maximum number of regions a profiler can track.
+pub const MAX_REGIONS: usize = 16;
+
+/// The timing statistics for a region in clock cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// The region's name.
+    pub name: &'static str,
+    /// The shortest time spent in the region in a frame.
+    pub min: u32,
+    /// The longest time spent in the region in a frame.
+    pub max: u32,
+    /// The total time spent in the region in all frames.
+    pub total: u64,
+    /// The number of frames the region was entered in.
+    pub frames: u32,
+    /// The number of times the region was too long to time.
+    pub dropped: u32,
+    // The time spent in the region in the current frame
+    current: u32,
+    entered: bool,
+}
+
+impl Stats {
+    const fn new(name: &'static str) -> Self {
+        Stats {
+            name,
+            min: u32::MAX,
+            max: 0,
+            total: 0,
+            frames: 0,
+            dropped: 0,
+            current: 0,
+            entered: false,
+        }
+    }
+
+    fn add(&mut self, cycles: u32) {
+        self.current = self.current.saturating_add(cycles);
+        self.entered = true;
+    }
+
+    fn end_frame(&mut self) {
+        if !self.entered {
+            return
+        }
+        self.min = self.min.min(self.current);
+        self.max = self.max.max(self.current);
+        self.total += self.current as u64;
+        self.frames += 1;
+        self.current = 0;
+        self.entered = false;
+    }
+
+    /// The average time spent in the region per frame.
+    pub fn average(&self) -> u32 {
+        if self.frames == 0 {
+            0
+        } else {
+            (self.total / self.frames as u64) as u32
+        }
+    }
+}
+
+// The counter's target, which sets the target flag half way to an overflow.
+const HALF: u16 = 0x8000;
+
+/// Times named code regions using root counter 2.
+pub struct Profiler {
+    timer: Timer2,
+    divider: u32,
+    // The counter's value when it was last read
+    last: u16,
+    // The time in clock cycles when the counter was last read
+    time: u32,
+    // The number of times the counter may have wrapped around more than once
+    // between reads
+    gaps: u32,
+    regions: [Stats; MAX_REGIONS],
+    len: usize,
+}
+
+/// A scoped guard which adds the time until it's dropped to a region.
+pub struct Guard<'a> {
+    profiler: &'a mut Profiler,
+    region: Option<usize>,
+    start: u32,
+    gaps: u32,
+}
+
+impl<'a> Guard<'a> {
+    /// Starts timing a region nested inside this guard's region.
+    pub fn start(&mut self, name: &'static str) -> Guard<'_> {
+        self.profiler.start(name)
+    }
+}
+
+impl<'a> Drop for Guard<'a> {
+    fn drop(&mut self) {
+        let elapsed = self.profiler.now().wrapping_sub(self.start);
+        let dropped = self.profiler.gaps != self.gaps;
+        if let Some(region) = self.region {
+            let region = &mut self.profiler.regions[region];
+            if dropped {
+                region.dropped += 1;
+            } else {
+                region.add(elapsed);
+            }
+        }
+    }
+}
+
+impl Profiler {
+    /// Creates a profiler which counts system clock cycles. This restarts
+    /// root counter 2.
+    pub fn new() -> Self {
+        Self::with_source(Source::System, 1)
+    }
+
+    /// Creates a profiler which counts system clock cycles divided by 8. This
+    /// restarts root counter 2.
+    pub fn new_div8() -> Self {
+        Self::with_source(Source::SystemDiv8, 8)
+    }
+
+    fn with_source(source: Source, divider: u32) -> Self {
+        let mut timer = Timer2::new();
+        timer.set_target(HALF);
+        // Both sources are always available for timer 2
+        timer.start(source);
+        Profiler {
+            timer,
+            divider,
+            last: 0,
+            time: 0,
+            gaps: 0,
+            regions: [Stats::new(""); MAX_REGIONS],
+            len: 0,
+        }
+    }
+
+    /// Gets the time in clock cycles since the profiler was created, wrapping
+    /// around at `u32::MAX`. Whole overflows of the 16-bit root counter between
+    /// calls are missed, as described in the [module docs][self].
+    pub fn now(&mut self) -> u32 {
+        // Passing both half way and the overflow takes over half an overflow
+        // period, so when both flags are set the counter may have wrapped
+        // around more than once since the last read
+        if self.timer.reached() == (true, true) {
+            self.gaps = self.gaps.wrapping_add(1);
+        }
+        let now = self.timer.get();
+        self.time = self.time.wrapping_add(now.wrapping_sub(self.last) as u32);
+        self.last = now;
+        self.time
+    }
+
+    // Finds a region by name, adding it if there's space.
+    fn region(&mut self, name: &'static str) -> Option<usize> {
+        let idx = self.regions[..self.len]
+            .iter()
+            .position(|region| region.name == name);
+        if idx.is_some() || self.len == MAX_REGIONS {
+            return idx
+        }
+        self.regions[self.len] = Stats::new(name);
+        self.len += 1;
+        Some(self.len - 1)
+    }
+
+    /// Starts timing a region until the returned guard is dropped. Regions
+    /// past the first [`MAX_REGIONS`] are ignored.
+    pub fn start(&mut self, name: &'static str) -> Guard<'_> {
+        let region = self.region(name);
+        let start = self.now();
+        let gaps = self.gaps;
+        Guard {
+            profiler: self,
+            region,
+            start,
+            gaps,
+        }
+    }
+
+    /// Times a closure as a region and returns its return value.
+    pub fn time<F: FnOnce() -> R, R>(&mut self, name: &'static str, f: F) -> R {
+        let _guard = self.start(name);
+        f()
+    }
+
+    /// Adds a time measured in clock cycles to a region.
+    pub fn record(&mut self, name: &'static str, cycles: u32) {
+        if let Some(region) = self.region(name) {
+            self.regions[region].add(cycles);
+        }
+    }
+
+    /// Adds the times recorded since the last call to each region's
+    /// statistics. This should be called once per frame.
+    pub fn end_frame(&mut self) {
+        for region in &mut self.regions[..self.len] {
+            region.end_frame();
+        }
+    }
+
+    /// Removes all regions.
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    /// The statistics for each region in the order they were first entered.
+    pub fn regions(&self) -> &[Stats] {
+        &self.regions[..self.len]
+    }
+
+    /// Converts a time in clock cycles to microseconds.
+    pub fn to_micros(&self, cycles: u32) -> u32 {
+        (cycles as u64 * self.divider as u64 * 1_000_000 / SYSTEM_CLOCK as u64) as u32
+    }
+
+    /// Prints the minimum, average and maximum time spent in each region per
+    /// frame in microseconds and the number of times it was dropped to stdout.
+    pub fn print(&self) {
+        println!(
+            "{:<16} {:>7} {:>7} {:>7} {:>7}",
+            "region (us)", "min", "avg", "max", "dropped"
+        );
+        for region in self.regions() {
+            if region.frames == 0 && region.dropped == 0 {
+                continue
+            }
+            let min = if region.frames == 0 { 0 } else { region.min };
+            println!(
+                "{:<16} {:>7} {:>7} {:>7} {:>7}",
+                region.name,
+                self.to_micros(min),
+                self.to_micros(region.average()),
+                self.to_micros(region.max),
+                region.dropped
+            );
+        }
+    }
+
+    /// Draws the average and maximum time spent in each region per frame in
+    /// microseconds in a text box.
+    pub fn draw(&self, text: &mut TextBox) {
+        for region in self.regions() {
+            if region.frames == 0 {
+                continue
+            }
+            dprintln!(
+                text,
+                "{} {}/{}us",
+                region.name,
+                self.to_micros(region.average()),
+                self.to_micros(region.max)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Profiler, Stats};
+
+    #[test_case]
+    fn stats() {
+        let mut stats = Stats::new("test");
+        stats.end_frame();
+        assert!(stats.frames == 0);
+        assert!(stats.average() == 0);
+        stats.add(100);
+        stats.add(50);
+        stats.end_frame();
+        stats.add(50);
+        stats.end_frame();
+        assert!(stats.frames == 2);
+        assert!(stats.min == 50);
+        assert!(stats.max == 150);
+        assert!(stats.average() == 100);
+    }
+
+    #[test_case]
+    fn dropped() {
+        let mut profiler = Profiler::new();
+        profiler.time("short", || ());
+        // Spin for several overflow periods without reading the counter
+        profiler.time("long", || {
+            for i in 0..100_000 {
+                core::hint::black_box(i);
+            }
+        });
+        profiler.end_frame();
+        let regions = profiler.regions();
+        assert!(regions[0].frames == 1 && regions[0].dropped == 0);
+        assert!(regions[1].frames == 0 && regions[1].dropped == 1);
+    }
+}
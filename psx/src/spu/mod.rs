@@ -0,0 +1,377 @@
+//! Sound effect playback with automatic voice allocation
+//!
+//! [`Spu`] uploads ADPCM sample data to sound RAM through [`dma::SPU`],
+//! allocates space for it and plays it on whichever voice is free, stealing
+//! the voice which was started the longest time ago if all 24 are busy. The
+//! reverb unit is configured with a [`ReverbPreset`] whose work area is
+//! reserved at the end of sound RAM.
+
+use crate::dma;
+use crate::format::vag::VAG;
+use crate::hw::spu;
+use crate::hw::spu::voice::{Adsr, AdsrHigh, AdsrLow, AdsrVolume, Pitch, RepeatAddress,
+                            StartAddress, VolumeLeft, VolumeRight};
+use crate::hw::spu::{Address, TransferMode, VoiceFlags, Volume, VOICES};
+use crate::hw::Register;
+
+mod ram;
+mod reverb;
+mod sequencer;
+
+pub use ram::Allocator;
+pub use reverb::ReverbPreset;
+pub use sequencer::{Bank, Sequencer};
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// The maximum number of samples which can be in sound RAM at once.
+pub const MAX_SAMPLES: usize = 64;
+
+/// The maximum volume of a voice.
+pub const MAX_VOLUME: u16 = 0x3FFF;
+
+// The first 4 KB of sound RAM are used by the CD audio and voice 1/3 capture
+// buffers.
+const CAPTURE_END: u32 = 0x1000;
+// An ADPCM block which loops silently. Voices which aren't playing are pointed
+// here so they don't play garbage if they're keyed on by accident.
+const SILENCE: u32 = CAPTURE_END;
+const SILENCE_BLOCK: [u32; 4] = [0x0000_0700, 0, 0, 0];
+const RAM_START: u32 = SILENCE + 0x10;
+
+// DMA transfers to sound RAM are split into blocks of at most 16 words.
+const DMA_BLOCK: usize = 16;
+// The size of an ADPCM block in words.
+const ADPCM_BLOCK: usize = 4;
+
+/// An SPU error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// There isn't enough free sound RAM.
+    OutOfMemory,
+    /// The maximum number of allocations was reached.
+    TooManyAllocations,
+    /// An allocation of 0 bytes was requested.
+    ZeroSize,
+    /// The address doesn't match an allocation.
+    BadAddress,
+    /// The data isn't a multiple of the 16-byte ADPCM block size.
+    BadSampleSize,
+    /// The data couldn't be sent by DMA.
+    DMA(dma::Error),
+}
+
+/// ADPCM sample data in sound RAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// The address in sound RAM.
+    pub addr: u32,
+    /// The size in bytes.
+    pub size: u32,
+    /// The sample rate in Hz.
+    pub sample_rate: u32,
+}
+
+/// The settings used to key on a voice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoiceParams {
+    /// The address of the ADPCM data in sound RAM.
+    pub addr: u32,
+    /// The raw pitch where 0x1000 is 44.1 kHz.
+    pub pitch: u16,
+    /// The volume ranging from 0 to 0x3FFF.
+    pub volume: u16,
+    /// The pan ranging from -127 (left) to 127 (right).
+    pub pan: i8,
+    /// The envelope.
+    pub adsr: Adsr,
+}
+
+/// A handle to the SPU which allocates sound RAM and voices.
+pub struct Spu {
+    dma: dma::SPU,
+    control: spu::Control,
+    ram: Allocator<MAX_SAMPLES>,
+    // The order voices were keyed on in for voice stealing
+    started: [u32; VOICES],
+    counter: u32,
+    reserved: u32,
+    reverb: ReverbPreset,
+}
+
+fn voice_volume(volume: u16, pan: i8) -> (i16, i16) {
+    let volume = volume.min(MAX_VOLUME) as i32;
+    let pan = (pan as i32).max(-127);
+    let left = volume * (127 - pan.max(0)) / 127;
+    let right = volume * (127 + pan.min(0)) / 127;
+    // Fixed voice volumes are stored halved
+    ((left * 2) as i16, (right * 2) as i16)
+}
+
+impl Spu {
+    /// Initializes the SPU, silencing all voices and enabling the CD audio
+    /// input.
+    pub fn new() -> Self {
+        let mut control = spu::Control::skip_load();
+        control
+            .enable(true)
+            .mute(false)
+            .cd_audio(true)
+            .set_transfer_mode(TransferMode::Stop)
+            .store();
+        spu::KeyOff::skip_load().assign(!0 >> 8).store();
+        spu::TransferControl::skip_load().set_normal().store();
+        spu::CdVolumeLeft::skip_load().set_volume(0x7FFF).store();
+        spu::CdVolumeRight::skip_load().set_volume(0x7FFF).store();
+        spu::PitchModulation::skip_load().clear_all().store();
+        spu::NoiseMode::skip_load().clear_all().store();
+        spu::ReverbMode::skip_load().clear_all().store();
+        let mut spu = Spu {
+            dma: dma::SPU::new(),
+            control,
+            ram: Allocator::new(RAM_START, spu::RAM_SIZE),
+            started: [0; VOICES],
+            counter: 0,
+            reserved: 0,
+            reverb: ReverbPreset::Off,
+        };
+        // This can't fail since the block is 16 bytes
+        spu.write(SILENCE, &SILENCE_BLOCK).ok();
+        spu.set_main_volume(MAX_VOLUME, MAX_VOLUME);
+        for voice in 0..VOICES {
+            VolumeLeft::skip_load(voice).set_volume(0).store();
+            VolumeRight::skip_load(voice).set_volume(0).store();
+            Pitch::skip_load(voice).set_sample_rate(44_100).store();
+            StartAddress::skip_load(voice).set_address(SILENCE).store();
+            RepeatAddress::skip_load(voice).set_address(SILENCE).store();
+            AdsrLow::skip_load(voice).set_adsr(Adsr::new()).store();
+            AdsrHigh::skip_load(voice).set_adsr(Adsr::new()).store();
+        }
+        // Play the silent block so every voice's end flag is set, then release
+        // the voices so their envelopes drop to zero. Key off has to wait until
+        // the attack starts or the key on may be dropped.
+        let all = !0 >> 8;
+        spu::KeyOn::skip_load().assign(all).store();
+        while (0..VOICES).any(|voice| AdsrVolume::new(voice).get_volume() == 0) {}
+        spu::KeyOff::skip_load().assign(all).store();
+        while (0..VOICES).any(|voice| spu.is_playing(voice)) {}
+        spu
+    }
+
+    fn set_transfer_mode(&mut self, mode: TransferMode) {
+        self.control.set_transfer_mode(mode).store();
+        spu::Status::skip_load().wait_for_mode(mode);
+    }
+
+    /// Writes `data` to sound RAM at `addr` through DMA. The length of `data`
+    /// must be a multiple of 4 words.
+    pub fn write(&mut self, addr: u32, data: &[u32]) -> Result<()> {
+        if data.len() % ADPCM_BLOCK != 0 {
+            return Err(Error::BadSampleSize)
+        }
+        if data.is_empty() {
+            return Ok(())
+        }
+        let words = if data.len() % DMA_BLOCK == 0 {
+            DMA_BLOCK
+        } else {
+            ADPCM_BLOCK
+        };
+        self.set_transfer_mode(TransferMode::Stop);
+        spu::TransferAddress::skip_load().set_address(addr).store();
+        self.set_transfer_mode(TransferMode::DMAWrite);
+        let res = self
+            .dma
+            .setup_send_blocks()
+            .send_blocks_and(data, data.len() / words, || ())
+            .map_err(Error::DMA);
+        spu::Status::skip_load().wait();
+        self.set_transfer_mode(TransferMode::Stop);
+        res
+    }
+
+    /// Allocates sound RAM for ADPCM data and uploads it.
+    pub fn upload(&mut self, data: &[u32], sample_rate: u32) -> Result<Sample> {
+        let size = (data.len() * 4) as u32;
+        let addr = self.ram.alloc(size)?;
+        if let Err(err) = self.write(addr, data) {
+            self.ram.free(addr).ok();
+            return Err(err)
+        }
+        Ok(Sample {
+            addr,
+            size,
+            sample_rate,
+        })
+    }
+
+    /// Allocates sound RAM for a VAG file's ADPCM data and uploads it.
+    pub fn upload_vag(&mut self, vag: &VAG) -> Result<Sample> {
+        self.upload(vag.data, vag.sample_rate)
+    }
+
+    /// Frees the sound RAM used by a sample. Voices playing it should be
+    /// stopped first.
+    pub fn free(&mut self, sample: Sample) -> Result<()> {
+        self.ram.free(sample.addr)
+    }
+
+    /// The sound RAM allocator.
+    pub fn ram(&mut self) -> &mut Allocator<MAX_SAMPLES> {
+        &mut self.ram
+    }
+
+    /// Sets the main output volume ranging from 0 to 0x3FFF.
+    pub fn set_main_volume(&mut self, left: u16, right: u16) {
+        let left = left.min(MAX_VOLUME) as i16 * 2;
+        let right = right.min(MAX_VOLUME) as i16 * 2;
+        spu::MainVolumeLeft::skip_load().set_volume(left).store();
+        spu::MainVolumeRight::skip_load().set_volume(right).store();
+    }
+
+    /// Excludes the voices whose bits are set in `voices` from automatic
+    /// allocation so they can be driven directly.
+    pub fn reserve_voices(&mut self, voices: u32) {
+        self.reserved |= voices;
+    }
+
+    /// Returns the voices whose bits are set in `voices` to automatic
+    /// allocation.
+    pub fn unreserve_voices(&mut self, voices: u32) {
+        self.reserved &= !voices;
+    }
+
+    /// Checks if a voice is still playing. A voice is playing from when it's
+    /// keyed on until it reaches the end of its sample. Looping samples keep
+    /// playing until their envelope's volume reaches zero.
+    pub fn is_playing(&self, voice: usize) -> bool {
+        // The end flag is cleared by keying on and set by each ADPCM block
+        // with the end flag, which also mutes samples that don't loop
+        !spu::VoiceEnd::new().voice_set(voice) || AdsrVolume::new(voice).get_volume() != 0
+    }
+
+    /// Picks a free voice or the one that was started the longest time ago.
+    /// Reserved voices are never picked so this returns `None` if all voices
+    /// are reserved.
+    pub fn alloc_voice(&mut self) -> Option<usize> {
+        let mut oldest = None;
+        let reserved = self.reserved;
+        for voice in (0..VOICES).filter(|v| reserved & (1 << v) == 0) {
+            if !self.is_playing(voice) {
+                return Some(voice)
+            }
+            let age = self.counter.wrapping_sub(self.started[voice]);
+            match oldest {
+                Some((_, oldest_age)) if oldest_age >= age => {},
+                _ => oldest = Some((voice, age)),
+            }
+        }
+        oldest.map(|(voice, _)| voice)
+    }
+
+    /// Gets an ID for the sound last started on a voice. This changes each
+    /// time the voice is keyed on, so it can be used to check if a voice was
+    /// stolen.
+    pub fn voice_id(&self, voice: usize) -> u32 {
+        self.started[voice]
+    }
+
+    /// Plays a sample at its sample rate on an automatically allocated voice.
+    /// The volume ranges from 0 to 0x3FFF and the pan ranges from -127 (left)
+    /// to 127 (right). Returns the voice the sample is played on or `None` if
+    /// all voices are reserved.
+    pub fn play(&mut self, sample: &Sample, volume: u16, pan: i8) -> Option<usize> {
+        self.play_pitched(sample, sample.sample_rate, volume, pan)
+    }
+
+    /// Plays a sample at `sample_rate` on an automatically allocated voice.
+    /// Returns the voice the sample is played on or `None` if all voices are
+    /// reserved.
+    pub fn play_pitched(
+        &mut self, sample: &Sample, sample_rate: u32, volume: u16, pan: i8,
+    ) -> Option<usize> {
+        let voice = self.alloc_voice()?;
+        self.play_on(voice, sample, sample_rate, volume, pan, Adsr::new());
+        Some(voice)
+    }
+
+    /// Plays a sample on a specific voice with an envelope.
+    pub fn play_on(
+        &mut self, voice: usize, sample: &Sample, sample_rate: u32, volume: u16, pan: i8,
+        adsr: Adsr,
+    ) {
+        let params = VoiceParams {
+            addr: sample.addr,
+            pitch: Pitch::skip_load(voice)
+                .set_sample_rate(sample_rate)
+                .to_bits(),
+            volume,
+            pan,
+            adsr,
+        };
+        self.key_on(voice, &params);
+    }
+
+    /// Sets up a voice and keys it on. Returns the voice's new
+    /// [`ID`][Spu::voice_id].
+    pub fn key_on(&mut self, voice: usize, params: &VoiceParams) -> u32 {
+        spu::KeyOff::skip_load().set_voice(voice).store();
+        self.set_volume(voice, params.volume, params.pan);
+        Pitch::skip_load(voice).assign(params.pitch).store();
+        StartAddress::skip_load(voice)
+            .set_address(params.addr)
+            .store();
+        AdsrLow::skip_load(voice).set_adsr(params.adsr).store();
+        AdsrHigh::skip_load(voice).set_adsr(params.adsr).store();
+        self.counter = self.counter.wrapping_add(1);
+        self.started[voice] = self.counter;
+        spu::KeyOn::skip_load().set_voice(voice).store();
+        self.counter
+    }
+
+    /// Sets a voice's volume ranging from 0 to 0x3FFF and pan ranging from
+    /// -127 (left) to 127 (right).
+    pub fn set_volume(&mut self, voice: usize, volume: u16, pan: i8) {
+        let (left, right) = voice_volume(volume, pan);
+        VolumeLeft::skip_load(voice).set_volume(left).store();
+        VolumeRight::skip_load(voice).set_volume(right).store();
+    }
+
+    /// Changes the sample rate of a voice.
+    pub fn set_pitch(&mut self, voice: usize, sample_rate: u32) {
+        Pitch::skip_load(voice).set_sample_rate(sample_rate).store();
+    }
+
+    /// Changes the raw pitch of a voice where 0x1000 is 44.1 kHz.
+    pub fn set_raw_pitch(&mut self, voice: usize, pitch: u16) {
+        Pitch::skip_load(voice).assign(pitch).store();
+    }
+
+    /// Starts releasing a voice.
+    pub fn stop(&mut self, voice: usize) {
+        spu::KeyOff::skip_load().set_voice(voice).store();
+    }
+
+    /// Starts releasing all voices which aren't reserved.
+    pub fn stop_all(&mut self) {
+        spu::KeyOff::skip_load()
+            .assign(!self.reserved & (!0 >> 8))
+            .store();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spu;
+    use crate::hw::spu::VOICES;
+
+    #[test_case]
+    fn free_voices() {
+        // A fresh SPU has every voice released so none have to be stolen
+        let mut spu = Spu::new();
+        assert!((0..VOICES).all(|voice| !spu.is_playing(voice)));
+        assert!(spu.alloc_voice() == Some(0));
+        spu.reserve_voices(1);
+        assert!(spu.alloc_voice() == Some(1));
+    }
+}
@@ -0,0 +1,180 @@
+use super::{Error, Result};
+
+// Sound RAM addresses are stored in units of 8 bytes, but ADPCM blocks are 16
+// bytes so allocations are aligned to that.
+const ALIGN: u32 = 16;
+
+const fn align(x: u32) -> u32 {
+    (x + ALIGN - 1) & !(ALIGN - 1)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    addr: u32,
+    size: u32,
+}
+
+impl Block {
+    const fn end(&self) -> u32 {
+        self.addr + self.size
+    }
+}
+
+/// A first-fit allocator for sound RAM which tracks up to `N` allocations.
+#[derive(Debug)]
+pub struct Allocator<const N: usize> {
+    blocks: [Block; N],
+    len: usize,
+    start: u32,
+    end: u32,
+}
+
+impl<const N: usize> Allocator<N> {
+    /// Creates an allocator for the sound RAM between `start` and `end`.
+    pub const fn new(start: u32, end: u32) -> Self {
+        Allocator {
+            blocks: [Block { addr: 0, size: 0 }; N],
+            len: 0,
+            start: align(start),
+            end,
+        }
+    }
+
+    fn blocks(&self) -> &[Block] {
+        &self.blocks[..self.len]
+    }
+
+    /// Allocates `size` bytes and returns the address of the allocation. The
+    /// size is rounded up to a multiple of 16 bytes and must not be 0.
+    pub fn alloc(&mut self, size: u32) -> Result<u32> {
+        if size == 0 {
+            return Err(Error::ZeroSize)
+        }
+        if self.len == N {
+            return Err(Error::TooManyAllocations)
+        }
+        let size = align(size);
+        let mut addr = self.start;
+        let mut idx = self.len;
+        for (i, block) in self.blocks().iter().enumerate() {
+            if block.addr - addr >= size {
+                idx = i;
+                break
+            }
+            addr = block.end();
+        }
+        if idx == self.len && self.end.saturating_sub(addr) < size {
+            return Err(Error::OutOfMemory)
+        }
+        self.blocks.copy_within(idx..self.len, idx + 1);
+        self.blocks[idx] = Block { addr, size };
+        self.len += 1;
+        Ok(addr)
+    }
+
+    /// Frees the allocation starting at `addr`.
+    pub fn free(&mut self, addr: u32) -> Result<()> {
+        let idx = self
+            .blocks()
+            .iter()
+            .position(|block| block.addr == addr)
+            .ok_or(Error::BadAddress)?;
+        self.blocks.copy_within(idx + 1..self.len, idx);
+        self.len -= 1;
+        Ok(())
+    }
+
+    /// Frees all allocations.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Removes `size` bytes from the top of the allocator's range and returns
+    /// the address of the reserved area. This fails if an allocation is in
+    /// the way.
+    pub fn reserve_top(&mut self, size: u32) -> Result<u32> {
+        let size = align(size);
+        let top = self.blocks().last().map_or(self.start, Block::end);
+        if self.end.saturating_sub(top) < size {
+            return Err(Error::OutOfMemory)
+        }
+        self.end -= size;
+        Ok(self.end)
+    }
+
+    /// Returns `size` bytes previously reserved with
+    /// [`Allocator::reserve_top`] to the allocator.
+    pub fn unreserve_top(&mut self, size: u32) {
+        self.end += align(size);
+    }
+
+    /// The number of unallocated bytes.
+    pub fn available(&self) -> u32 {
+        let used: u32 = self.blocks().iter().map(|block| block.size).sum();
+        self.end - self.start - used
+    }
+
+    /// The largest allocation which would currently succeed.
+    pub fn largest_free(&self) -> u32 {
+        let mut addr = self.start;
+        let mut largest = 0;
+        for block in self.blocks() {
+            largest = largest.max(block.addr - addr);
+            addr = block.end();
+        }
+        largest.max(self.end.saturating_sub(addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Allocator;
+    use crate::spu::Error;
+
+    #[test_case]
+    fn first_fit() {
+        let mut ram = Allocator::<4>::new(0x1000, 0x2000);
+        let a = ram.alloc(0x100).unwrap();
+        let b = ram.alloc(0x10).unwrap();
+        let c = ram.alloc(0x1).unwrap();
+        assert!(a == 0x1000);
+        assert!(b == 0x1100);
+        assert!(c == 0x1110);
+        ram.free(b).unwrap();
+        assert!(ram.alloc(0x20).unwrap() == 0x1120);
+        assert!(ram.alloc(0x8).unwrap() == 0x1100);
+        assert!(ram.alloc(0x8) == Err(Error::TooManyAllocations));
+        assert!(ram.free(0x1234) == Err(Error::BadAddress));
+    }
+
+    #[test_case]
+    fn zero_size() {
+        let mut ram = Allocator::<4>::new(0x1000, 0x2000);
+        let a = ram.alloc(0x10).unwrap();
+        assert!(ram.alloc(0) == Err(Error::ZeroSize));
+        ram.free(a).unwrap();
+        assert!(ram.available() == 0x1000);
+    }
+
+    #[test_case]
+    fn out_of_memory() {
+        let mut ram = Allocator::<4>::new(0x1000, 0x2000);
+        assert!(ram.alloc(0x1001) == Err(Error::OutOfMemory));
+        ram.alloc(0x800).unwrap();
+        assert!(ram.available() == 0x800);
+        assert!(ram.largest_free() == 0x800);
+        assert!(ram.alloc(0x800).unwrap() == 0x1800);
+        assert!(ram.alloc(0x10) == Err(Error::OutOfMemory));
+    }
+
+    #[test_case]
+    fn reserve_top() {
+        let mut ram = Allocator::<4>::new(0x1000, 0x2000);
+        assert!(ram.reserve_top(0x400).unwrap() == 0x1C00);
+        assert!(ram.alloc(0xC00).unwrap() == 0x1000);
+        assert!(ram.alloc(0x10) == Err(Error::OutOfMemory));
+        assert!(ram.reserve_top(0x10) == Err(Error::OutOfMemory));
+        ram.unreserve_top(0x400);
+        assert!(ram.alloc(0x400).unwrap() == 0x1C00);
+    }
+}
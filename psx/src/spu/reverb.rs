@@ -0,0 +1,178 @@
+use super::{Result, Spu};
+use crate::hw::spu;
+use crate::hw::spu::reverb::{ReverbConfig, REGISTERS};
+use crate::hw::spu::{Address, VoiceFlags, Volume};
+use crate::hw::Register;
+
+// Zeros used to clear the reverb work area so old data isn't echoed.
+static ZEROS: [u32; 256] = [0; 256];
+
+/// The standard reverb presets.
+///
+/// The register values and work area sizes are the ones used by the official
+/// SDK's reverb modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReverbPreset {
+    /// Reverb is disabled and no work area is used.
+    Off,
+    /// A small room.
+    Room,
+    /// A small studio.
+    StudioSmall,
+    /// A medium studio.
+    StudioMedium,
+    /// A large studio.
+    StudioLarge,
+    /// A concert hall.
+    Hall,
+    /// A long echo with diffusion.
+    SpaceEcho,
+    /// A repeating echo with feedback.
+    Echo,
+    /// A single delayed copy without feedback.
+    Delay,
+    /// A short metallic echo, also known as half echo.
+    Pipe,
+}
+
+impl ReverbPreset {
+    /// The size of the work area in sound RAM in bytes.
+    pub const fn work_area_size(&self) -> u32 {
+        match self {
+            ReverbPreset::Off => 0,
+            ReverbPreset::Room => 0x26C0,
+            ReverbPreset::StudioSmall => 0x1F40,
+            ReverbPreset::StudioMedium => 0x4840,
+            ReverbPreset::StudioLarge => 0x6FE0,
+            ReverbPreset::Hall => 0xADE0,
+            ReverbPreset::SpaceEcho => 0xF6C0,
+            ReverbPreset::Echo | ReverbPreset::Delay => 0x1_8040,
+            ReverbPreset::Pipe => 0x3C00,
+        }
+    }
+
+    /// The values of the reverb configuration registers from dAPF1 to vRIN.
+    #[rustfmt::skip]
+    pub const fn registers(&self) -> [u16; REGISTERS] {
+        match self {
+            ReverbPreset::Off => [0; REGISTERS],
+            ReverbPreset::Room => [
+                0x007D, 0x005B, 0x6D80, 0x54B8, 0xBED0, 0x0000, 0x0000, 0xBA80,
+                0x5800, 0x5300, 0x04D6, 0x0333, 0x03F0, 0x0227, 0x0374, 0x01EF,
+                0x0334, 0x01B5, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+                0x0000, 0x0000, 0x01B4, 0x0136, 0x00B8, 0x005C, 0x8000, 0x8000,
+            ],
+            ReverbPreset::StudioSmall => [
+                0x0033, 0x0025, 0x70F0, 0x4FA8, 0xBCE0, 0x4410, 0xC0F0, 0x9C00,
+                0x5280, 0x4EC0, 0x03E4, 0x031B, 0x03A4, 0x02AF, 0x0372, 0x0266,
+                0x031C, 0x025D, 0x025C, 0x018E, 0x022F, 0x0135, 0x01D2, 0x00B7,
+                0x018F, 0x00B5, 0x00B4, 0x0080, 0x004C, 0x0026, 0x8000, 0x8000,
+            ],
+            ReverbPreset::StudioMedium => [
+                0x00B1, 0x007F, 0x70F0, 0x4FA8, 0xBCE0, 0x4510, 0xBEF0, 0xB4C0,
+                0x5280, 0x4EC0, 0x0904, 0x076B, 0x0824, 0x065F, 0x07A2, 0x0616,
+                0x076C, 0x05ED, 0x05EC, 0x042E, 0x050F, 0x0305, 0x0462, 0x02B7,
+                0x042F, 0x0265, 0x0264, 0x01B2, 0x0100, 0x0080, 0x8000, 0x8000,
+            ],
+            ReverbPreset::StudioLarge => [
+                0x00E3, 0x00A9, 0x6F60, 0x4FA8, 0xBCE0, 0x4510, 0xBEF0, 0xA680,
+                0x5680, 0x52C0, 0x0DFB, 0x0B58, 0x0D09, 0x0A3C, 0x0BD9, 0x0973,
+                0x0B59, 0x08DA, 0x08D9, 0x05E9, 0x07EC, 0x04B0, 0x06EF, 0x03D2,
+                0x05EA, 0x031D, 0x031C, 0x0238, 0x0154, 0x00AA, 0x8000, 0x8000,
+            ],
+            ReverbPreset::Hall => [
+                0x01A5, 0x0139, 0x6000, 0x5000, 0x4C00, 0xB800, 0xBC00, 0xC000,
+                0x6000, 0x5C00, 0x15BA, 0x11BB, 0x14C2, 0x10BD, 0x11BC, 0x0DC1,
+                0x11C0, 0x0DC3, 0x0DC0, 0x09C1, 0x0BC4, 0x07C1, 0x0A00, 0x06CD,
+                0x09C2, 0x05C1, 0x05C0, 0x041A, 0x0274, 0x013A, 0x8000, 0x8000,
+            ],
+            ReverbPreset::SpaceEcho => [
+                0x033D, 0x0231, 0x7E00, 0x5000, 0xB400, 0xB000, 0x4C00, 0xB000,
+                0x6000, 0x5400, 0x1ED6, 0x1A31, 0x1D14, 0x183B, 0x1BC2, 0x16B2,
+                0x1A32, 0x15EF, 0x15EE, 0x1055, 0x1334, 0x0F2D, 0x11F6, 0x0C5D,
+                0x1056, 0x0AE1, 0x0AE0, 0x07A2, 0x0464, 0x0232, 0x8000, 0x8000,
+            ],
+            ReverbPreset::Echo => [
+                0x0001, 0x0001, 0x7FFF, 0x7FFF, 0x0000, 0x0000, 0x0000, 0x8100,
+                0x0000, 0x0000, 0x1FFF, 0x0FFF, 0x1005, 0x0005, 0x0000, 0x0000,
+                0x1005, 0x0005, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+                0x0000, 0x0000, 0x1004, 0x1002, 0x0004, 0x0002, 0x8000, 0x8000,
+            ],
+            ReverbPreset::Delay => [
+                0x0001, 0x0001, 0x7FFF, 0x7FFF, 0x0000, 0x0000, 0x0000, 0x0000,
+                0x0000, 0x0000, 0x1FFF, 0x0FFF, 0x1005, 0x0005, 0x0000, 0x0000,
+                0x1005, 0x0005, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+                0x0000, 0x0000, 0x1004, 0x1002, 0x0004, 0x0002, 0x8000, 0x8000,
+            ],
+            ReverbPreset::Pipe => [
+                0x0017, 0x0013, 0x70F0, 0x4FA8, 0xBCE0, 0x4510, 0xBEF0, 0x8500,
+                0x5F80, 0x54C0, 0x0371, 0x02AF, 0x02E5, 0x01DF, 0x02B0, 0x01D7,
+                0x0358, 0x026A, 0x01D6, 0x011E, 0x012D, 0x00B1, 0x011F, 0x0059,
+                0x01A0, 0x00E3, 0x0058, 0x0040, 0x0028, 0x0014, 0x8000, 0x8000,
+            ],
+        }
+    }
+}
+
+impl Spu {
+    /// Configures the reverb unit with a preset.
+    ///
+    /// The preset's work area is reserved at the end of sound RAM with
+    /// [`Allocator::reserve_top`][super::Allocator::reserve_top] and cleared.
+    /// This fails if an allocation is in the way, in which case the previous
+    /// preset is kept.
+    pub fn set_reverb(&mut self, preset: ReverbPreset) -> Result<()> {
+        let old_size = self.reverb.work_area_size();
+        let new_size = preset.work_area_size();
+        self.ram.unreserve_top(old_size);
+        let addr = match self.ram.reserve_top(new_size) {
+            Ok(addr) => addr,
+            Err(err) => {
+                // This can't fail since the old work area was reserved
+                self.ram.reserve_top(old_size).ok();
+                return Err(err)
+            },
+        };
+        // Stop the reverb unit from writing to the work area while it changes
+        self.control.reverb(false).store();
+        self.reverb = preset;
+        if preset == ReverbPreset::Off {
+            return Ok(())
+        }
+        let mut offset = 0;
+        while offset < new_size {
+            let words = ((new_size - offset) as usize / 4).min(ZEROS.len());
+            self.write(addr + offset, &ZEROS[..words])?;
+            offset += words as u32 * 4;
+        }
+        spu::ReverbAddress::skip_load().set_address(addr).store();
+        ReverbConfig::skip_load().assign(preset.registers()).store();
+        self.control.reverb(true).store();
+        Ok(())
+    }
+
+    /// The current reverb preset.
+    pub fn reverb(&self) -> ReverbPreset {
+        self.reverb
+    }
+
+    /// Sets the reverb unit's output volume ranging from -0x8000 to 0x7FFE
+    /// where negative volumes invert the phase.
+    pub fn set_reverb_depth(&mut self, left: i16, right: i16) {
+        spu::ReverbVolumeLeft::skip_load().set_volume(left).store();
+        spu::ReverbVolumeRight::skip_load()
+            .set_volume(right)
+            .store();
+    }
+
+    /// Sends a voice's output to the reverb unit or stops sending it.
+    pub fn set_voice_reverb(&mut self, voice: usize, enabled: bool) {
+        let mut reverb = spu::ReverbMode::new();
+        if enabled {
+            reverb.set_voice(voice);
+        } else {
+            reverb.clear_voice(voice);
+        }
+        reverb.store();
+    }
+}
@@ -0,0 +1,447 @@
+use super::{Error, Result, Spu, VoiceParams, MAX_VOLUME};
+use crate::format::seq::{self, Event, Events, Seq};
+use crate::format::vab::{Tone, Vab};
+use crate::hw::spu::voice::Adsr;
+use crate::hw::spu::VOICES;
+
+const CHANNELS: usize = 16;
+
+// MIDI volumes, pans and velocities range from 0 to 127 where a pan of 64 is
+// centered.
+const MIDI_MAX: u32 = 127;
+const MIDI_CENTER: i32 = 64;
+
+const CC_DATA_ENTRY: u8 = 6;
+const CC_VOLUME: u8 = 7;
+const CC_PAN: u8 = 10;
+const CC_NRPN: u8 = 99;
+// Loops are marked with NRPN 20 followed by the loop count as data entry and
+// NRPN 30 at the end of the loop.
+const NRPN_LOOP_START: u8 = 20;
+const NRPN_LOOP_END: u8 = 30;
+const LOOP_FOREVER: u8 = 127;
+
+// The tone mode which sends a tone to the reverb unit
+const TONE_REVERB: u8 = 4;
+
+// The raw pitch of each semitone in an octave where 0x1000 is 44.1 kHz
+const SEMITONES: [u16; 13] = [
+    0x1000, 0x10F4, 0x11F6, 0x1307, 0x1429, 0x155C, 0x16A1, 0x17F9, 0x1966, 0x1AE9, 0x1C82, 0x1E34,
+    0x2000,
+];
+const FINE_STEPS: i32 = 128;
+const BEND_RANGE: i32 = 0x2000;
+const MAX_PITCH: i32 = 0x3FFF;
+
+/// A VAB bank whose VB body is in sound RAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bank<'a> {
+    /// The parsed VH header.
+    pub vab: Vab<'a>,
+    /// The address of the VB body in sound RAM.
+    pub addr: u32,
+}
+
+impl Spu {
+    /// Allocates sound RAM for a VAB bank's VB body and uploads it.
+    pub fn upload_bank<'a>(&mut self, vab: Vab<'a>, vb: &[u32]) -> Result<Bank<'a>> {
+        if ((vb.len() * 4) as u32) < vab.body_size() {
+            return Err(Error::BadSampleSize)
+        }
+        let sample = self.upload(vb, 0)?;
+        Ok(Bank {
+            vab,
+            addr: sample.addr,
+        })
+    }
+
+    /// Frees the sound RAM used by a bank. Sequences using it should be
+    /// stopped first.
+    pub fn free_bank(&mut self, bank: &Bank) -> Result<()> {
+        self.ram.free(bank.addr)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    program: u8,
+    volume: u8,
+    pan: u8,
+    bend: i16,
+    nrpn: u8,
+}
+
+impl Channel {
+    const fn new() -> Self {
+        Channel {
+            program: 0,
+            volume: MIDI_MAX as u8,
+            pan: MIDI_CENTER as u8,
+            bend: 0,
+            nrpn: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Note {
+    channel: u8,
+    note: u8,
+    velocity: u8,
+    tone: Tone,
+    // The voice's ID when it was keyed on to tell if it was stolen
+    id: u32,
+}
+
+fn note_pitch(note: u8, tone: &Tone, bend: i16) -> u16 {
+    let range = if bend < 0 {
+        tone.bend_min
+    } else {
+        tone.bend_max
+    } as i32;
+    // The distance from the center note in 1/128ths of a semitone
+    let fine = (note as i32 - tone.center as i32) * FINE_STEPS +
+        tone.fine_tune as i32 +
+        bend as i32 * range * FINE_STEPS / BEND_RANGE;
+    let octave = fine.div_euclid(12 * FINE_STEPS);
+    let fine = fine.rem_euclid(12 * FINE_STEPS);
+    let semitone = (fine / FINE_STEPS) as usize;
+    let low = SEMITONES[semitone] as i32;
+    let high = SEMITONES[semitone + 1] as i32;
+    let pitch = low + (high - low) * (fine % FINE_STEPS) / FINE_STEPS;
+    let pitch = if octave >= 0 {
+        pitch << octave.min(2)
+    } else {
+        pitch >> (-octave).min(16)
+    };
+    pitch.min(MAX_PITCH) as u16
+}
+
+/// Plays a sequence using the instruments in a [`Bank`].
+///
+/// [`Sequencer::tick`] must be called at the rate given when the sequencer is
+/// created, for example from a vblank or root counter interrupt. Several
+/// sequencers can play at the same time as long as each one is ticked.
+pub struct Sequencer<'a> {
+    bank: Bank<'a>,
+    seq: Seq<'a>,
+    events: Events<'a>,
+    pending: Option<(u32, Event)>,
+    tempo: u32,
+    tick_rate: u32,
+    // The time left over since the last event where each call to tick adds
+    // resolution * 1_000_000 and a MIDI tick takes tempo * tick_rate
+    elapsed: u64,
+    channels: [Channel; CHANNELS],
+    notes: [Option<Note>; VOICES],
+    loop_start: Option<(Events<'a>, u8)>,
+    // Set when playback jumps back to the start of the sequence or a loop
+    jumped: bool,
+    volume: u8,
+    looping: bool,
+    playing: bool,
+}
+
+impl<'a> Sequencer<'a> {
+    /// Creates a sequencer which plays `seq` when [`Sequencer::tick`] is
+    /// called `tick_rate` times per second.
+    pub fn new(bank: Bank<'a>, seq: Seq<'a>, tick_rate: u32) -> Self {
+        Sequencer {
+            bank,
+            seq,
+            events: seq.events(),
+            pending: None,
+            tempo: seq.tempo,
+            tick_rate: tick_rate.max(1),
+            elapsed: 0,
+            channels: [Channel::new(); CHANNELS],
+            notes: [None; VOICES],
+            loop_start: None,
+            jumped: false,
+            volume: MIDI_MAX as u8,
+            looping: false,
+            playing: true,
+        }
+    }
+
+    /// Restarts the sequence from the beginning when it ends.
+    pub fn set_looping(&mut self, looping: bool) -> &mut Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Sets the sequence's volume ranging from 0 to 127.
+    pub fn set_volume(&mut self, volume: u8) -> &mut Self {
+        self.volume = volume.min(MIDI_MAX as u8);
+        self
+    }
+
+    /// Checks if the sequence is still playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Stops the sequence and releases its notes.
+    pub fn stop(&mut self, spu: &mut Spu) {
+        for voice in 0..VOICES {
+            self.release(spu, voice);
+        }
+        self.playing = false;
+    }
+
+    /// Restarts the sequence from the beginning.
+    pub fn restart(&mut self, spu: &mut Spu) {
+        self.stop(spu);
+        self.events = self.seq.events();
+        self.pending = None;
+        self.tempo = self.seq.tempo;
+        self.elapsed = 0;
+        self.channels = [Channel::new(); CHANNELS];
+        self.loop_start = None;
+        self.playing = true;
+    }
+
+    /// Advances the sequence by one tick, playing any events which are due.
+    /// Returns whether the sequence is still playing.
+    ///
+    /// At most one pass through the sequence or a loop is played per tick, so
+    /// sequences which loop without advancing time play a pass each tick
+    /// instead of hanging.
+    pub fn tick(&mut self, spu: &mut Spu) -> core::result::Result<bool, seq::Error> {
+        if !self.playing {
+            return Ok(false)
+        }
+        self.elapsed += self.seq.resolution as u64 * 1_000_000;
+        let mut passes = 0;
+        loop {
+            let (wait, event) = match self.pending.take() {
+                Some(pending) => pending,
+                None => match self.events.next() {
+                    Some(res) => res?,
+                    None => (0, Event::EndOfTrack),
+                },
+            };
+            // Tempo changes only change the length of the following ticks
+            let tick_len = self.tempo.max(1) as u64 * self.tick_rate as u64;
+            let ticks = self.elapsed / tick_len;
+            if ticks < wait as u64 {
+                self.elapsed -= ticks * tick_len;
+                self.pending = Some((wait - ticks as u32, event));
+                return Ok(true)
+            }
+            self.elapsed -= wait as u64 * tick_len;
+            match event {
+                Event::Tempo(tempo) => self.tempo = tempo,
+                Event::EndOfTrack => {
+                    if !self.looping {
+                        self.stop(spu);
+                        return Ok(false)
+                    }
+                    self.events = self.seq.events();
+                    self.loop_start = None;
+                    self.jumped = true;
+                },
+                event => self.handle(spu, event),
+            }
+            if core::mem::take(&mut self.jumped) {
+                passes += 1;
+                // Continue from the start of the pass on the next tick
+                if passes > 1 {
+                    return Ok(true)
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, spu: &mut Spu, event: Event) {
+        match event {
+            Event::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => self.note_on(spu, channel, note, velocity),
+            Event::NoteOff { channel, note } => {
+                for voice in 0..VOICES {
+                    if matches!(self.notes[voice], Some(n) if n.channel == channel && n.note == note)
+                    {
+                        self.release(spu, voice);
+                    }
+                }
+            },
+            Event::ControlChange {
+                channel,
+                controller,
+                value,
+            } => self.control_change(spu, channel, value, controller),
+            Event::ProgramChange { channel, program } => {
+                self.channels[channel as usize].program = program;
+            },
+            Event::PitchBend { channel, value } => {
+                self.channels[channel as usize].bend = value;
+                for voice in 0..VOICES {
+                    if let Some(note) = self.active(spu, voice, channel) {
+                        spu.set_raw_pitch(voice, note_pitch(note.note, &note.tone, value));
+                    }
+                }
+            },
+            Event::Tempo(_) | Event::EndOfTrack | Event::Pressure => {},
+        }
+    }
+
+    fn control_change(&mut self, spu: &mut Spu, channel: u8, value: u8, controller: u8) {
+        let chan = &mut self.channels[channel as usize];
+        match controller {
+            CC_VOLUME => chan.volume = value,
+            CC_PAN => chan.pan = value,
+            CC_NRPN => {
+                chan.nrpn = value;
+                if value == NRPN_LOOP_END {
+                    self.loop_end();
+                }
+                return
+            },
+            CC_DATA_ENTRY => {
+                if chan.nrpn == NRPN_LOOP_START {
+                    self.loop_start = Some((self.events.clone(), value));
+                }
+                return
+            },
+            _ => return,
+        }
+        for voice in 0..VOICES {
+            if let Some(note) = self.active(spu, voice, channel) {
+                let (volume, pan) = self.mix(&note);
+                spu.set_volume(voice, volume, pan);
+            }
+        }
+    }
+
+    fn loop_end(&mut self) {
+        match &mut self.loop_start {
+            Some((start, count)) => {
+                // Counts of 0 and 127 loop forever
+                if *count == 0 || *count == LOOP_FOREVER {
+                    self.events = start.clone();
+                    self.jumped = true;
+                } else if *count > 1 {
+                    *count -= 1;
+                    self.events = start.clone();
+                    self.jumped = true;
+                } else {
+                    self.loop_start = None;
+                }
+            },
+            None => {},
+        }
+    }
+
+    fn note_on(&mut self, spu: &mut Spu, channel: u8, note: u8, velocity: u8) {
+        let program = self.channels[channel as usize].program;
+        for tone in self.bank.vab.tones(program).filter(|tone| tone.plays(note)) {
+            let Some((offset, _)) = self.bank.vab.vag(tone.vag as u16) else {
+                continue
+            };
+            let mut playing = Note {
+                channel,
+                note,
+                velocity,
+                tone,
+                id: 0,
+            };
+            let (volume, pan) = self.mix(&playing);
+            let params = VoiceParams {
+                addr: self.bank.addr + offset,
+                pitch: note_pitch(note, &tone, self.channels[channel as usize].bend),
+                volume,
+                pan,
+                adsr: Adsr(tone.adsr()),
+            };
+            let Some(voice) = spu.alloc_voice() else {
+                return
+            };
+            spu.set_voice_reverb(voice, tone.mode == TONE_REVERB);
+            playing.id = spu.key_on(voice, &params);
+            self.notes[voice] = Some(playing);
+        }
+    }
+
+    // Gets the note playing on a voice if it's on `channel` and the voice
+    // wasn't stolen.
+    fn active(&mut self, spu: &Spu, voice: usize, channel: u8) -> Option<Note> {
+        let note = self.notes[voice]?;
+        if note.id != spu.voice_id(voice) {
+            self.notes[voice] = None;
+            return None
+        }
+        (note.channel == channel).then_some(note)
+    }
+
+    fn release(&mut self, spu: &mut Spu, voice: usize) {
+        if let Some(note) = self.notes[voice].take() {
+            if note.id == spu.voice_id(voice) {
+                spu.stop(voice);
+            }
+        }
+    }
+
+    // Combines the volumes and pans of the note, channel, tone, program, bank
+    // and sequence.
+    fn mix(&self, note: &Note) -> (u16, i8) {
+        let chan = &self.channels[note.channel as usize];
+        let vab = &self.bank.vab;
+        let program = vab.program(chan.program);
+        let (prog_volume, prog_pan) =
+            program.map_or((MIDI_MAX as u8, MIDI_CENTER as u8), |p| (p.volume, p.pan));
+        let volumes = [
+            note.velocity,
+            chan.volume,
+            note.tone.volume,
+            prog_volume,
+            vab.volume,
+            self.volume,
+        ];
+        let volume = volumes.iter().fold(MAX_VOLUME as u32, |acc, &v| {
+            acc * (v as u32).min(MIDI_MAX) / MIDI_MAX
+        });
+        let pan: i32 = [chan.pan, note.tone.pan, prog_pan, vab.pan]
+            .iter()
+            .map(|&p| p as i32 - MIDI_CENTER)
+            .sum();
+        let pan = (pan * MIDI_MAX as i32 / MIDI_CENTER).clamp(-(MIDI_MAX as i32), MIDI_MAX as i32);
+        (volume as u16, pan as i8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::note_pitch;
+    use crate::format::vab::Tone;
+
+    const TONE: Tone = Tone {
+        priority: 0,
+        mode: 0,
+        volume: 127,
+        pan: 64,
+        center: 60,
+        fine_tune: 0,
+        min_note: 0,
+        max_note: 127,
+        bend_min: 2,
+        bend_max: 2,
+        adsr1: 0,
+        adsr2: 0,
+        program: 0,
+        vag: 1,
+    };
+
+    #[test_case]
+    fn pitch() {
+        assert!(note_pitch(60, &TONE, 0) == 0x1000);
+        assert!(note_pitch(72, &TONE, 0) == 0x2000);
+        assert!(note_pitch(48, &TONE, 0) == 0x800);
+        assert!(note_pitch(67, &TONE, 0) == 0x17F9);
+        assert!(note_pitch(96, &TONE, 0) == 0x3FFF);
+        assert!(note_pitch(60, &TONE, 0x1000) == 0x10F4);
+        assert!(note_pitch(60, &TONE, -0x2000) == 0xE41);
+    }
+}
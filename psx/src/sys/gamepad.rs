@@ -1,4 +1,7 @@
 //! Gamepad polling operations
+//!
+//! These go through the BIOS pad handler. See [`pad`][crate::pad] for a driver
+//! which talks to controllers directly.
 use crate::sys::kernel;
 use core::marker::PhantomData;
 use core::mem::size_of;
@@ -237,6 +240,13 @@ impl Buttons {
     }
 }
 
+impl From<Buttons> for crate::pad::Buttons {
+    fn from(buttons: Buttons) -> Self {
+        // The BIOS buffer's buttons are active low
+        crate::pad::Buttons::new(!buttons.value)
+    }
+}
+
 impl JoyStick {
     /// Check the joystick's horizontal offset when the `Gamepad` was polled.
     pub fn horizontal(&self) -> i8 {
@@ -0,0 +1,138 @@
+//! Root counter (timer) operations
+//!
+//! Each of the three root counters is a 16-bit counter which increments on
+//! either the system clock or a counter-specific clock. Counters can reset when
+//! they reach a target value and request an IRQ when they do.
+
+use crate::hw::timer::{timer0, timer1, timer2};
+use crate::hw::timer::{CounterMode, CounterTarget, CounterValue};
+
+pub use crate::hw::timer::{Source, SyncMode};
+
+/// The system clock's frequency in Hz.
+pub const SYSTEM_CLOCK: u32 = 33_868_800;
+
+/// A handle to a root counter represented by a triple of registers. These
+/// should be created by calling [`Timer::new`] through the type aliases in the
+/// [`timer`][`crate::timer`] module.
+pub struct Timer<V: CounterValue, M: CounterMode, T: CounterTarget> {
+    value: V,
+    mode: M,
+    target: T,
+}
+
+/// Root counter 0 which counts the system clock or the GPU's dot clock
+pub type Timer0 = Timer<timer0::Current, timer0::Mode, timer0::Target>;
+/// Root counter 1 which counts the system clock or hblanks
+pub type Timer1 = Timer<timer1::Current, timer1::Mode, timer1::Target>;
+/// Root counter 2 which counts the system clock or the system clock / 8
+pub type Timer2 = Timer<timer2::Current, timer2::Mode, timer2::Target>;
+
+impl<V: CounterValue, M: CounterMode, T: CounterTarget> Timer<V, M, T> {
+    /// Creates a handle to a root counter without changing its configuration.
+    pub fn new() -> Self {
+        Timer {
+            value: V::skip_load(),
+            mode: M::new(),
+            target: T::new(),
+        }
+    }
+
+    /// Starts counting `source` from zero until the counter overflows. Returns
+    /// `None` if the source isn't available for this counter.
+    pub fn start(&mut self, source: Source) -> Option<&mut Self> {
+        self.mode.clear_all().set_source(source)?.store();
+        Some(self)
+    }
+
+    /// Starts counting `source` from zero, resetting the counter each time it
+    /// reaches `target`. If `irq` is set, the counter's IRQ is requested each
+    /// time the target is reached. Returns `None` if the source isn't available
+    /// for this counter.
+    pub fn start_periodic(&mut self, source: Source, target: u16, irq: bool) -> Option<&mut Self> {
+        self.target.set_target(target).store();
+        self.mode
+            .clear_all()
+            .set_source(source)?
+            .reset_on_target(true)
+            .irq_on_target(irq)
+            .irq_repeat(true)
+            .store();
+        Some(self)
+    }
+
+    /// Stops the counter from requesting IRQs. This writes the counter mode,
+    /// which also resets the counter to zero.
+    pub fn disable_irq(&mut self) -> &mut Self {
+        self.mode
+            .irq_on_target(false)
+            .irq_on_overflow(false)
+            .store();
+        self
+    }
+
+    /// Synchronizes the counter with the GPU's blanking signals or stops
+    /// synchronizing if `mode` is `None`. This resets the counter to zero.
+    pub fn set_sync(&mut self, mode: Option<SyncMode>) -> &mut Self {
+        self.mode.set_sync(mode).store();
+        self
+    }
+
+    /// The clock the counter increments on.
+    pub fn source(&self) -> Source {
+        self.mode.get_source()
+    }
+
+    /// Gets the counter's current value.
+    pub fn get(&mut self) -> u16 {
+        self.value.load().get_value()
+    }
+
+    /// Sets the counter's current value.
+    pub fn set(&mut self, value: u16) -> &mut Self {
+        self.value.set_value(value).store();
+        self
+    }
+
+    /// Resets the counter to zero.
+    pub fn reset(&mut self) -> &mut Self {
+        self.set(0)
+    }
+
+    /// Gets the counter's target value.
+    pub fn target(&self) -> u16 {
+        self.target.get_target()
+    }
+
+    /// Sets the counter's target value.
+    pub fn set_target(&mut self, target: u16) -> &mut Self {
+        self.target.set_target(target).store();
+        self
+    }
+
+    /// Checks if the counter reached its target value and if it overflowed
+    /// since the flags were last read. Reading the flags clears both of them.
+    pub fn reached(&mut self) -> (bool, bool) {
+        self.mode.load();
+        (self.mode.reached_target(), self.mode.reached_overflow())
+    }
+
+    /// Checks if the counter reached its target value since the flags were
+    /// last read. This also clears the overflow flag, so use
+    /// [`Timer::reached`] to check both.
+    pub fn reached_target(&mut self) -> bool {
+        self.reached().0
+    }
+
+    /// Checks if the counter overflowed since the flags were last read. This
+    /// also clears the target flag, so use [`Timer::reached`] to check both.
+    pub fn reached_overflow(&mut self) -> bool {
+        self.reached().1
+    }
+
+    /// Blocks until the counter reaches its target value.
+    pub fn wait_target(&mut self) -> &mut Self {
+        while !self.reached_target() {}
+        self
+    }
+}
@@ -0,0 +1,417 @@
+//! STR video playback
+//!
+//! STR files interleave video sectors with XA-ADPCM audio sectors. Each frame
+//! is split across consecutive video sectors which start with a 32-byte header
+//! followed by a chunk of the frame's bitstream. [`Player`] reads the sectors,
+//! reassembles frames with an [`Assembler`], decodes their bitstreams into
+//! run-length codes for the MDEC and uploads the decoded pixels to VRAM in
+//! 16-pixel wide columns. Audio sectors are sent straight to the SPU by the
+//! CD-ROM controller.
+//!
+//! The GPU must be set up for DMA to GP0 (as
+//! [`Framebuffer`][crate::Framebuffer] does) before playing a video.
+
+use crate::cdrom::{CdRom, Msf, SectorSize};
+use crate::dma;
+use crate::format::bs;
+use crate::gpu::Vertex;
+use crate::hw::cdrom::{Command, IntCause};
+use crate::hw::gpu::{Status, GP0};
+use crate::hw::Register;
+use crate::mdec::{Depth, Mdec};
+use crate::{cdrom, mdec};
+use core::slice;
+
+type Result<T> = core::result::Result<T, Error>;
+
+/// The size of a sector's user data in words.
+pub const SECTOR_WORDS: usize = 512;
+/// The size of the frame data in each video sector in words.
+pub const CHUNK_WORDS: usize = SECTOR_WORDS - HEADER_WORDS;
+/// The maximum number of sectors in a frame.
+pub const MAX_CHUNKS: u16 = 32;
+
+// The status and type halfwords at the start of each video sector.
+const MAGIC: u32 = 0x8001_0160;
+const HEADER_WORDS: usize = 8;
+// The size of a decoded 15-bit macroblock in words.
+const MACROBLOCK_WORDS: usize = Depth::Bit15.macroblock_words();
+// The largest GPU DMA block size in words.
+const GPU_BLOCK: usize = 16;
+
+/// A video playback error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// A sector couldn't be read.
+    CdRom(cdrom::Error),
+    /// A frame couldn't be decoded by the MDEC.
+    MDEC(mdec::Error),
+    /// A frame's bitstream couldn't be decoded.
+    Bitstream(bs::Error),
+    /// A video sector's header has an invalid chunk number or count.
+    BadSector,
+    /// A frame is larger than the frame buffer.
+    FrameTooLarge,
+    /// A frame's run-length codes or pixels don't fit in the provided buffers.
+    BufferTooSmall,
+    /// The pixels couldn't be transferred to the GPU by DMA.
+    DMA(dma::Error),
+}
+
+/// The header at the start of each video sector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectorHeader {
+    /// The index of the sector within the frame.
+    pub chunk: u16,
+    /// The number of sectors in the frame.
+    pub chunks: u16,
+    /// The frame number starting from 1.
+    pub frame: u32,
+    /// The size of the frame's bitstream in bytes.
+    pub frame_size: u32,
+    /// The frame's width in pixels.
+    pub width: u16,
+    /// The frame's height in pixels.
+    pub height: u16,
+}
+
+impl SectorHeader {
+    /// Parses the header of a sector's user data or returns `None` if it's not
+    /// a video sector.
+    pub fn parse(sector: &[u32]) -> Option<Self> {
+        if sector.len() < HEADER_WORDS || sector[0] != MAGIC {
+            return None
+        }
+        Some(SectorHeader {
+            chunk: sector[1] as u16,
+            chunks: (sector[1] >> 16) as u16,
+            frame: sector[2],
+            frame_size: sector[3],
+            width: sector[4] as u16,
+            height: (sector[4] >> 16) as u16,
+        })
+    }
+}
+
+/// A complete frame's bitstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// The frame number starting from 1.
+    pub number: u32,
+    /// The frame's width in pixels.
+    pub width: u16,
+    /// The frame's height in pixels.
+    pub height: u16,
+    /// The frame's bitstream.
+    pub data: &'a [u32],
+}
+
+impl<'a> Frame<'a> {
+    /// Gets the frame's bitstream as bytes.
+    pub fn bytes(&self) -> &'a [u8] {
+        let ptr = self.data.as_ptr() as *const u8;
+        unsafe { slice::from_raw_parts(ptr, self.data.len() * 4) }
+    }
+}
+
+/// Reassembles frames from video sectors which may arrive out of order.
+///
+/// Sectors from a new frame replace any incomplete frame, so a frame with a
+/// lost sector is dropped.
+pub struct Assembler<'a> {
+    buf: &'a mut [u32],
+    header: Option<SectorHeader>,
+    received: u32,
+}
+
+impl<'a> Assembler<'a> {
+    /// Creates an assembler storing frames in `buf`.
+    pub fn new(buf: &'a mut [u32]) -> Self {
+        Assembler {
+            buf,
+            header: None,
+            received: 0,
+        }
+    }
+
+    // A mask of the chunks in a complete frame.
+    fn all_chunks(chunks: u16) -> u32 {
+        u32::MAX >> (32 - chunks as u32)
+    }
+
+    /// Adds a sector to the current frame. Returns true if this completed the
+    /// frame and false if it's incomplete or the sector isn't a video sector.
+    pub fn push(&mut self, sector: &[u32]) -> Result<bool> {
+        let header = match SectorHeader::parse(sector) {
+            Some(header) => header,
+            None => return Ok(false),
+        };
+        if header.chunks == 0 || header.chunks > MAX_CHUNKS || header.chunk >= header.chunks {
+            return Err(Error::BadSector)
+        }
+        if self.header.map(|current| current.frame) != Some(header.frame) {
+            self.header = Some(header);
+            self.received = 0;
+        }
+        let start = header.chunk as usize * CHUNK_WORDS;
+        let data = &sector[HEADER_WORDS..];
+        let dest = self
+            .buf
+            .get_mut(start..start + data.len())
+            .ok_or(Error::FrameTooLarge)?;
+        dest.copy_from_slice(data);
+        let complete = self.received == Self::all_chunks(header.chunks);
+        self.received |= 1 << header.chunk;
+        Ok(!complete && self.received == Self::all_chunks(header.chunks))
+    }
+
+    /// Gets the current frame if all of its sectors were received.
+    pub fn frame(&self) -> Option<Frame<'_>> {
+        let header = self.header?;
+        if self.received != Self::all_chunks(header.chunks) {
+            return None
+        }
+        let words = (header.frame_size as usize + 3) / 4;
+        let len = words.min(header.chunks as usize * CHUNK_WORDS);
+        Some(Frame {
+            number: header.frame,
+            width: header.width,
+            height: header.height,
+            data: &self.buf[..len],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Stopped,
+    Reading,
+    Pausing,
+}
+
+/// Plays STR videos from the CD-ROM.
+pub struct Player<'a> {
+    cd: &'a mut CdRom,
+    mdec: &'a mut Mdec,
+    cd_dma: dma::CDROM,
+    gpu_dma: dma::GPU,
+    gp0: GP0,
+    sector: [u32; SECTOR_WORDS],
+    frames: Assembler<'a>,
+    rle: &'a mut [u32],
+    slice: &'a mut [u32],
+    origin: Vertex,
+    remaining: u32,
+    state: State,
+}
+
+impl<'a> Player<'a> {
+    /// Creates a video player which reassembles frames in `frame`, decodes
+    /// their bitstreams into `rle` and decodes one 16-pixel wide column of
+    /// pixels at a time into `slice`.
+    ///
+    /// `slice` must hold 128 words per 16 pixels of the video's height.
+    pub fn new(
+        cd: &'a mut CdRom, mdec: &'a mut Mdec, frame: &'a mut [u32], rle: &'a mut [u32],
+        slice: &'a mut [u32],
+    ) -> Self {
+        Player {
+            cd,
+            mdec,
+            cd_dma: dma::CDROM::new(),
+            gpu_dma: dma::GPU::new(),
+            gp0: GP0::skip_load(),
+            sector: [0; SECTOR_WORDS],
+            frames: Assembler::new(frame),
+            rle,
+            slice,
+            origin: Vertex(0, 0),
+            remaining: 0,
+            state: State::Stopped,
+        }
+    }
+
+    /// Sets where in VRAM the top-left corner of each frame is uploaded.
+    pub fn set_origin(&mut self, origin: Vertex) -> &mut Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Starts playing the `sectors` sectors of an STR file starting at `lba`.
+    /// If `audio` is a file and channel number, the matching XA-ADPCM sectors
+    /// are played alongside the video.
+    pub fn play(&mut self, lba: u32, sectors: u32, audio: Option<(u8, u8)>) -> Result<()> {
+        let mode = self
+            .cd
+            .mode()
+            .sector_size(SectorSize::Bytes2048)
+            .xa_adpcm(false)
+            .xa_filter(false)
+            .double_speed(true);
+        self.cd.set_mode(mode).map_err(Error::CdRom)?;
+        match audio {
+            Some((file, channel)) => {
+                self.cd.play_xa(lba, file, channel).map_err(Error::CdRom)?;
+            },
+            None => {
+                self.cd.set_loc(Msf::from_lba(lba)).map_err(Error::CdRom)?;
+                self.cd.read_s().map_err(Error::CdRom)?;
+            },
+        }
+        self.remaining = sectors;
+        self.state = State::Reading;
+        Ok(())
+    }
+
+    /// Checks if the video finished playing or was stopped.
+    pub fn done(&self) -> bool {
+        self.state == State::Stopped
+    }
+
+    /// Checks if the controller has signalled anything, reading the next
+    /// sector if one is ready. If the sector completes a frame, the frame is
+    /// decoded and uploaded to VRAM and its number is returned.
+    pub fn poll(&mut self) -> Result<Option<u32>> {
+        if self.state == State::Stopped {
+            return Ok(None)
+        }
+        let response = match self.cd.poll() {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        match (self.state, response.cause) {
+            (State::Reading, IntCause::DataReady) => {
+                self.cd
+                    .receive_data(&mut self.cd_dma, &mut self.sector)
+                    .map_err(Error::CdRom)?;
+                self.remaining = self.remaining.saturating_sub(1);
+                if self.remaining == 0 {
+                    self.cd.send(Command::Pause, &[]);
+                    self.state = State::Pausing;
+                }
+                if self.frames.push(&self.sector)? {
+                    return self.upload().map(Some)
+                }
+                Ok(None)
+            },
+            // Sectors may still arrive before the pause takes effect
+            (State::Pausing, IntCause::DataReady | IntCause::Acknowledge) => Ok(None),
+            (State::Pausing, IntCause::Complete) => {
+                self.state = State::Stopped;
+                Ok(None)
+            },
+            (_, IntCause::DiskError) => {
+                self.state = State::Stopped;
+                Err(Error::CdRom(response.error()))
+            },
+            (_, other) => Err(Error::CdRom(cdrom::Error::UnexpectedInterrupt(other))),
+        }
+    }
+
+    // Decodes the current frame and uploads it to VRAM.
+    fn upload(&mut self) -> Result<u32> {
+        // This is only called once a frame is complete
+        let frame = self.frames.frame().unwrap();
+        let words = bs::decode(frame.bytes(), frame.width, frame.height, self.rle)
+            .map_err(Error::Bitstream)?;
+        let rows = (frame.height as usize + 15) / 16;
+        let columns = (frame.width as usize + 15) / 16;
+        let slice = self
+            .slice
+            .get_mut(..rows * MACROBLOCK_WORDS)
+            .ok_or(Error::BufferTooSmall)?;
+        let Vertex(x, y) = self.origin;
+        let size = Vertex(16, rows as i16 * 16);
+        let gp0 = &mut self.gp0;
+        let gpu_dma = &mut self.gpu_dma;
+        let mut res = Ok(());
+        self.mdec
+            .decode_slices(
+                &self.rle[..words],
+                slice,
+                columns,
+                Depth::Bit15,
+                |i, pixels| {
+                    Status::new().wait_cmd().wait_dma();
+                    gp0.copy_to_vram(Vertex(x + 16 * i as i16, y), size);
+                    let blocks = pixels.len() / GPU_BLOCK;
+                    gpu_dma.setup_send_blocks();
+                    if let Err(err) = gpu_dma.send_blocks_and(pixels, blocks, || ()) {
+                        res = Err(Error::DMA(err));
+                    }
+                },
+            )
+            .map_err(Error::MDEC)?;
+        res.map(|()| frame.number)
+    }
+
+    /// Stops reading sectors and waits for the drive to pause.
+    pub fn stop(&mut self) -> Result<()> {
+        match self.state {
+            State::Reading => {
+                self.cd.pause().map_err(Error::CdRom)?;
+            },
+            // The pause was already sent, so wait for it to complete
+            State::Pausing => {
+                while self.state == State::Pausing {
+                    self.poll()?;
+                }
+            },
+            State::Stopped => {},
+        }
+        self.state = State::Stopped;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Assembler, Error, SectorHeader, CHUNK_WORDS, MAGIC, SECTOR_WORDS};
+
+    fn sector(chunk: u16, chunks: u16, frame: u32) -> [u32; SECTOR_WORDS] {
+        let mut sector = [chunk as u32 + 1; SECTOR_WORDS];
+        sector[..8].copy_from_slice(&[
+            MAGIC,
+            chunk as u32 | (chunks as u32) << 16,
+            frame,
+            8 * CHUNK_WORDS as u32,
+            320 | 240 << 16,
+            0,
+            0,
+            0,
+        ]);
+        sector
+    }
+
+    #[test_case]
+    fn header() {
+        let header = SectorHeader::parse(&sector(1, 3, 7)).unwrap();
+        assert!(header.chunk == 1);
+        assert!(header.chunks == 3);
+        assert!(header.frame == 7);
+        assert!(header.width == 320);
+        assert!(header.height == 240);
+        assert!(SectorHeader::parse(&[0; SECTOR_WORDS]).is_none());
+    }
+
+    #[test_case]
+    fn assemble() {
+        let mut buf = [0; 2 * CHUNK_WORDS];
+        let mut frames = Assembler::new(&mut buf);
+        assert!(frames.push(&sector(1, 2, 1)) == Ok(false));
+        assert!(frames.frame().is_none());
+        assert!(frames.push(&sector(0, 2, 1)) == Ok(true));
+        // Duplicate sectors don't complete the frame again
+        assert!(frames.push(&sector(0, 2, 1)) == Ok(false));
+        let frame = frames.frame().unwrap();
+        assert!(frame.number == 1);
+        assert!(frame.data.len() == 2 * CHUNK_WORDS);
+        assert!(frame.data[0] == 1 && frame.data[CHUNK_WORDS] == 2);
+        // A new frame drops the incomplete one
+        assert!(frames.push(&sector(0, 2, 2)) == Ok(false));
+        assert!(frames.push(&sector(1, 2, 3)) == Ok(false));
+        assert!(frames.push(&sector(0, 2, 3)) == Ok(true));
+        assert!(frames.push(&sector(2, 3, 4)) == Err(Error::FrameTooLarge));
+        assert!(frames.push(&sector(2, 2, 4)) == Err(Error::BadSector));
+    }
+}
@@ -1,14 +1,37 @@
 use std::env::args;
-use std::f64::consts::FRAC_PI_8;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_8, PI};
 use std::fs;
 
+// The number of entries in the sine table, excluding the entry for π/2.
+const SINE_TABLE_SIZE: u16 = 1024;
+// The number of entries in the arctangent table, excluding the entry for 1.0.
+const ATAN_TABLE_SIZE: u16 = 1024;
+
+// Appends the entries to `table` in lines of up to 100 characters.
+fn push_entries<I: Iterator<Item = String>>(table: &mut String, entries: I) {
+    let mut line_length = 0;
+    for entry in entries {
+        let table_entry = format!(" {},", entry);
+        if line_length + table_entry.len() > 100 {
+            line_length = 0;
+        }
+        if line_length == 0 {
+            *table += "\n";
+            *table += "   ";
+            line_length += 4;
+        }
+        *table += &table_entry;
+        line_length += table_entry.len();
+    }
+    *table += "\n];\n";
+}
+
 fn main() {
     let table_size = match args().skip(1).next() {
         Some(arg) => arg.parse::<u16>().expect("Unable to parse u16 argument"),
         None => 16,
     } * 1024;
 
-
     let cos_idx_fn = "pub fn cosine_table(idx: usize) -> f16 {\
          \n    if idx == 0 {\
          \n        f16(0x1_000)\
@@ -24,24 +47,39 @@ fn main() {
          const COSINE_TABLE: [u8; COSINE_TABLE_SIZE] = [",
         cos_idx_fn, table_size,
     );
-    let mut line_length = 0;
-    for x in 0..table_size {
+    let cos_entries = (0..table_size).map(|x| {
         let radians = f64::from(x * (16 * 1024 / table_size)) * FRAC_PI_8 / 4096.0;
         let float = f64::cos(radians);
         let fixed = (float * 4096.0).trunc() as i16;
-        let table_entry = format!(" {:?},", ((fixed as u16) >> 4) as u8);
-        if line_length + table_entry.len() > 100 {
-            line_length = 0;
-        }
-        if line_length == 0 {
-            cosine_table += "\n";
-            cosine_table += "   ";
-            line_length += 4;
-        }
-        cosine_table += &table_entry;
-        line_length += table_entry.len();
-    }
-    cosine_table += "\n];\n";
+        format!("{:?}", ((fixed as u16) >> 4) as u8)
+    });
+    push_entries(&mut cosine_table, cos_entries);
+
+    // The sine of angles from 0 to π/2 in 1.3.12 fixed-point
+    cosine_table += &format!(
+        "\nconst SINE_TABLE_SIZE: usize = {};\n\
+         const SINE_TABLE: [u16; SINE_TABLE_SIZE + 1] = [",
+        SINE_TABLE_SIZE,
+    );
+    let sine_entries = (0..=SINE_TABLE_SIZE).map(|x| {
+        let radians = f64::from(x) * FRAC_PI_2 / f64::from(SINE_TABLE_SIZE);
+        format!("{:?}", (f64::sin(radians) * 4096.0).round() as u16)
+    });
+    push_entries(&mut cosine_table, sine_entries);
+
+    // The arctangent of ratios from 0.0 to 1.0 in `Rad` units
+    cosine_table += &format!(
+        "\nconst ATAN_TABLE_SIZE: usize = {};\n\
+         const ATAN_TABLE: [u16; ATAN_TABLE_SIZE + 1] = [",
+        ATAN_TABLE_SIZE,
+    );
+    let atan_entries = (0..=ATAN_TABLE_SIZE).map(|x| {
+        let ratio = f64::from(x) / f64::from(ATAN_TABLE_SIZE);
+        let angle = f64::atan(ratio) * 32768.0 / PI;
+        format!("{:?}", angle.round() as u16)
+    });
+    push_entries(&mut cosine_table, atan_entries);
+
     let cos_table_file = "trig.rs";
     fs::write(cos_table_file, cosine_table)
         .unwrap_or_else(|_| panic!("Unable to write to {}", cos_table_file));